@@ -0,0 +1,535 @@
+/* Call frame information
+ * .eh_frame and .debug_frame say, for every instruction of a function, how
+ * to find its caller: the CFA (the stack pointer before the call) as a
+ * register plus offset or an expression, and where each of the caller's
+ * registers was saved relative to it. CIEs hold what functions share, FDEs
+ * the instructions for one address range.
+ */
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::lines::Reader;
+
+/// Registers we unwind, by DWARF number. The return address is kept as
+/// `rip`, the caller's PC.
+pub const REG_COUNT: usize = 17;
+pub const REG_NAMES: [&str; REG_COUNT] = [
+    "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rip",
+];
+pub const DW_RBP: usize = 6;
+pub const DW_RSP: usize = 7;
+pub const DW_RA: usize = 16;
+
+/* DW_EH_PE_* pointer encodings */
+const DW_EH_PE_OMIT: u8 = 0xff;
+const DW_EH_PE_PCREL: u8 = 0x10;
+
+/* Most operations a single expression may run, backward branches included */
+const MAX_EXPR_OPS: usize = 1000;
+
+/// How to compute the CFA
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfaRule {
+    RegOffset(usize, i64),
+    Expr(Vec<u8>),
+}
+
+/// Where a caller's register is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegRule {
+    Undefined,
+    SameValue,
+    /// Saved at CFA + n
+    Offset(i64),
+    /// Is CFA + n
+    ValOffset(i64),
+    /// In another register
+    Register(usize),
+    /// Saved at the address the expression computes
+    Expr(Vec<u8>),
+    /// Is what the expression computes
+    ValExpr(Vec<u8>),
+}
+
+/// Rules for one address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnwindRow {
+    pub cfa: CfaRule,
+    pub regs: Vec<RegRule>,
+    /// The function is a signal trampoline (augmentation `S`)
+    pub signal: bool,
+}
+
+impl UnwindRow {
+    fn initial() -> UnwindRow {
+        // Callee saved registers are left alone unless the CFI says so, and
+        // the caller's stack pointer is the CFA
+        let regs = (0..REG_COUNT)
+            .map(|reg| match reg {
+                3 | DW_RBP | 12..=15 => RegRule::SameValue,
+                DW_RSP => RegRule::ValOffset(0),
+                _ => RegRule::Undefined,
+            })
+            .collect();
+        UnwindRow { cfa: CfaRule::RegOffset(DW_RSP, 8), regs, signal: false }
+    }
+}
+
+struct Cie {
+    code_align: u64,
+    data_align: i64,
+    ra_reg: u64,
+    fde_encoding: u8,
+    /// Augmentation `z`, FDEs have augmentation data to skip
+    augmented: bool,
+    signal: bool,
+    initial: Vec<u8>,
+}
+
+struct Fde {
+    start: u64,
+    end: u64,
+    cie: usize,
+    instructions: Vec<u8>,
+}
+
+/// The CFI of one file, addressed by offset from its module base
+#[derive(Default)]
+pub struct CallFrameTable {
+    /// Link time address of the module base
+    load: u64,
+    cies: Vec<Cie>,
+    /// Sorted by start address
+    fdes: Vec<Fde>,
+}
+
+/* Pointer encoded as `enc`, `addr` being the address of the section data */
+fn read_encoded(r: &mut Reader, enc: u8, addr: u64) -> Option<u64> {
+    if enc == DW_EH_PE_OMIT { return Some(0) }
+    let field = addr.wrapping_add(r.pos as u64);
+    let val = match enc & 0x0f {
+        0x00 | 0x04 | 0x0c => r.u64()?,
+        0x01 => r.uleb()?,
+        0x02 => u64::from(r.u16()?),
+        0x03 => u64::from(r.u32()?),
+        0x09 => r.sleb()? as u64,
+        0x0a => r.u16()? as i16 as u64,
+        0x0b => r.u32()? as i32 as u64,
+        _ => return None,
+    };
+    // Only absolute and PC relative pointers are used on x86-64
+    match enc & 0x70 {
+        0 => Some(val),
+        DW_EH_PE_PCREL => Some(field.wrapping_add(val)),
+        _ => None,
+    }
+}
+
+fn parse_cie(r: &mut Reader, end: usize, addr: u64) -> Option<Cie> {
+    let version = r.u8()?;
+    let augmentation = r.cstr()?;
+    if augmentation.contains("eh") { r.u64()?; }
+    if version >= 4 {
+        r.u8()?;  // address_size
+        r.u8()?;  // segment_selector_size
+    }
+    let code_align = r.uleb()?;
+    let data_align = r.sleb()?;
+    let ra_reg = if version == 1 { u64::from(r.u8()?) } else { r.uleb()? };
+
+    let mut cie = Cie {
+        code_align,
+        data_align,
+        ra_reg,
+        fde_encoding: 0,
+        augmented: false,
+        signal: false,
+        initial: Vec::new(),
+    };
+    if let Some(rest) = augmentation.strip_prefix('z') {
+        cie.augmented = true;
+        let len = r.uleb()? as usize;
+        let data_end = r.pos.checked_add(len)?;
+        for c in rest.chars() {
+            match c {
+                'L' => { r.u8()?; },
+                'P' => {
+                    let enc = r.u8()?;
+                    read_encoded(r, enc & 0x7f, addr)?;
+                },
+                'R' => cie.fde_encoding = r.u8()?,
+                'S' => cie.signal = true,
+                _ => break,
+            }
+        }
+        r.pos = data_end;
+    }
+    cie.initial = r.data.get(r.pos..end)?.to_vec();
+    Some(cie)
+}
+
+fn parse_fde(r: &mut Reader, end: usize, addr: u64, cie: &Cie, idx: usize, eh_frame: bool) -> Option<Fde> {
+    let enc = if eh_frame { cie.fde_encoding } else { 0 };
+    let start = read_encoded(r, enc, addr)?;
+    // The length is never relative
+    let len = read_encoded(r, enc & 0x0f, addr)?;
+    if cie.augmented {
+        let skip = r.uleb()? as usize;
+        r.bytes(skip)?;
+    }
+    Some(Fde {
+        start,
+        end: start.wrapping_add(len),
+        cie: idx,
+        instructions: r.data.get(r.pos..end)?.to_vec(),
+    })
+}
+
+/* Set the rule of a register, ignoring the ones we don't unwind */
+fn set_rule(row: &mut UnwindRow, cie: &Cie, reg: u64, rule: RegRule) {
+    let reg = if reg == cie.ra_reg { DW_RA as u64 } else { reg };
+    if let Some(slot) = row.regs.get_mut(reg as usize) {
+        *slot = rule;
+    }
+}
+
+fn restore_rule(row: &mut UnwindRow, initial: &UnwindRow, cie: &Cie, reg: u64) {
+    let reg = if reg == cie.ra_reg { DW_RA as u64 } else { reg };
+    if let Some(rule) = initial.regs.get(reg as usize) {
+        row.regs[reg as usize] = rule.clone();
+    }
+}
+
+/* Run CFA instructions starting at `loc` until the row for `target` */
+fn execute(cie: &Cie, insns: &[u8], mut loc: u64, target: u64,
+           row: &mut UnwindRow, initial: &UnwindRow) -> Option<()> {
+    let mut r = Reader { data: insns, pos: 0 };
+    let mut saved: Vec<UnwindRow> = Vec::new();
+    let (ca, da) = (cie.code_align, cie.data_align);
+
+    while r.pos < insns.len() {
+        let op = r.u8()?;
+        let mut next = None;
+        match op & 0xc0 {
+            0x40 => next = Some(loc.wrapping_add(u64::from(op & 0x3f) * ca)),
+            0x80 => {
+                let off = r.uleb()? as i64 * da;
+                set_rule(row, cie, u64::from(op & 0x3f), RegRule::Offset(off));
+            },
+            0xc0 => restore_rule(row, initial, cie, u64::from(op & 0x3f)),
+            _ => match op {
+                0x00 => {},
+                0x01 => next = Some(r.u64()?),
+                0x02 => next = Some(loc.wrapping_add(u64::from(r.u8()?) * ca)),
+                0x03 => next = Some(loc.wrapping_add(u64::from(r.u16()?) * ca)),
+                0x04 => next = Some(loc.wrapping_add(u64::from(r.u32()?) * ca)),
+                0x05 => {
+                    let reg = r.uleb()?;
+                    let off = r.uleb()? as i64 * da;
+                    set_rule(row, cie, reg, RegRule::Offset(off));
+                },
+                0x06 => { let reg = r.uleb()?; restore_rule(row, initial, cie, reg); },
+                0x07 => { let reg = r.uleb()?; set_rule(row, cie, reg, RegRule::Undefined); },
+                0x08 => { let reg = r.uleb()?; set_rule(row, cie, reg, RegRule::SameValue); },
+                0x09 => {
+                    let reg = r.uleb()?;
+                    let other = r.uleb()? as usize;
+                    let rule = if other < REG_COUNT { RegRule::Register(other) } else { RegRule::Undefined };
+                    set_rule(row, cie, reg, rule);
+                },
+                0x0a => saved.push(row.clone()),
+                0x0b => *row = saved.pop()?,
+                0x0c => {
+                    let reg = r.uleb()? as usize;
+                    row.cfa = CfaRule::RegOffset(reg, r.uleb()? as i64);
+                },
+                0x0d => {
+                    let reg = r.uleb()? as usize;
+                    let off = match row.cfa { CfaRule::RegOffset(_, off) => off, _ => 0 };
+                    row.cfa = CfaRule::RegOffset(reg, off);
+                },
+                0x0e => {
+                    let off = r.uleb()? as i64;
+                    if let CfaRule::RegOffset(_, cur) = &mut row.cfa { *cur = off; }
+                },
+                0x0f => {
+                    let len = r.uleb()? as usize;
+                    row.cfa = CfaRule::Expr(r.bytes(len)?.to_vec());
+                },
+                0x10 | 0x16 => {
+                    let reg = r.uleb()?;
+                    let len = r.uleb()? as usize;
+                    let expr = r.bytes(len)?.to_vec();
+                    let rule = if op == 0x10 { RegRule::Expr(expr) } else { RegRule::ValExpr(expr) };
+                    set_rule(row, cie, reg, rule);
+                },
+                0x11 => {
+                    let reg = r.uleb()?;
+                    let off = r.sleb()? * da;
+                    set_rule(row, cie, reg, RegRule::Offset(off));
+                },
+                0x12 => {
+                    let reg = r.uleb()? as usize;
+                    row.cfa = CfaRule::RegOffset(reg, r.sleb()? * da);
+                },
+                0x13 => {
+                    let off = r.sleb()? * da;
+                    if let CfaRule::RegOffset(_, cur) = &mut row.cfa { *cur = off; }
+                },
+                0x14 => {
+                    let reg = r.uleb()?;
+                    let off = r.uleb()? as i64 * da;
+                    set_rule(row, cie, reg, RegRule::ValOffset(off));
+                },
+                0x15 => {
+                    let reg = r.uleb()?;
+                    let off = r.sleb()? * da;
+                    set_rule(row, cie, reg, RegRule::ValOffset(off));
+                },
+                // DW_CFA_GNU_args_size
+                0x2e => { r.uleb()?; },
+                // DW_CFA_GNU_negative_offset_extended
+                0x2f => {
+                    let reg = r.uleb()?;
+                    let off = -(r.uleb()? as i64) * da;
+                    set_rule(row, cie, reg, RegRule::Offset(off));
+                },
+                _ => return None,
+            },
+        }
+
+        if let Some(next) = next {
+            if next > target { break }
+            loc = next;
+        }
+    }
+    Some(())
+}
+
+impl CallFrameTable {
+    /// `load` is the link time address of the module base
+    pub fn new(load: u64) -> CallFrameTable {
+        CallFrameTable { load, ..CallFrameTable::default() }
+    }
+
+    /// Add the entries of an .eh_frame or .debug_frame section whose data
+    /// is at link time address `addr`
+    pub fn add_section(&mut self, data: &[u8], addr: u64, eh_frame: bool) {
+        // CIE index by section offset
+        let mut cies: HashMap<u64, usize> = HashMap::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let mut r = Reader { data, pos };
+            let (length, dwarf64) = match r.u32() {
+                Some(0xffff_ffff) => match r.u64() {
+                    Some(len) => (len, true),
+                    None => break,
+                },
+                Some(len) => (u64::from(len), false),
+                None => break,
+            };
+            // A zero length terminates .eh_frame
+            if length == 0 {
+                if eh_frame { break }
+                pos = r.pos;
+                continue;
+            }
+            let end = match r.pos.checked_add(length as usize) {
+                Some(end) if end <= data.len() => end,
+                _ => break,
+            };
+
+            let id_pos = r.pos as u64;
+            let id = match r.offset(dwarf64) {
+                Some(id) => id,
+                None => break,
+            };
+            let is_cie = match (eh_frame, dwarf64) {
+                (true, _) => id == 0,
+                (false, true) => id == !0,
+                (false, false) => id == 0xffff_ffff,
+            };
+
+            if is_cie {
+                if let Some(cie) = parse_cie(&mut r, end, addr) {
+                    cies.insert(pos as u64, self.cies.len());
+                    self.cies.push(cie);
+                }
+            } else {
+                // .eh_frame points back from the pointer, .debug_frame
+                // gives the section offset
+                let cie_pos = if eh_frame { id_pos.checked_sub(id) } else { Some(id) };
+                if let Some(&idx) = cie_pos.and_then(|p| cies.get(&p)) {
+                    match parse_fde(&mut r, end, addr, &self.cies[idx], idx, eh_frame) {
+                        Some(fde) if fde.start != 0 => self.fdes.push(fde),
+                        _ => {},
+                    }
+                }
+            }
+            pos = end;
+        }
+        self.fdes.sort_by_key(|fde| fde.start);
+    }
+
+    pub fn len(&self) -> usize { self.fdes.len() }
+    pub fn is_empty(&self) -> bool { self.fdes.is_empty() }
+
+    /// Unwind rules at `offset` from the module base
+    pub fn row(&self, offset: u64) -> Option<UnwindRow> {
+        let addr = offset.wrapping_add(self.load);
+        let end = self.fdes
+            .binary_search_by(|fde| if fde.start <= addr { Ordering::Less } else { Ordering::Greater })
+            .unwrap_err();
+        let fde = self.fdes[..end].iter().rev().take(8).find(|fde| addr < fde.end)?;
+        let cie = &self.cies[fde.cie];
+
+        let defaults = UnwindRow::initial();
+        let mut initial = defaults.clone();
+        initial.signal = cie.signal;
+        execute(cie, &cie.initial, fde.start, !0, &mut initial, &defaults)?;
+        let mut row = initial.clone();
+        execute(cie, &fde.instructions, fde.start, addr, &mut row, &initial)?;
+        Some(row)
+    }
+}
+
+fn binary(stack: &mut Vec<u64>, f: impl Fn(u64, u64) -> Option<u64>) -> Option<()> {
+    let b = stack.pop()?;
+    let a = stack.pop()?;
+    stack.push(f(a, b)?);
+    Some(())
+}
+
+/// Evaluate a DWARF expression from a CFI rule. `regs` are the frame's
+/// registers by DWARF number, `push` goes on the stack first (the CFA for
+/// register rules) and `read` fetches 8 bytes of memory.
+pub fn eval_expression(expr: &[u8], regs: &[Option<u64>], push: Option<u64>,
+                       read: &dyn Fn(u64) -> Option<u64>) -> Option<u64> {
+    let mut r = Reader { data: expr, pos: 0 };
+    let mut stack: Vec<u64> = push.into_iter().collect();
+    let reg = |num: u64| regs.get(num as usize).and_then(|v| *v);
+    let cmp = |stack: &mut Vec<u64>, f: fn(&i64, &i64) -> bool| {
+        binary(stack, |a, b| Some(f(&(a as i64), &(b as i64)) as u64))
+    };
+
+    let mut ops = 0;
+    while r.pos < expr.len() {
+        ops += 1;
+        if ops > MAX_EXPR_OPS { return None }
+        let op = r.u8()?;
+        match op {
+            0x30..=0x4f => stack.push(u64::from(op - 0x30)),
+            0x50..=0x6f => stack.push(reg(u64::from(op - 0x50))?),
+            0x70..=0x8f => {
+                let off = r.sleb()?;
+                stack.push(reg(u64::from(op - 0x70))?.wrapping_add(off as u64));
+            },
+            0x92 => {
+                let num = r.uleb()?;
+                let off = r.sleb()?;
+                stack.push(reg(num)?.wrapping_add(off as u64));
+            },
+            0x08 => stack.push(u64::from(r.u8()?)),
+            0x09 => stack.push(r.u8()? as i8 as u64),
+            0x0a => stack.push(u64::from(r.u16()?)),
+            0x0b => stack.push(r.u16()? as i16 as u64),
+            0x0c => stack.push(u64::from(r.u32()?)),
+            0x0d => stack.push(r.u32()? as i32 as u64),
+            0x0e | 0x0f => stack.push(r.u64()?),
+            0x10 => stack.push(r.uleb()?),
+            0x11 => stack.push(r.sleb()? as u64),
+            0x06 => {
+                let addr = stack.pop()?;
+                stack.push(read(addr)?);
+            },
+            0x12 => stack.push(*stack.last()?),
+            0x13 => { stack.pop()?; },
+            0x14 => stack.push(*stack.get(stack.len().checked_sub(2)?)?),
+            0x15 => {
+                let idx = r.u8()? as usize;
+                stack.push(*stack.get(stack.len().checked_sub(idx + 1)?)?);
+            },
+            0x16 => {
+                let len = stack.len();
+                if len < 2 { return None }
+                stack.swap(len - 1, len - 2);
+            },
+            0x17 => {
+                let len = stack.len();
+                if len < 3 { return None }
+                stack[len - 3..].rotate_right(1);
+            },
+            0x19 => {
+                let v = stack.pop()? as i64;
+                stack.push(v.wrapping_abs() as u64);
+            },
+            0x1a => binary(&mut stack, |a, b| Some(a & b))?,
+            0x1b => binary(&mut stack, |a, b| (a as i64).checked_div(b as i64).map(|v| v as u64))?,
+            0x1c => binary(&mut stack, |a, b| Some(a.wrapping_sub(b)))?,
+            0x1d => binary(&mut stack, |a, b| a.checked_rem(b))?,
+            0x1e => binary(&mut stack, |a, b| Some(a.wrapping_mul(b)))?,
+            0x1f => {
+                let v = stack.pop()? as i64;
+                stack.push(v.wrapping_neg() as u64);
+            },
+            0x20 => {
+                let v = stack.pop()?;
+                stack.push(!v);
+            },
+            0x21 => binary(&mut stack, |a, b| Some(a | b))?,
+            0x22 => binary(&mut stack, |a, b| Some(a.wrapping_add(b)))?,
+            0x23 => {
+                let v = stack.pop()?;
+                stack.push(v.wrapping_add(r.uleb()?));
+            },
+            0x24 => binary(&mut stack, |a, b| Some(a.checked_shl(b as u32).unwrap_or(0)))?,
+            0x25 => binary(&mut stack, |a, b| Some(a.checked_shr(b as u32).unwrap_or(0)))?,
+            0x26 => binary(&mut stack, |a, b| Some(((a as i64) >> std::cmp::min(b, 63)) as u64))?,
+            0x27 => binary(&mut stack, |a, b| Some(a ^ b))?,
+            0x29 => cmp(&mut stack, i64::eq)?,
+            0x2a => cmp(&mut stack, i64::ge)?,
+            0x2b => cmp(&mut stack, i64::gt)?,
+            0x2c => cmp(&mut stack, i64::le)?,
+            0x2d => cmp(&mut stack, i64::lt)?,
+            0x2e => cmp(&mut stack, i64::ne)?,
+            0x28 | 0x2f => {
+                let off = i64::from(r.u16()? as i16);
+                let taken = op == 0x2f || stack.pop()? != 0;
+                if taken {
+                    let pos = r.pos as i64 + off;
+                    if pos < 0 || pos as usize > expr.len() { return None }
+                    r.pos = pos as usize;
+                }
+            },
+            0x96 => {},
+            _ => return None,
+        }
+    }
+    stack.pop()
+}
+
+/// The caller's registers from a frame's registers and its unwind row, and
+/// the frame's CFA. `read` fetches 8 bytes of memory.
+pub fn unwind_regs(row: &UnwindRow, regs: &[Option<u64>; REG_COUNT],
+                   read: &dyn Fn(u64) -> Option<u64>) -> Option<(u64, [Option<u64>; REG_COUNT])> {
+    let cfa = match &row.cfa {
+        CfaRule::RegOffset(reg, off) => regs.get(*reg).and_then(|v| *v)?.wrapping_add(*off as u64),
+        CfaRule::Expr(expr) => eval_expression(expr, regs, None, read)?,
+    };
+
+    let mut caller = [None; REG_COUNT];
+    for (num, rule) in row.regs.iter().enumerate() {
+        caller[num] = match rule {
+            RegRule::Undefined => None,
+            RegRule::SameValue => regs[num],
+            RegRule::Offset(off) => read(cfa.wrapping_add(*off as u64)),
+            RegRule::ValOffset(off) => Some(cfa.wrapping_add(*off as u64)),
+            RegRule::Register(other) => regs[*other],
+            RegRule::Expr(expr) => eval_expression(expr, regs, Some(cfa), read).and_then(read),
+            RegRule::ValExpr(expr) => eval_expression(expr, regs, Some(cfa), read),
+        };
+    }
+    Some((cfa, caller))
+}
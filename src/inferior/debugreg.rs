@@ -0,0 +1,282 @@
+/* Hardware breakpoints and watchpoints
+ * Programmed through the x86 debug registers in `struct user.u_debugreg`.
+ *   DR0-DR3: addresses
+ *   DR6: status, bits 0-3 tell which slot fired
+ *   DR7: control, local enable bits plus RW/LEN fields per slot
+ * Nothing is written into the inferior's code.
+ */
+
+use nix::unistd::Pid;
+
+use super::ffi;
+use super::expr::ExprContext;
+use super::{ BreakpointAction, Inferior, InferiorState };
+
+/// offsetof(struct user, u_debugreg) on x86_64
+const DEBUGREG_OFFSET: usize = 848;
+
+/// Number of address slots (DR0-DR3)
+pub const HW_SLOTS: usize = 4;
+
+/// DR6 bits that report which slot fired
+const DR6_SLOT_MASK: u64 = 0xf;
+
+/// What a hardware slot traps on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HwKind {
+    /// `hbreak`, instruction fetch
+    Execute,
+    /// `watch`, data writes
+    Write,
+    /// `rwatch`, data reads. x86 can't trap on reads alone, so this is
+    /// programmed as read/write and traps that changed the value are skipped
+    Read,
+    /// `awatch`, reads or writes
+    Access,
+}
+
+impl HwKind {
+    /* DR7 RW field */
+    fn rw_bits(self) -> u64 {
+        match self {
+            HwKind::Execute => 0b00,
+            HwKind::Write => 0b01,
+            HwKind::Read | HwKind::Access => 0b11,
+        }
+    }
+
+    /// What gdb calls it when reporting
+    pub fn describe(self) -> &'static str {
+        match self {
+            HwKind::Execute => "Hardware breakpoint",
+            HwKind::Write => "Hardware watchpoint",
+            HwKind::Read => "Hardware read watchpoint",
+            HwKind::Access => "Hardware access (read/write) watchpoint",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HwKind::Execute => "hw",
+            HwKind::Write => "watch",
+            HwKind::Read => "rwatch",
+            HwKind::Access => "awatch",
+        }
+    }
+}
+
+/* DR7 LEN field */
+fn len_bits(len: usize) -> u64 {
+    match len {
+        2 => 0b01,
+        4 => 0b11,
+        8 => 0b10,
+        _ => 0b00,
+    }
+}
+
+/// A breakpoint or watchpoint held in one of the debug register slots
+#[derive(Clone, Debug)]
+pub struct HwBreakpoint {
+    /// Number shared with software breakpoints
+    pub id: usize,
+    pub addr: usize,
+    /// Bytes watched, 1 for execute breakpoints
+    pub len: usize,
+    pub kind: HwKind,
+    pub enabled: bool,
+    pub freq: u64,
+    /// Last value seen at `addr`, used to report old and new values
+    pub value: u64,
+}
+
+impl Inferior {
+    /* Set a hardware breakpoint (`Execute`) or watchpoint */
+    pub fn set_hw_breakpoint(&mut self, addr: usize, len: usize, kind: HwKind) -> Result<usize, failure::Error> {
+        if self.state != InferiorState::Stopped {
+            return Err(failure::format_err!("The inferior must be stopped to set breakpoints"));
+        }
+
+        let len = if kind == HwKind::Execute { 1 } else { len };
+        if ![1, 2, 4, 8].contains(&len) {
+            return Err(failure::format_err!("Watch length must be 1, 2, 4 or 8 bytes"));
+        }
+        if addr & (len - 1) != 0 {
+            return Err(failure::format_err!("Address {:#x} is not aligned to {} bytes", addr, len));
+        }
+
+        let slot = self.hw_breakpoints.iter().position(|s| s.is_none())
+            .ok_or_else(|| failure::format_err!("All {} hardware debug registers are in use", HW_SLOTS))?;
+        let value = if kind == HwKind::Execute { 0 } else { self.memory(addr as u64, len)? };
+
+        let id = self.alloc_bp_id();
+        self.hw_breakpoints[slot] = Some(HwBreakpoint {
+            id, addr, len, kind,
+            enabled: true,
+            freq: 0,
+            value,
+        });
+
+        if let Err(e) = self.program_debugregs() {
+            self.hw_breakpoints[slot] = None;
+            return Err(failure::format_err!("Unable to program debug registers: {}", e));
+        }
+        Ok(id)
+    }
+
+    pub(crate) fn hw_slot(&self, id: usize) -> Option<usize> {
+        self.hw_breakpoints.iter()
+            .position(|s| s.as_ref().map(|hw| hw.id) == Some(id))
+    }
+
+    pub(crate) fn delete_hw_breakpoint(&mut self, slot: usize) -> Result<(), failure::Error> {
+        self.hw_breakpoints[slot] = None;
+        self.program_debugregs()?;
+        Ok(())
+    }
+
+    pub(crate) fn enable_hw_breakpoint(&mut self, slot: usize, enabled: bool) -> Result<(), failure::Error> {
+        if let Some(hw) = self.hw_breakpoints[slot].as_mut() {
+            hw.enabled = enabled;
+        }
+        self.program_debugregs()?;
+        Ok(())
+    }
+
+    /// All hardware breakpoints and watchpoints with their slot
+    pub fn hw_breakpoints(&self) -> Vec<(usize, &HwBreakpoint)> {
+        self.hw_breakpoints.iter()
+            .enumerate()
+            .filter_map(|(slot, hw)| hw.as_ref().map(|hw| (slot, hw)))
+            .collect()
+    }
+
+    /* DR7 value for the current slots */
+    fn dr7(&self) -> u64 {
+        let mut dr7 = 0;
+        for (slot, hw) in self.hw_breakpoints() {
+            if !hw.enabled { continue }
+            dr7 |= 1 << (slot * 2);
+            dr7 |= hw.kind.rw_bits() << (16 + slot * 4);
+            dr7 |= len_bits(hw.len) << (18 + slot * 4);
+        }
+        dr7
+    }
+
+    /* Write DR0-DR3 and DR7 into every thread */
+    pub(crate) fn program_debugregs(&self) -> nix::Result<()> {
+        for tid in self.thread_pids() {
+            self.program_thread_debugregs(tid)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn program_thread_debugregs(&self, tid: Pid) -> nix::Result<()> {
+        // Disable everything first, the kernel validates DR7 against the
+        // addresses currently in DR0-DR3
+        set_debugreg(tid, 7, 0)?;
+        for (slot, hw) in self.hw_breakpoints() {
+            set_debugreg(tid, slot, hw.addr as u64)?;
+        }
+        set_debugreg(tid, 7, self.dr7())
+    }
+
+    /* Forget every slot and disable them in each thread, so nothing
+     * traps once we're no longer there to catch it */
+    pub(crate) fn clear_hw_breakpoints(&mut self) {
+        for slot in self.hw_breakpoints.iter_mut() {
+            *slot = None;
+        }
+        for tid in self.thread_pids() {
+            let _ = set_debugreg(tid, 7, 0);
+        }
+    }
+
+    /* Turn a single slot off in DR7 for `tid`, used to step past it */
+    pub(crate) fn mask_hw_slot(&self, tid: Pid, slot: usize) -> nix::Result<()> {
+        set_debugreg(tid, 7, self.dr7() & !(1 << (slot * 2)))
+    }
+
+    /* Slot of an enabled execute breakpoint at `pc` */
+    pub(crate) fn hw_exec_slot(&self, pc: usize) -> Option<usize> {
+        self.hw_breakpoints().into_iter()
+            .find(|(_, hw)| hw.enabled && hw.kind == HwKind::Execute && hw.addr == pc)
+            .map(|(slot, _)| slot)
+    }
+
+    /* Check DR6 to see if a SIGTRAP came from a debug register */
+    pub(crate) fn handle_hw_breakpoint(&mut self, tid: Pid) -> BreakpointAction {
+        let dr6 = match get_debugreg(tid, 6) {
+            Ok(dr6) => dr6 as u64,
+            Err(_) => return BreakpointAction::Unknown,
+        };
+        if dr6 & DR6_SLOT_MASK == 0 {
+            return BreakpointAction::Unknown;
+        }
+
+        // DR6 is sticky, clear it for the next trap
+        let _ = set_debugreg(tid, 6, 0);
+
+        let mut action = BreakpointAction::Resume;
+        for slot in 0..HW_SLOTS {
+            if dr6 & (1 << slot) == 0 { continue }
+            let hw = match &self.hw_breakpoints[slot] {
+                Some(hw) if hw.enabled => hw.clone(),
+                _ => continue,
+            };
+
+            let new = if hw.kind == HwKind::Execute { 0 } else {
+                self.memory(hw.addr as u64, hw.len).unwrap_or(hw.value)
+            };
+
+            // A write of the same value isn't a change, keep going like gdb
+            if hw.kind == HwKind::Write && new == hw.value {
+                continue;
+            }
+            // The slot also traps writes, a changed value means this wasn't
+            // a read. Remember it and keep going like gdb
+            if hw.kind == HwKind::Read && new != hw.value {
+                self.hw_breakpoints[slot].as_mut().unwrap().value = new;
+                continue;
+            }
+
+            match hw.kind {
+                HwKind::Execute => {
+                    println!("{} {} (DR{}) hit at {:#x}", hw.kind.describe(), hw.id, slot, hw.addr);
+                },
+                HwKind::Write => {
+                    println!("{} {} (DR{}): {:#x}", hw.kind.describe(), hw.id, slot, hw.addr);
+                    println!("Old value = {:#x}", hw.value);
+                    println!("New value = {:#x}", new);
+                },
+                HwKind::Read => {
+                    println!("{} {} (DR{}): {:#x}", hw.kind.describe(), hw.id, slot, hw.addr);
+                    println!("Value = {:#x}", new);
+                },
+                HwKind::Access => {
+                    println!("{} {} (DR{}): {:#x}", hw.kind.describe(), hw.id, slot, hw.addr);
+                    if new != hw.value {
+                        println!("Old value = {:#x}", hw.value);
+                        println!("New value = {:#x}", new);
+                    } else {
+                        println!("Value = {:#x}", new);
+                    }
+                },
+            }
+
+            let hw = self.hw_breakpoints[slot].as_mut().unwrap();
+            hw.freq += 1;
+            hw.value = new;
+            action = BreakpointAction::Stop;
+        }
+        action
+    }
+}
+
+fn get_debugreg(tid: Pid, idx: usize) -> nix::Result<i64> {
+    ffi::peek_user(tid, DEBUGREG_OFFSET + idx * 8)
+}
+
+fn set_debugreg(tid: Pid, idx: usize, value: u64) -> nix::Result<()> {
+    ffi::poke_user(tid, DEBUGREG_OFFSET + idx * 8, value)
+}
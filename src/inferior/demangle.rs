@@ -0,0 +1,90 @@
+/* Demangling
+ * Rust symbols come in the legacy scheme, `_ZN6twelve4main17h<hash>E`, or
+ * v0, `_RNvCs<disambiguator>_6twelve4main`. C++ uses Itanium `_Z` names,
+ * which legacy Rust names also start with; those are told apart by the
+ * hash element before the closing `E`.
+ */
+
+use std::fmt;
+
+/// `set demangle on|off|nohash`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DemangleMode {
+    /// Symbol names as they are in the file
+    Off,
+    /// Demangled, Rust hashes included
+    On,
+    /// Demangled without Rust hashes and crate disambiguators
+    NoHash,
+}
+
+impl fmt::Display for DemangleMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DemangleMode::Off => "off",
+            DemangleMode::On => "on",
+            DemangleMode::NoHash => "nohash",
+        })
+    }
+}
+
+/// Parse the value given to `set demangle`
+pub fn parse_demangle_mode(s: &str) -> Option<DemangleMode> {
+    match s.trim() {
+        "on" | "1" | "yes" => Some(DemangleMode::On),
+        "off" | "0" | "no" => Some(DemangleMode::Off),
+        "nohash" => Some(DemangleMode::NoHash),
+        _ => None,
+    }
+}
+
+/* `_ZN...17h<16 hex digits>E`, LLVM may add `.llvm.<hex>` after it. The
+ * path itself can hold dots, `..` stands for `::` in generic arguments. */
+fn is_rust_legacy(name: &str) -> bool {
+    if !name.starts_with("_ZN") { return false }
+    let name = match name.rfind(".llvm.") {
+        Some(pos) if name[pos + 6..].chars().all(|c| c.is_ascii_hexdigit()) => &name[..pos],
+        _ => name,
+    };
+    if !name.ends_with('E') || name.len() < 3 + 20 { return false }
+    let hash = &name[name.len() - 20..name.len() - 1];
+    hash.starts_with("17h") && hash[3..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn demangle_rust(name: &str, mode: DemangleMode) -> Option<String> {
+    let sym = rustc_demangle::try_demangle(name).ok()?;
+    Some(match mode {
+        DemangleMode::NoHash => format!("{:#}", sym),
+        _ => sym.to_string(),
+    })
+}
+
+fn demangle_cpp(name: &str) -> Option<String> {
+    let sym = cpp_demangle::Symbol::new(name.as_bytes()).ok()?;
+    Some(sym.to_string())
+}
+
+/// `name` as `mode` wants it shown. Anything that doesn't demangle is
+/// returned as is.
+pub fn demangle(name: &str, mode: DemangleMode) -> String {
+    let demangled = match mode {
+        DemangleMode::Off => None,
+        _ if name.starts_with("_R") || is_rust_legacy(name) => demangle_rust(name, mode),
+        _ if name.starts_with("_Z") => demangle_cpp(name),
+        _ => None,
+    };
+    demangled.unwrap_or_else(|| name.to_string())
+}
+
+/// Does the symbol `name` answer to `wanted`, which is either the name
+/// itself or a demangled path. `twelve::main`, `twelve::main::h<hash>` and
+/// for C++ `ns::func` without its parameters all match.
+pub fn symbol_matches(name: &str, wanted: &str) -> bool {
+    if name == wanted { return true }
+    if !name.starts_with("_Z") && !name.starts_with("_R") { return false }
+
+    let full = demangle(name, DemangleMode::On);
+    if full == wanted { return true }
+    let short = demangle(name, DemangleMode::NoHash);
+    short == wanted || (short.starts_with(wanted) && short[wanted.len()..].starts_with('('))
+}
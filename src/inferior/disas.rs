@@ -0,0 +1,93 @@
+/* Disassembly of inferior code through capstone */
+
+use capstone::prelude::*;
+use nix::sys::ptrace;
+
+use std::fmt;
+
+use super::Inferior;
+
+/// Longest possible x86 instruction
+pub const MAX_INSN_LEN: usize = 15;
+
+/// A decoded instruction
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub addr: usize,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub op_str: String,
+}
+
+impl Instruction {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn is_call(&self) -> bool {
+        self.mnemonic.starts_with("call")
+    }
+
+    pub fn is_ret(&self) -> bool {
+        self.mnemonic.starts_with("ret")
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.mnemonic, self.op_str)
+    }
+}
+
+/// Decode up to `count` x86_64 instructions from `code` located at `addr`
+pub fn disassemble(code: &[u8], addr: usize, count: usize) -> Vec<Instruction> {
+    let cs = match Capstone::new()
+        .x86()
+        .mode(arch::x86::ArchMode::Mode64)
+        .syntax(arch::x86::ArchSyntax::Intel)
+        .build() {
+        Ok(cs) => cs,
+        Err(_) => return Vec::new(),
+    };
+
+    let insns = match cs.disasm_count(code, addr as u64, count) {
+        Ok(insns) => insns,
+        Err(_) => return Vec::new(),
+    };
+    insns.iter().map(|i| Instruction {
+        addr: i.address() as usize,
+        bytes: i.bytes().to_vec(),
+        mnemonic: i.mnemonic().unwrap_or("").to_string(),
+        op_str: i.op_str().unwrap_or("").to_string(),
+    }).collect()
+}
+
+impl Inferior {
+    /// Disassemble `count` instructions of the inferior starting at `addr`
+    pub fn disassemble(&self, addr: usize, count: usize) -> Vec<Instruction> {
+        match self.memory_view(addr, count * MAX_INSN_LEN) {
+            Ok(code) => disassemble(&code, addr, count),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// `disas [location] [count]`, the current instruction marked with `=>`
+    pub fn show_disassembly(&self, addr: usize, count: usize) -> Result<(), failure::Error> {
+        self.check_mapped(addr, 1)?;
+        let insns = self.disassemble(addr, count);
+        if insns.is_empty() {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr));
+        }
+
+        let pc = ptrace::getregs(self.tid).map(|regs| regs.rip as usize).ok();
+        for insn in insns {
+            let marker = if Some(insn.addr) == pc { "=>" } else { "  " };
+            println!("{} {:#x}{}:\t{}", marker, insn.addr, self.addr_label(insn.addr), insn);
+        }
+        Ok(())
+    }
+}
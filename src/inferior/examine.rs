@@ -0,0 +1,206 @@
+/* Examining memory
+ * `x/<count><fmt><size> <addr>` in the style of gdb and a `hexdump` with
+ * offset, hex and ASCII columns. Every row is colored by the kind of
+ * mapping it's in, the same way `vmmap` colors them.
+ */
+
+use colored::*;
+
+use super::Inferior;
+
+/// How `x` shows each unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XFmt {
+    Hex,
+    Decimal,
+    Unsigned,
+    Char,
+    Str,
+    Insn,
+}
+
+/// Parsed `/<count><fmt><size>` suffix of `x`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XFormat {
+    pub count: usize,
+    pub fmt: XFmt,
+    /// Unit size in bytes: b, h, w or g
+    pub size: usize,
+}
+
+impl Default for XFormat {
+    fn default() -> Self {
+        XFormat { count: 1, fmt: XFmt::Hex, size: 4 }
+    }
+}
+
+/// Parse the part after `x/`, e.g. `4xg`, `s` or `10i`. Letters can come
+/// in any order after the count.
+pub fn parse_x_format(s: &str) -> Result<XFormat, failure::Error> {
+    let mut format = XFormat::default();
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if !digits.is_empty() {
+        format.count = digits.parse()?;
+    }
+
+    let mut size = None;
+    for c in s[digits.len()..].chars() {
+        match c {
+            'x' => format.fmt = XFmt::Hex,
+            'd' => format.fmt = XFmt::Decimal,
+            'u' => format.fmt = XFmt::Unsigned,
+            'c' => format.fmt = XFmt::Char,
+            's' => format.fmt = XFmt::Str,
+            'i' => format.fmt = XFmt::Insn,
+            'b' => size = Some(1),
+            'h' => size = Some(2),
+            'w' => size = Some(4),
+            'g' => size = Some(8),
+            _ => return Err(failure::format_err!("Invalid format letter '{}'", c)),
+        }
+    }
+
+    format.size = match format.fmt {
+        XFmt::Char | XFmt::Str | XFmt::Insn => 1,
+        _ => size.unwrap_or(format.size),
+    };
+    Ok(format)
+}
+
+/// One `hexdump` row: offset, up to 16 hex bytes and their ASCII
+pub fn hexdump_line(offset: usize, bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for i in 0..16 {
+        match bytes.get(i) {
+            Some(b) => hex.push_str(&format!("{:02x} ", b)),
+            None => hex.push_str("   "),
+        }
+        if i == 7 { hex.push(' '); }
+    }
+    let ascii: String = bytes.iter().map(|&b| printable(b)).collect();
+    format!("{:08x}  {} |{}|", offset, hex, ascii)
+}
+
+fn printable(b: u8) -> char {
+    if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }
+}
+
+/* Escape a string the way it would be written in C */
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in bytes {
+        match b {
+            b'\n' => s.push_str("\\n"),
+            b'\t' => s.push_str("\\t"),
+            b'\r' => s.push_str("\\r"),
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            b if b.is_ascii_graphic() || b == b' ' => s.push(b as char),
+            b => s.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    s
+}
+
+/* Format one unit for x */
+fn format_unit(bytes: &[u8], fmt: XFmt) -> String {
+    let mut word = [0u8; 8];
+    word[..bytes.len()].copy_from_slice(bytes);
+    let val = u64::from_le_bytes(word);
+    let bits = bytes.len() * 8;
+
+    match fmt {
+        XFmt::Hex => format!("{:#0width$x}", val, width = bytes.len() * 2 + 2),
+        XFmt::Unsigned => val.to_string(),
+        XFmt::Decimal => {
+            // Sign extend from the unit size
+            let shift = 64 - bits;
+            (((val << shift) as i64) >> shift).to_string()
+        },
+        XFmt::Char => format!("{:<4} '{}'", val as i8, escape(&bytes[..1])),
+        XFmt::Str | XFmt::Insn => unreachable!(),
+    }
+}
+
+/// Longest string `x/s` reads
+const MAX_STRING: usize = 4096;
+
+impl Inferior {
+    fn print_row(&self, addr: usize, row: String) {
+        match self.mem.region(addr).and_then(|r| r.color()) {
+            Some(color) => println!("{}", row.color(color)),
+            None => println!("{}", row),
+        }
+    }
+
+    /// `x/<count><fmt><size> <addr>`
+    pub fn examine(&self, addr: usize, format: &XFormat) -> Result<(), failure::Error> {
+        self.check_mapped(addr, 1)?;
+        match format.fmt {
+            XFmt::Str => self.examine_strings(addr, format.count),
+            XFmt::Insn => self.examine_instructions(addr, format.count),
+            _ => self.examine_units(addr, format),
+        }
+    }
+
+    fn examine_units(&self, addr: usize, format: &XFormat) -> Result<(), failure::Error> {
+        let len = format.count.checked_mul(format.size)
+            .ok_or_else(|| failure::format_err!("Count {} is too large", format.count))?;
+        let bytes = self.memory_view(addr, self.clamp_to_mapped(addr, len)?)?;
+        let per_row = match format.fmt {
+            XFmt::Char => 8,
+            _ => std::cmp::max(16 / format.size, 2),
+        };
+
+        for (i, row) in bytes.chunks(per_row * format.size).enumerate() {
+            let row_addr = addr + i * per_row * format.size;
+            let units: Vec<String> = row.chunks(format.size)
+                .filter(|u| u.len() == format.size)
+                .map(|u| format_unit(u, format.fmt))
+                .collect();
+            if units.is_empty() { break }
+            self.print_row(row_addr, format!("{:#x}{}:\t{}", row_addr, self.addr_label(row_addr), units.join("\t")));
+        }
+
+        if bytes.len() < len {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr + bytes.len()));
+        }
+        Ok(())
+    }
+
+    fn examine_strings(&self, mut addr: usize, count: usize) -> Result<(), failure::Error> {
+        for _ in 0..count {
+            let bytes = self.memory_view(addr, MAX_STRING)?;
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            let more = if len == bytes.len() { "..." } else { "" };
+            self.print_row(addr, format!("{:#x}{}:\t\"{}\"{}", addr, self.addr_label(addr), escape(&bytes[..len]), more));
+            addr += len + 1;
+        }
+        Ok(())
+    }
+
+    fn examine_instructions(&self, addr: usize, count: usize) -> Result<(), failure::Error> {
+        let insns = self.disassemble(addr, count);
+        if insns.is_empty() {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr));
+        }
+        for insn in insns {
+            self.print_row(insn.addr, format!("{:#x}{}:\t{}", insn.addr, self.addr_label(insn.addr), insn));
+        }
+        Ok(())
+    }
+
+    /// `hexdump <addr> <len>`
+    pub fn hexdump(&self, addr: usize, len: usize) -> Result<(), failure::Error> {
+        let bytes = self.memory_view(addr, self.clamp_to_mapped(addr, len)?)?;
+        for (i, row) in bytes.chunks(16).enumerate() {
+            let row_addr = addr + i * 16;
+            self.print_row(row_addr, format!("{:#016x}  {}", row_addr, hexdump_line(i * 16, row)));
+        }
+
+        if bytes.len() < len {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr + bytes.len()));
+        }
+        Ok(())
+    }
+}
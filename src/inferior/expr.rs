@@ -0,0 +1,371 @@
+/* Expression evaluation
+ * Small C-like expressions over registers, memory and constants.
+ * Used by breakpoint conditions: `break 0x401000 if $rax == 5`
+ */
+
+use std::fmt;
+
+/// Anything expressions can be evaluated against
+pub trait ExprContext {
+    /// Value of a register by name (without the `$`)
+    fn register(&self, name: &str) -> Result<u64, failure::Error>;
+
+    /// Read `size` bytes of memory at `addr` as a little endian integer
+    fn memory(&self, addr: u64, size: usize) -> Result<u64, failure::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Mul, Div, Rem,
+    Add, Sub,
+    Shl, Shr,
+    Lt, Le, Gt, Ge,
+    Eq, Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Mul => "*", BinOp::Div => "/", BinOp::Rem => "%",
+            BinOp::Add => "+", BinOp::Sub => "-",
+            BinOp::Shl => "<<", BinOp::Shr => ">>",
+            BinOp::Lt => "<", BinOp::Le => "<=", BinOp::Gt => ">", BinOp::Ge => ">=",
+            BinOp::Eq => "==", BinOp::Ne => "!=",
+            BinOp::BitAnd => "&", BinOp::BitXor => "^", BinOp::BitOr => "|",
+            BinOp::And => "&&", BinOp::Or => "||",
+        }
+    }
+
+    /* Binding power, higher binds tighter */
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::BitOr => 3,
+            BinOp::BitXor => 4,
+            BinOp::BitAnd => 5,
+            BinOp::Eq | BinOp::Ne => 6,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 7,
+            BinOp::Shl | BinOp::Shr => 8,
+            BinOp::Add | BinOp::Sub => 9,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    /// Constant
+    Num(u64),
+    /// `$rax`
+    Reg(String),
+    /// `*addr` or `*(u32*)addr`, with the size of the read in bytes
+    Deref(Box<Expr>, usize),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &dyn ExprContext) -> Result<u64, failure::Error> {
+        Ok(match self {
+            Expr::Num(n) => *n,
+            Expr::Reg(name) => ctx.register(name)?,
+            Expr::Deref(addr, size) => ctx.memory(addr.eval(ctx)?, *size)?,
+            Expr::Unary(op, e) => {
+                let v = e.eval(ctx)?;
+                match op {
+                    UnOp::Neg => v.wrapping_neg(),
+                    UnOp::Not => (v == 0) as u64,
+                    UnOp::BitNot => !v,
+                }
+            },
+            Expr::Binary(op, l, r) => {
+                let l = l.eval(ctx)?;
+                // Short circuit so `$rdi && *$rdi == 0` doesn't fault
+                match op {
+                    BinOp::And if l == 0 => return Ok(0),
+                    BinOp::Or if l != 0 => return Ok(1),
+                    _ => {},
+                }
+                let r = r.eval(ctx)?;
+                match op {
+                    BinOp::Mul => l.wrapping_mul(r),
+                    BinOp::Div => l.checked_div(r).ok_or_else(|| failure::format_err!("Division by zero"))?,
+                    BinOp::Rem => l.checked_rem(r).ok_or_else(|| failure::format_err!("Division by zero"))?,
+                    BinOp::Add => l.wrapping_add(r),
+                    BinOp::Sub => l.wrapping_sub(r),
+                    BinOp::Shl => l.checked_shl(r as u32).unwrap_or(0),
+                    BinOp::Shr => l.checked_shr(r as u32).unwrap_or(0),
+                    BinOp::Lt => (l < r) as u64,
+                    BinOp::Le => (l <= r) as u64,
+                    BinOp::Gt => (l > r) as u64,
+                    BinOp::Ge => (l >= r) as u64,
+                    BinOp::Eq => (l == r) as u64,
+                    BinOp::Ne => (l != r) as u64,
+                    BinOp::BitAnd => l & r,
+                    BinOp::BitXor => l ^ r,
+                    BinOp::BitOr => l | r,
+                    BinOp::And | BinOp::Or => (r != 0) as u64,
+                }
+            },
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Num(n) => write!(f, "{:#x}", n),
+            Expr::Reg(name) => write!(f, "${}", name),
+            Expr::Deref(e, 8) => write!(f, "*{}", e),
+            Expr::Deref(e, size) => write!(f, "*({}*){}", size_type(*size), e),
+            Expr::Unary(UnOp::Neg, e) => write!(f, "-{}", e),
+            Expr::Unary(UnOp::Not, e) => write!(f, "!{}", e),
+            Expr::Unary(UnOp::BitNot, e) => write!(f, "~{}", e),
+            Expr::Binary(op, l, r) => write!(f, "({} {} {})", l, op.symbol(), r),
+        }
+    }
+}
+
+/* Size in bytes of the types allowed in pointer casts */
+fn type_size(name: &str) -> Option<usize> {
+    Some(match name {
+        "u8" | "i8" | "char" => 1,
+        "u16" | "i16" | "short" => 2,
+        "u32" | "i32" | "int" => 4,
+        "u64" | "i64" | "long" | "usize" | "isize" => 8,
+        _ => return None,
+    })
+}
+
+fn size_type(size: usize) -> &'static str {
+    match size {
+        1 => "u8",
+        2 => "u16",
+        4 => "u32",
+        _ => "u64",
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(u64),
+    Reg(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/* Longest operators first so `<=` wins over `<` */
+const OPERATORS: &[&str] = &[
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, failure::Error> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() { i += 1; }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(parse_number(&text)?));
+        } else if c == '$' || c.is_alphabetic() || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+            let text: String = chars[start..i].iter().collect();
+            if let Some(reg) = text.strip_prefix('$') {
+                tokens.push(Token::Reg(reg.to_lowercase()));
+            } else {
+                tokens.push(Token::Ident(text));
+            }
+        } else {
+            let rest: String = chars[i..].iter().collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                },
+                None => return Err(failure::format_err!("Unexpected character '{}' in expression", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a number, hex with `0x` and decimal otherwise
+pub fn parse_number(s: &str) -> Result<u64, failure::Error> {
+    let res = if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse::<u64>()
+    };
+    res.map_err(|_| failure::format_err!("Invalid number \"{}\"", s))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, off: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + off)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, tok: Token) -> Result<(), failure::Error> {
+        match self.next() {
+            Some(ref t) if *t == tok => Ok(()),
+            Some(t) => Err(failure::format_err!("Expected {:?}, found {:?}", tok, t)),
+            None => Err(failure::format_err!("Expected {:?} at end of expression", tok)),
+        }
+    }
+
+    fn binop(&self) -> Option<BinOp> {
+        Some(match self.peek()? {
+            Token::Op("*") => BinOp::Mul,
+            Token::Op("/") => BinOp::Div,
+            Token::Op("%") => BinOp::Rem,
+            Token::Op("+") => BinOp::Add,
+            Token::Op("-") => BinOp::Sub,
+            Token::Op("<<") => BinOp::Shl,
+            Token::Op(">>") => BinOp::Shr,
+            Token::Op("<") => BinOp::Lt,
+            Token::Op("<=") => BinOp::Le,
+            Token::Op(">") => BinOp::Gt,
+            Token::Op(">=") => BinOp::Ge,
+            Token::Op("==") => BinOp::Eq,
+            Token::Op("!=") => BinOp::Ne,
+            Token::Op("&") => BinOp::BitAnd,
+            Token::Op("^") => BinOp::BitXor,
+            Token::Op("|") => BinOp::BitOr,
+            Token::Op("&&") => BinOp::And,
+            Token::Op("||") => BinOp::Or,
+            _ => return None,
+        })
+    }
+
+    /* Precedence climbing over binary operators */
+    fn expr(&mut self, min_prec: u8) -> Result<Expr, failure::Error> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.binop() {
+            if op.precedence() < min_prec { break }
+            self.next();
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, failure::Error> {
+        match self.peek() {
+            Some(Token::Op("-")) => { self.next(); Ok(Expr::Unary(UnOp::Neg, Box::new(self.unary()?))) },
+            Some(Token::Op("!")) => { self.next(); Ok(Expr::Unary(UnOp::Not, Box::new(self.unary()?))) },
+            Some(Token::Op("~")) => { self.next(); Ok(Expr::Unary(UnOp::BitNot, Box::new(self.unary()?))) },
+            Some(Token::Op("*")) => {
+                self.next();
+                let size = self.pointer_cast()?.unwrap_or(8);
+                Ok(Expr::Deref(Box::new(self.unary()?), size))
+            },
+            _ => self.primary(),
+        }
+    }
+
+    /* `(u32*)` following a dereference, returns the pointee size */
+    fn pointer_cast(&mut self) -> Result<Option<usize>, failure::Error> {
+        let size = match (self.peek(), self.peek_at(1), self.peek_at(2), self.peek_at(3)) {
+            (Some(Token::LParen), Some(Token::Ident(ty)), Some(Token::Op("*")), Some(Token::RParen)) => {
+                type_size(ty).ok_or_else(|| failure::format_err!("Unknown type \"{}\"", ty))?
+            },
+            _ => return Ok(None),
+        };
+        self.pos += 4;
+        Ok(Some(size))
+    }
+
+    fn primary(&mut self) -> Result<Expr, failure::Error> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Reg(r)) => Ok(Expr::Reg(r)),
+            Some(Token::LParen) => {
+                let e = self.expr(0)?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            },
+            Some(Token::Ident(name)) => Err(failure::format_err!("No symbol \"{}\" in current context", name)),
+            Some(t) => Err(failure::format_err!("Unexpected {:?} in expression", t)),
+            None => Err(failure::format_err!("Unexpected end of expression")),
+        }
+    }
+}
+
+/// Parse an expression such as `*(u32*)($rsp + 8) == 0x41`
+pub fn parse(s: &str) -> Result<Expr, failure::Error> {
+    let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+    if parser.tokens.is_empty() {
+        return Err(failure::format_err!("Empty expression"));
+    }
+
+    let expr = parser.expr(0)?;
+    if let Some(t) = parser.peek() {
+        return Err(failure::format_err!("Unexpected {:?} after expression", t));
+    }
+    Ok(expr)
+}
+
+/// Parse `<lvalue> = <expr>` for `set`. The left side has to be a
+/// register or a dereference.
+pub fn parse_assignment(s: &str) -> Result<(Expr, Expr), failure::Error> {
+    let bytes = s.as_bytes();
+    let pos = (0..bytes.len())
+        .find(|&i| {
+            bytes[i] == b'='
+                && (i == 0 || !b"=!<>".contains(&bytes[i - 1]))
+                && bytes.get(i + 1) != Some(&b'=')
+        })
+        .ok_or_else(|| failure::format_err!("Expected an assignment: <lvalue> = <expr>"))?;
+
+    let target = parse(&s[..pos])?;
+    match target {
+        Expr::Reg(_) | Expr::Deref(..) => {},
+        _ => return Err(failure::format_err!("Left operand of assignment is not an lvalue")),
+    }
+    Ok((target, parse(&s[pos + 1..])?))
+}
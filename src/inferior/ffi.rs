@@ -6,11 +6,166 @@ mod ffi {
     }
 }
 
-/* Disable ASLR */
-pub fn disable_aslr() -> () {
-    unsafe {
-        let old = ffi::personality(0xffffffff);
-        ffi::personality((old | 0x0040000) as i64);
+use libc::{c_void, pid_t};
+use nix::errno::Errno;
+use nix::sys::ptrace::Options;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::ptr;
+
+/// Event reported for PTRACE_INTERRUPT and for the initial stop of a new
+/// thread when the inferior was seized
+pub const PTRACE_EVENT_STOP: libc::c_int = 128;
+
+/* personality(2) flags, from <linux/personality.h> */
+pub const ADDR_NO_RANDOMIZE: u64 = 0x0040000;
+pub const MMAP_PAGE_ZERO: u64 = 0x0100000;
+pub const ADDR_COMPAT_LAYOUT: u64 = 0x0200000;
+pub const READ_IMPLIES_EXEC: u64 = 0x0400000;
+pub const ADDR_LIMIT_32BIT: u64 = 0x0800000;
+
+/* Persona of the calling process */
+pub fn get_personality() -> nix::Result<u64> {
+    let res = unsafe { ffi::personality(0xffffffff) };
+    Errno::result(res).map(|persona| persona as u64)
+}
+
+/* Replace the persona of the calling process */
+pub fn set_personality(persona: u64) -> nix::Result<()> {
+    let res = unsafe { ffi::personality(persona as i64) };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_SEIZE: Attach to a running thread without stopping it */
+pub fn seize(pid: Pid, options: Options) -> nix::Result<()> {
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_SEIZE,
+                     pid_t::from(pid),
+                     ptr::null_mut::<c_void>(),
+                     options.bits() as *mut c_void)
+    };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_INTERRUPT: Stop a seized thread */
+pub fn interrupt(pid: Pid) -> nix::Result<()> {
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_INTERRUPT,
+                     pid_t::from(pid),
+                     ptr::null_mut::<c_void>(),
+                     ptr::null_mut::<c_void>())
+    };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_DETACH: Let go of a thread, delivering `sig` to it */
+pub fn detach(pid: Pid, sig: Option<Signal>) -> nix::Result<()> {
+    let data = match sig {
+        Some(sig) => sig as libc::c_int,
+        None => 0,
+    };
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_DETACH,
+                     pid_t::from(pid),
+                     ptr::null_mut::<c_void>(),
+                     data as *mut c_void)
+    };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_SYSCALL: Continue until the next syscall entry or exit */
+pub fn syscall(pid: Pid, sig: Option<Signal>) -> nix::Result<()> {
+    let data = match sig {
+        Some(sig) => sig as libc::c_int,
+        None => 0,
+    };
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_SYSCALL,
+                     pid_t::from(pid),
+                     ptr::null_mut::<c_void>(),
+                     data as *mut c_void)
+    };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_GET_SYSCALL_INFO and its `op` values, from <linux/ptrace.h> */
+const PTRACE_GET_SYSCALL_INFO: libc::c_uint = 0x420e;
+pub const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+pub const PTRACE_SYSCALL_INFO_EXIT: u8 = 2;
+
+/* PTRACE_GET_SYSCALL_INFO: Which kind of syscall stop the thread is in.
+ * Only the leading `op` byte of `struct ptrace_syscall_info` is used;
+ * the kernel (5.3+) copies no more than the buffer holds. */
+pub fn syscall_info_op(pid: Pid) -> nix::Result<u8> {
+    let mut info = [0u8; 88];
+    let res = unsafe {
+        libc::ptrace(PTRACE_GET_SYSCALL_INFO,
+                     pid_t::from(pid),
+                     info.len() as *mut c_void,
+                     info.as_mut_ptr() as *mut c_void)
+    };
+    Errno::result(res).map(|_| info[0])
+}
+
+/* tgkill: Send a signal to a single thread */
+pub fn tgkill(pid: Pid, tid: Pid, sig: Signal) -> nix::Result<()> {
+    let res = unsafe {
+        libc::syscall(libc::SYS_tgkill, pid_t::from(pid), pid_t::from(tid), sig as libc::c_int)
+    };
+    Errno::result(res).map(drop)
+}
+
+/* PTRACE_PEEKUSER: Read a word from the thread's `struct user` */
+pub fn peek_user(pid: Pid, offset: usize) -> nix::Result<i64> {
+    let res = unsafe {
+        Errno::clear();
+        libc::ptrace(libc::PTRACE_PEEKUSER,
+                     pid_t::from(pid),
+                     offset as *mut c_void,
+                     ptr::null_mut::<c_void>())
+    };
+    match Errno::result(res) {
+        Ok(..) | Err(nix::Error::Sys(Errno::UnknownErrno)) => Ok(res),
+        Err(e) => Err(e),
     }
 }
 
+/* PTRACE_POKEUSER: Write a word into the thread's `struct user` */
+pub fn poke_user(pid: Pid, offset: usize, data: u64) -> nix::Result<()> {
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_POKEUSER,
+                     pid_t::from(pid),
+                     offset as *mut c_void,
+                     data as *mut c_void)
+    };
+    Errno::result(res).map(drop)
+}
+
+/* Register sets for PTRACE_GETREGSET, from <elf.h> */
+pub const NT_PRFPREG: libc::c_int = 2;
+pub const NT_X86_XSTATE: libc::c_int = 0x202;
+
+/* PTRACE_GETREGSET: Fill `buf` with register set `nt`, returns the size
+ * the kernel wrote */
+pub fn get_regset(pid: Pid, nt: libc::c_int, buf: &mut [u8]) -> nix::Result<usize> {
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut c_void, iov_len: buf.len() };
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_GETREGSET,
+                     pid_t::from(pid),
+                     nt as *mut c_void,
+                     &mut iov as *mut libc::iovec as *mut c_void)
+    };
+    Errno::result(res).map(|_| iov.iov_len)
+}
+
+/* PTRACE_SETREGSET: Load register set `nt` from `buf` */
+pub fn set_regset(pid: Pid, nt: libc::c_int, buf: &[u8]) -> nix::Result<()> {
+    let mut iov = libc::iovec { iov_base: buf.as_ptr() as *mut c_void, iov_len: buf.len() };
+    let res = unsafe {
+        libc::ptrace(libc::PTRACE_SETREGSET,
+                     pid_t::from(pid),
+                     nt as *mut c_void,
+                     &mut iov as *mut libc::iovec as *mut c_void)
+    };
+    Errno::result(res).map(drop)
+}
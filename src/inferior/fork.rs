@@ -0,0 +1,245 @@
+/* Fork, vfork and exec
+ * The debugger follows a single process. Whichever side of a fork is not
+ * followed has our breakpoints taken back out of its memory and is then
+ * detached, or held stopped when detach-on-fork is off.
+ */
+
+use nix::sys::ptrace;
+use nix::unistd::Pid;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{ wait_for_stop, write_byte_to, Breakpoint, Inferior, InferiorState };
+
+/// Which process to keep debugging after a fork
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowForkMode {
+    Parent,
+    Child,
+}
+
+impl Inferior {
+    pub fn set_follow_fork_mode(&mut self, mode: FollowForkMode) { self.follow_fork_mode = mode; }
+    pub fn set_detach_on_fork(&mut self, val: bool)              { self.detach_on_fork = val; }
+
+    /* A traced child of a fork can report its first stop before the
+     * parent's fork event. Anything stopping that isn't one of our
+     * threads is taken to be such a child. */
+    pub(crate) fn is_new_process(&self, pid: Pid) -> bool {
+        !self.thread_states.contains_key(&pid)
+            && !PathBuf::from(format!("/proc/{}/task/{}", self.pid, pid)).exists()
+    }
+
+    pub(crate) fn child_stopped_early(&mut self, pid: Pid) {
+        self.early_children.insert(pid);
+    }
+
+    /* PTRACE_EVENT_FORK or PTRACE_EVENT_VFORK from `parent` */
+    pub(crate) fn handle_fork(&mut self, parent: Pid, event: i32) {
+        let kind = fork_name(event);
+        let child = match self.fork_child(parent) {
+            Some(child) => child,
+            None => {
+                let _ = self.resume_thread(parent, None);
+                return;
+            }
+        };
+
+        if self.follow_fork_mode == FollowForkMode::Parent {
+            self.release_fork_child(child, kind);
+            let _ = self.resume_thread(parent, None);
+            return;
+        }
+
+        println!("[Attaching after process {} {} to child process {}]", self.pid, kind, child);
+
+        // The rest of the parent has to be still before it's let go
+        self.stop_other_threads(parent);
+        let parent_tids = self.thread_pids();
+        if event == libc::PTRACE_EVENT_VFORK {
+            // The child runs in the parent's memory until it execs or
+            // exits, so our breakpoints can't come out of it until then
+            self.vfork_parent = Some(parent_tids);
+        } else {
+            self.remove_breakpoints_from(parent);
+            self.release_process(parent_tids, "parent");
+        }
+
+        self.pid = child;
+        self.tid = child;
+        self.tids.clear();
+        self.thread_states.clear();
+        self.pending_signals.clear();
+        self.add_thread(child);
+        self.prefetch_inferior_data();
+
+        // Debug registers aren't inherited across fork
+        if let Err(e) = self.program_debugregs() {
+            println!("Unable to set debug registers for process {}: {}", child, e);
+        }
+        self.thread_states.insert(child, InferiorState::Running);
+        let _ = self.resume_thread(child, None);
+    }
+
+    /* Let go of the child of a fork we aren't following. Also used while
+     * stopping threads, where there's no room to switch processes. */
+    pub(crate) fn release_fork_child_of(&mut self, parent: Pid, event: i32) {
+        if let Some(child) = self.fork_child(parent) {
+            self.release_fork_child(child, fork_name(event));
+        }
+    }
+
+    fn release_fork_child(&mut self, child: Pid, kind: &str) {
+        // With vfork this also takes them out of the parent, they go back
+        // in at PTRACE_EVENT_VFORK_DONE
+        self.remove_breakpoints_from(child);
+        if kind == "vfork" {
+            self.vfork_child = true;
+        }
+        if self.detach_on_fork {
+            println!("[Detaching after {} from child process {}]", kind, child);
+        }
+        self.release_process(vec![child], "child");
+    }
+
+    /* New pid from a fork event, once it has stopped */
+    fn fork_child(&mut self, parent: Pid) -> Option<Pid> {
+        let child = match ptrace::getevent(parent) {
+            Ok(child) => Pid::from_raw(child as i32),
+            Err(e) => {
+                println!("Unable to get the pid of the new process: {}", e);
+                return None;
+            }
+        };
+
+        if !self.early_children.remove(&child) {
+            wait_for_stop(child);
+        }
+        Some(child)
+    }
+
+    /* Detach from or hold on to a process we stopped following */
+    fn release_process(&mut self, tids: Vec<Pid>, what: &str) {
+        if self.detach_on_fork {
+            for tid in tids {
+                let _ = ptrace::detach(tid);
+            }
+        } else {
+            if let Some(pid) = tids.first() {
+                println!("[Keeping {} process {} stopped]", what, pid);
+            }
+            self.held_processes.extend(tids);
+        }
+    }
+
+    /* Detach from everything kept stopped with detach-on-fork off */
+    pub(crate) fn release_held_processes(&mut self) {
+        for tid in self.held_processes.drain(..) {
+            let _ = ptrace::detach(tid);
+        }
+    }
+
+    /* PTRACE_EVENT_VFORK_DONE: the vfork child no longer shares our memory */
+    pub(crate) fn handle_vfork_done(&mut self) {
+        if !self.vfork_child { return }
+        self.vfork_child = false;
+
+        let active: Vec<usize> = self.breakpoints.iter()
+            .filter(|(_, bp)| bp.enabled)
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in active {
+            if let Err(e) = self.write_byte(addr, 0xcc) {
+                println!("Unable to reinsert breakpoint at {:#x}: {}", addr, e);
+            }
+        }
+    }
+
+    /* The vfork child we followed left the parent's memory, clean up the
+     * parent and let it go */
+    fn finish_vfork_parent(&mut self) {
+        if let Some(parent_tids) = self.vfork_parent.take() {
+            if let Some(&parent) = parent_tids.first() {
+                self.remove_breakpoints_from(parent);
+            }
+            self.release_process(parent_tids, "parent");
+        }
+    }
+
+    /* Write the original bytes back into another process that has our
+     * breakpoints in it, leaving our own table alone */
+    fn remove_breakpoints_from(&self, pid: Pid) {
+        for (&addr, bp) in self.breakpoints.iter().filter(|(_, bp)| bp.enabled) {
+            if let Some(byte) = bp.orig_byte {
+                if let Err(e) = write_byte_to(pid, addr, byte) {
+                    println!("Unable to remove breakpoint at {:#x} from {}: {}", addr, pid, e);
+                }
+            }
+        }
+    }
+
+    /* PTRACE_EVENT_EXEC: everything about the address space is new */
+    pub(crate) fn handle_exec(&mut self) {
+        self.finish_vfork_parent();
+
+        // Only the leader survives an exec, whichever thread called it
+        let pid = self.pid;
+        self.tid = pid;
+        self.tids.clear();
+        self.thread_states.clear();
+        self.pending_signals.clear();
+        self.add_thread(pid);
+        self.thread_states.insert(pid, InferiorState::Stopped);
+
+        self.prefetch_inferior_data();
+        if let Ok(exe) = self.procfs.exe() {
+            self.location = exe.to_string_lossy().into_owned();
+        }
+        println!("process {} is executing new program: {}", pid, self.location);
+
+        self.parse();
+        self.clear_solibs();
+        self.reset_breakpoints();
+        self.refresh_modules();
+        self.init_solib_tracking();
+    }
+
+    /* Take breakpoints off the old image after an exec or a fresh `run`.
+     * They wait in `target_breakpoints` by module and offset and are set
+     * again as `refresh_modules` and the dynamic linker load their
+     * modules. Nothing is written, the old memory is gone. */
+    pub(crate) fn reset_breakpoints(&mut self) {
+        let old: Vec<(usize, Breakpoint)> = self.breakpoints.drain().collect();
+        for (addr, mut bp) in old {
+            if bp.id == 0 { continue }
+            bp.orig_byte = None;
+
+            if bp.modname.is_empty() {
+                println!("Breakpoint {} at {:#x} is not in a module and was deleted", bp.id, addr);
+                continue;
+            }
+
+            let module: Arc<String> = bp.modname.clone();
+            self.note_breakpoint_bounds(&module, bp.offset);
+            self.target_breakpoints.entry(module.to_string()).or_default().push(bp);
+        }
+
+        // Debug registers are cleared by exec and the addresses mean
+        // nothing in the new image
+        for slot in 0..self.hw_breakpoints.len() {
+            if let Some(hw) = self.hw_breakpoints[slot].take() {
+                println!("{} {} deleted because the program has changed.", hw.kind.describe(), hw.id);
+            }
+        }
+    }
+
+    /* The followed vfork child exited without exec */
+    pub(crate) fn process_exited(&mut self) {
+        self.finish_vfork_parent();
+    }
+}
+
+fn fork_name(event: i32) -> &'static str {
+    if event == libc::PTRACE_EVENT_VFORK { "vfork" } else { "fork" }
+}
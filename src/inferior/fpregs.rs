@@ -0,0 +1,639 @@
+/* x87, SSE, AVX and AVX-512 registers
+ * Fetched with PTRACE_GETREGSET as NT_X86_XSTATE, or NT_PRFPREG when the
+ * kernel has no XSAVE support. Both start with the 512 byte FXSAVE area:
+ *   0   fcw, fsw, abridged ftw, fop, fip, fdp, mxcsr
+ *   32  st0-st7, 16 bytes apiece with the 80 bit value in the low 10
+ *   160 xmm0-xmm15
+ * XSAVE components follow the 64 byte header at 512: the upper halves of
+ * ymm0-15, the AVX-512 opmasks, the upper halves of zmm0-15 and zmm16-31.
+ * Their offsets come from CPUID leaf 0xd. A component that's clear in the
+ * header's XSTATE_BV is in its initial state, all zeros.
+ */
+
+use super::expr::parse_number;
+use super::{ ffi, Inferior, InferiorState };
+
+const FCW: usize = 0;
+const FSW: usize = 2;
+const FTW: usize = 4;
+const FOP: usize = 6;
+const MXCSR: usize = 24;
+const ST_AREA: usize = 32;
+const XMM_AREA: usize = 160;
+const SW_XFEATURES: usize = 472;
+const FXSAVE_SIZE: usize = 512;
+const XSTATE_BV: usize = 512;
+
+/* XSAVE state components */
+const X87: u32 = 0;
+const SSE: u32 = 1;
+const YMM_HI128: u32 = 2;
+const OPMASK: u32 = 5;
+const ZMM_HI256: u32 = 6;
+const HI16_ZMM: u32 = 7;
+
+/// Legacy area plus the XSAVE header
+const XSAVE_HEADER_END: usize = XSTATE_BV + 64;
+
+/// MXCSR bits and their names
+pub static MXCSR_BITS: &[(u32, &str)] = &[
+    (0, "IE"), (1, "DE"), (2, "ZE"), (3, "OE"), (4, "UE"), (5, "PE"),
+    (6, "DAZ"), (7, "IM"), (8, "DM"), (9, "ZM"), (10, "OM"), (11, "UM"),
+    (12, "PM"), (15, "FZ"),
+];
+
+/* Size of the XSAVE area. CPUID 0xd subleaf 0 gives it for the features
+ * enabled in XCR0 (ebx) and for everything the CPU supports (ecx); AMX
+ * and AVX-512 can take it well past a page */
+fn xstate_size() -> usize {
+    let leaf = std::arch::x86_64::__cpuid_count(0xd, 0);
+    std::cmp::max(std::cmp::max(leaf.ebx, leaf.ecx) as usize, XSAVE_HEADER_END)
+}
+
+/* Standard format offsets, used when CPUID doesn't report a component */
+fn component_offset(component: u32) -> usize {
+    let leaf = std::arch::x86_64::__cpuid_count(0xd, component);
+    if leaf.eax != 0 {
+        return leaf.ebx as usize;
+    }
+    match component {
+        YMM_HI128 => 576,
+        OPMASK => 1088,
+        ZMM_HI256 => 1152,
+        _ => 1664,
+    }
+}
+
+/// Vector register families
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VecKind {
+    Xmm,
+    Ymm,
+    Zmm,
+    /// AVX-512 opmask k0-k7
+    K,
+}
+
+impl VecKind {
+    /// Width in bytes
+    pub fn width(self) -> usize {
+        match self {
+            VecKind::Xmm => 16,
+            VecKind::Ymm => 32,
+            VecKind::Zmm => 64,
+            VecKind::K => 8,
+        }
+    }
+}
+
+/// `xmm3`, `ymm12`, `zmm31` or `k1`
+pub fn parse_vector_name(name: &str) -> Option<(VecKind, usize)> {
+    let name = name.trim_start_matches('$').to_lowercase();
+    let (kind, num, max) = if let Some(num) = name.strip_prefix("xmm") {
+        (VecKind::Xmm, num, 32)
+    } else if let Some(num) = name.strip_prefix("ymm") {
+        (VecKind::Ymm, num, 32)
+    } else if let Some(num) = name.strip_prefix("zmm") {
+        (VecKind::Zmm, num, 32)
+    } else if let Some(num) = name.strip_prefix('k') {
+        (VecKind::K, num, 8)
+    } else {
+        return None;
+    };
+    match num.parse::<usize>() {
+        Ok(n) if n < max && !num.starts_with('+') => Some((kind, n)),
+        _ => None,
+    }
+}
+
+/// `st0`-`st7`, also written `st(0)`
+pub fn parse_st_name(name: &str) -> Option<usize> {
+    let name = name.trim_start_matches('$').to_lowercase().replace(['(', ')'], "");
+    if !name.starts_with("st") { return None }
+    match name[2..].parse::<usize>() {
+        Ok(n) if n < 8 => Some(n),
+        _ => None,
+    }
+}
+
+/// Any register handled here rather than by `registers`
+pub fn is_fp_register(name: &str) -> bool {
+    let name = name.trim_start_matches('$').to_lowercase();
+    parse_vector_name(&name).is_some()
+        || parse_st_name(&name).is_some()
+        || ["mxcsr", "fcw", "fsw", "ftw", "fop", "fctrl", "fstat", "ftag"].contains(&name.as_str())
+}
+
+/// Element type of a vector lane view
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lane {
+    Float,
+    Double,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Int128,
+}
+
+impl Lane {
+    pub fn size(self) -> usize {
+        match self {
+            Lane::Int8 => 1,
+            Lane::Int16 => 2,
+            Lane::Float | Lane::Int32 => 4,
+            Lane::Double | Lane::Int64 => 8,
+            Lane::Int128 => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lane::Float => "float",
+            Lane::Double => "double",
+            Lane::Int8 => "int8",
+            Lane::Int16 => "int16",
+            Lane::Int32 => "int32",
+            Lane::Int64 => "int64",
+            Lane::Int128 => "int128",
+        }
+    }
+}
+
+const LANES: &[Lane] = &[Lane::Float, Lane::Double, Lane::Int8, Lane::Int16, Lane::Int32, Lane::Int64, Lane::Int128];
+
+/// Lane views of a `width` byte register, `v4_float`, `v2_int64`, ...
+pub fn lane_views(width: usize) -> Vec<(String, Lane)> {
+    LANES.iter()
+        .filter(|lane| lane.size() <= width)
+        .map(|&lane| (format!("v{}_{}", width / lane.size(), lane.name()), lane))
+        .collect()
+}
+
+/// Parse a view name such as `v4_float` for a `width` byte register
+pub fn parse_lane_view(view: &str, width: usize) -> Option<Lane> {
+    lane_views(width).into_iter()
+        .find(|(name, _)| name == view)
+        .map(|(_, lane)| lane)
+}
+
+/// `{1.5, 2, 0, 0}`, integers in hex
+pub fn format_lanes(bytes: &[u8], lane: Lane) -> String {
+    let lanes: Vec<String> = bytes.chunks(lane.size())
+        .map(|b| {
+            let mut raw = [0u8; 16];
+            raw[..b.len()].copy_from_slice(b);
+            let val = u128::from_le_bytes(raw);
+            match lane {
+                Lane::Float => f32::from_bits(val as u32).to_string(),
+                Lane::Double => f64::from_bits(val as u64).to_string(),
+                _ => format!("{:#x}", val),
+            }
+        })
+        .collect();
+    format!("{{{}}}", lanes.join(", "))
+}
+
+/// Encode `{a, b, c, d}` as lanes of `lane` filling `width` bytes
+pub fn encode_lanes(values: &str, lane: Lane, width: usize) -> Result<Vec<u8>, failure::Error> {
+    let values: Vec<&str> = values.trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect();
+    let count = width / lane.size();
+    if values.len() != count {
+        return Err(failure::format_err!("Expected {} values for v{}_{}, got {}",
+                                        count, count, lane.name(), values.len()));
+    }
+
+    let mut bytes = Vec::with_capacity(width);
+    for v in values {
+        let raw: u128 = match lane {
+            Lane::Float => (parse_float(v)? as f32).to_bits() as u128,
+            Lane::Double => parse_float(v)?.to_bits() as u128,
+            _ => parse_int(v)?,
+        };
+        if lane.size() < 16 && lane != Lane::Float && lane != Lane::Double {
+            let bits = lane.size() * 8;
+            let fits = raw >> bits == 0 || (raw as i128) >> (bits - 1) == -1;
+            if !fits {
+                return Err(failure::format_err!("Value {} doesn't fit in {}", v, lane.name()));
+            }
+        }
+        bytes.extend_from_slice(&raw.to_le_bytes()[..lane.size()]);
+    }
+    Ok(bytes)
+}
+
+fn parse_float(s: &str) -> Result<f64, failure::Error> {
+    s.parse::<f64>().map_err(|_| failure::format_err!("Invalid number \"{}\"", s))
+}
+
+/* Hex or decimal, negative values sign extended to 128 bits */
+fn parse_int(s: &str) -> Result<u128, failure::Error> {
+    let (neg, digits) = match s.strip_prefix('-') { Some(digits) => (true, digits), None => (false, s) };
+    let val = if digits.starts_with("0x") || digits.starts_with("0X") {
+        u128::from_str_radix(&digits[2..], 16)
+    } else {
+        digits.parse::<u128>()
+    }.map_err(|_| failure::format_err!("Invalid number \"{}\"", s))?;
+    Ok(if neg { val.wrapping_neg() } else { val })
+}
+
+/// 80 bit extended precision to the nearest f64
+pub fn x87_to_f64(raw: &[u8]) -> f64 {
+    let mut m = [0u8; 8];
+    m.copy_from_slice(&raw[..8]);
+    let mantissa = u64::from_le_bytes(m);
+    let se = u16::from_le_bytes([raw[8], raw[9]]);
+    let sign = if se & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = (se & 0x7fff) as i32;
+
+    if exp == 0 && mantissa == 0 {
+        return sign * 0.0;
+    }
+    if exp == 0x7fff {
+        return if mantissa << 1 == 0 { sign * f64::INFINITY } else { f64::NAN };
+    }
+    let exp = exp - 16383;
+    if exp > 1023 {
+        return sign * f64::INFINITY;
+    }
+    if exp < -1100 {
+        return sign * 0.0;
+    }
+    // Scale in two steps so results in the subnormal range survive
+    let fraction = mantissa as f64 / 9_223_372_036_854_775_808.0;
+    let hi = std::cmp::max(exp, -1022);
+    sign * fraction * pow2(hi) * pow2(exp - hi)
+}
+
+/* 2^k for -1022 <= k <= 1023 */
+fn pow2(k: i32) -> f64 {
+    f64::from_bits(((k + 1023) as u64) << 52)
+}
+
+/// f64 to 80 bit extended precision, exact
+pub fn f64_to_x87(val: f64) -> [u8; 10] {
+    let bits = val.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exp = ((bits >> 52) & 0x7ff) as i32;
+    let frac = bits & ((1 << 52) - 1);
+
+    let (exp, mantissa) = if exp == 0 && frac == 0 {
+        (0, 0)
+    } else if exp == 0x7ff {
+        (0x7fff, (1 << 63) | (frac << 11))
+    } else if exp == 0 {
+        // Subnormal doubles are normal numbers here
+        let top = 63 - frac.leading_zeros() as i32;
+        (top - 1074 + 16383, frac << frac.leading_zeros())
+    } else {
+        (exp - 1023 + 16383, (1 << 63) | (frac << 11))
+    };
+
+    let mut raw = [0u8; 10];
+    raw[..8].copy_from_slice(&mantissa.to_le_bytes());
+    raw[8..].copy_from_slice(&(sign | exp as u16).to_le_bytes());
+    raw
+}
+
+/// `[ IM DM ZM OM UM PM ]` for the set bits of MXCSR, with the rounding mode
+pub fn decode_mxcsr(mxcsr: u32) -> String {
+    let mut set: Vec<&str> = MXCSR_BITS.iter()
+        .filter(|(bit, _)| mxcsr & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    set.push(match (mxcsr >> 13) & 3 {
+        0 => "RC=nearest",
+        1 => "RC=down",
+        2 => "RC=up",
+        _ => "RC=zero",
+    });
+    format!("[ {} ]", set.join(" "))
+}
+
+/// Floating point and vector state of a thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FpRegs {
+    data: Vec<u8>,
+    /// From NT_X86_XSTATE, so XSAVE components can follow the legacy area
+    xstate: bool,
+}
+
+impl FpRegs {
+    /// Wrap a raw FXSAVE (`xstate` false) or XSAVE area
+    pub fn from_bytes(data: Vec<u8>, xstate: bool) -> FpRegs {
+        let mut data = data;
+        if data.len() < FXSAVE_SIZE {
+            data.resize(FXSAVE_SIZE, 0);
+        }
+        FpRegs { data, xstate }
+    }
+
+    pub fn bytes(&self) -> &[u8] { &self.data }
+
+    fn u16_at(&self, off: usize) -> u16 {
+        u16::from_le_bytes([self.data[off], self.data[off + 1]])
+    }
+
+    fn u32_at(&self, off: usize) -> u32 {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(&self.data[off..off + 4]);
+        u32::from_le_bytes(b)
+    }
+
+    fn u64_at(&self, off: usize) -> u64 {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(&self.data[off..off + 8]);
+        u64::from_le_bytes(b)
+    }
+
+    pub fn fcw(&self) -> u16 { self.u16_at(FCW) }
+    pub fn fsw(&self) -> u16 { self.u16_at(FSW) }
+    /// Abridged tag word, one bit per physical register, set when in use
+    pub fn ftw(&self) -> u8 { self.data[FTW] }
+    pub fn fop(&self) -> u16 { self.u16_at(FOP) }
+    pub fn mxcsr(&self) -> u32 { self.u32_at(MXCSR) }
+
+    /// Physical register number of st0
+    pub fn top(&self) -> usize { ((self.fsw() >> 11) & 7) as usize }
+
+    /// Raw 80 bits of st(i)
+    pub fn st_raw(&self, i: usize) -> &[u8] {
+        &self.data[ST_AREA + i * 16..ST_AREA + i * 16 + 10]
+    }
+
+    pub fn st(&self, i: usize) -> f64 { x87_to_f64(self.st_raw(i)) }
+
+    /// Is st(i) holding a value
+    pub fn st_valid(&self, i: usize) -> bool {
+        self.ftw() & (1 << ((self.top() + i) & 7)) != 0
+    }
+
+    /// XCR0: state components the OS has enabled. x87 and SSE only when
+    /// all we have is FXSAVE.
+    pub fn features(&self) -> u64 {
+        if self.xstate && self.data.len() > XSTATE_BV { self.u64_at(SW_XFEATURES) } else { 0x3 }
+    }
+
+    pub fn has_avx(&self) -> bool { self.features() & (1 << YMM_HI128) != 0 }
+
+    pub fn has_avx512(&self) -> bool {
+        let mask = (1 << OPMASK) | (1 << ZMM_HI256) | (1 << HI16_ZMM);
+        self.features() & mask == mask
+    }
+
+    /* Byte ranges making up a vector register, with the component each
+     * range belongs to */
+    fn segments(&self, kind: VecKind, n: usize) -> Result<Vec<(u32, usize, usize)>, failure::Error> {
+        let need_avx512 = kind == VecKind::Zmm || kind == VecKind::K || n >= 16;
+        if need_avx512 && !self.has_avx512() {
+            return Err(failure::format_err!("AVX-512 state isn't available"));
+        }
+        if kind == VecKind::Ymm && !self.has_avx() {
+            return Err(failure::format_err!("AVX state isn't available"));
+        }
+
+        let width = kind.width();
+        if kind == VecKind::K {
+            return Ok(vec![(OPMASK, component_offset(OPMASK) + n * 8, 8)]);
+        }
+        if n >= 16 {
+            return Ok(vec![(HI16_ZMM, component_offset(HI16_ZMM) + (n - 16) * 64, width)]);
+        }
+
+        let mut segs = vec![(SSE, XMM_AREA + n * 16, 16)];
+        if width > 16 {
+            segs.push((YMM_HI128, component_offset(YMM_HI128) + n * 16, 16));
+        }
+        if width > 32 {
+            segs.push((ZMM_HI256, component_offset(ZMM_HI256) + n * 32, 32));
+        }
+        Ok(segs)
+    }
+
+    fn in_use(&self, component: u32) -> bool {
+        component <= SSE || !self.xstate || self.u64_at(XSTATE_BV) & (1 << component) != 0
+    }
+
+    /// Contents of a vector or opmask register
+    pub fn vector(&self, kind: VecKind, n: usize) -> Result<Vec<u8>, failure::Error> {
+        let mut bytes = Vec::with_capacity(kind.width());
+        for (component, off, len) in self.segments(kind, n)? {
+            if !self.in_use(component) || off + len > self.data.len() {
+                bytes.resize(bytes.len() + len, 0);
+            } else {
+                bytes.extend_from_slice(&self.data[off..off + len]);
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Replace a vector or opmask register, `bytes` is zero extended
+    pub fn set_vector(&mut self, kind: VecKind, n: usize, bytes: &[u8]) -> Result<(), failure::Error> {
+        if bytes.len() > kind.width() {
+            return Err(failure::format_err!("Value is wider than {} bytes", kind.width()));
+        }
+        let mut bytes = bytes.to_vec();
+        bytes.resize(kind.width(), 0);
+
+        let mut pos = 0;
+        for (component, off, len) in self.segments(kind, n)? {
+            if off + len > self.data.len() {
+                return Err(failure::format_err!("Register state is too short for {:?}{}", kind, n));
+            }
+            // Bringing a component out of its initial state, the rest of it
+            // has to read as zeros
+            if !self.in_use(component) {
+                self.clear_component(component);
+            }
+            self.data[off..off + len].copy_from_slice(&bytes[pos..pos + len]);
+            self.mark_in_use(component);
+            pos += len;
+        }
+        Ok(())
+    }
+
+    fn clear_component(&mut self, component: u32) {
+        let off = component_offset(component);
+        let size = std::arch::x86_64::__cpuid_count(0xd, component).eax as usize;
+        let end = std::cmp::min(off + size, self.data.len());
+        for b in &mut self.data[off..end] { *b = 0; }
+    }
+
+    fn mark_in_use(&mut self, component: u32) {
+        if !self.xstate { return }
+        let bv = self.u64_at(XSTATE_BV) | (1 << component);
+        self.data[XSTATE_BV..XSTATE_BV + 8].copy_from_slice(&bv.to_le_bytes());
+    }
+
+    /// Set st(i), marking it valid
+    pub fn set_st(&mut self, i: usize, val: f64) {
+        let off = ST_AREA + i * 16;
+        self.data[off..off + 10].copy_from_slice(&f64_to_x87(val));
+        self.data[FTW] |= 1 << ((self.top() + i) & 7);
+        self.mark_in_use(X87);
+    }
+
+    /// Set mxcsr or one of the x87 control words
+    pub fn set_control(&mut self, name: &str, val: u64) -> Result<(), failure::Error> {
+        let (off, size) = match name {
+            "mxcsr" => (MXCSR, 4),
+            "fcw" | "fctrl" => (FCW, 2),
+            "fsw" | "fstat" => (FSW, 2),
+            "ftw" | "ftag" => (FTW, 1),
+            "fop" => (FOP, 2),
+            _ => return Err(failure::format_err!("Invalid register \"${}\"", name)),
+        };
+        if size < 8 && val >> (size * 8) != 0 {
+            return Err(failure::format_err!("Value {:#x} doesn't fit in ${}", val, name));
+        }
+        self.data[off..off + size].copy_from_slice(&val.to_le_bytes()[..size]);
+        self.mark_in_use(if name == "mxcsr" { SSE } else { X87 });
+        Ok(())
+    }
+}
+
+impl Inferior {
+    /// Floating point and vector registers of the current thread
+    pub fn fp_registers(&self) -> Result<FpRegs, failure::Error> {
+        let mut buf = vec![0u8; xstate_size()];
+        if let Ok(len) = ffi::get_regset(self.tid, ffi::NT_X86_XSTATE, &mut buf) {
+            buf.truncate(len);
+            return Ok(FpRegs::from_bytes(buf, true));
+        }
+
+        let mut buf = vec![0u8; FXSAVE_SIZE];
+        let len = ffi::get_regset(self.tid, ffi::NT_PRFPREG, &mut buf)
+            .map_err(|e| failure::format_err!("Unable to read floating point registers: {}", e))?;
+        buf.truncate(len);
+        Ok(FpRegs::from_bytes(buf, false))
+    }
+
+    /// Write the registers back to the current thread
+    pub fn set_fp_registers(&self, regs: &FpRegs) -> Result<(), failure::Error> {
+        let nt = if regs.xstate { ffi::NT_X86_XSTATE } else { ffi::NT_PRFPREG };
+        ffi::set_regset(self.tid, nt, &regs.data)
+            .map_err(|e| failure::format_err!("Unable to write floating point registers: {}", e))
+    }
+
+    /// `set $xmm0.v4_int32 = {1, 2, 3, 4}`, `set $ymm1 = 0x41`,
+    /// `set $st0 = 1.5` or `set $mxcsr = 0x1f80`
+    pub fn set_fp_register(&mut self, name: &str, view: Option<&str>, value: &str) -> Result<(), failure::Error> {
+        if self.state != InferiorState::Stopped {
+            return Err(failure::format_err!("The program is not being run or is not stopped"));
+        }
+        let name = name.trim_start_matches('$').to_lowercase();
+        let value = value.trim();
+        let mut regs = self.fp_registers()?;
+
+        if let Some((kind, n)) = parse_vector_name(&name) {
+            let bytes = match view {
+                Some(view) => {
+                    let lane = parse_lane_view(view, kind.width())
+                        .ok_or_else(|| failure::format_err!("${} has no view \"{}\"", name, view))?;
+                    encode_lanes(value, lane, kind.width())?
+                },
+                None => parse_int(value)?.to_le_bytes().to_vec(),
+            };
+            regs.set_vector(kind, n, &bytes[..std::cmp::min(bytes.len(), kind.width())])?;
+        } else if let Some(i) = parse_st_name(&name) {
+            regs.set_st(i, parse_float(value)?);
+        } else {
+            regs.set_control(&name, parse_number(value)?)?;
+        }
+        self.set_fp_registers(&regs)
+    }
+
+    /// `info registers xmm0`: Every lane view of a vector register, or
+    /// the value of an x87/SSE control register
+    pub fn show_fp_register(&self, name: &str) -> Result<(), failure::Error> {
+        let name = name.trim_start_matches('$').to_lowercase();
+        let regs = self.fp_registers()?;
+
+        if let Some((kind, n)) = parse_vector_name(&name) {
+            let bytes = regs.vector(kind, n)?;
+            if kind == VecKind::K {
+                println!("{:<10}{:#018x}", name, u64::from_le_bytes(to_array8(&bytes)));
+                return Ok(());
+            }
+            println!("{}", name);
+            for (view, lane) in lane_views(kind.width()) {
+                println!("  {:<12}= {}", view, format_lanes(&bytes, lane));
+            }
+        } else if let Some(i) = parse_st_name(&name) {
+            println!("{:<10}{}", name, format_st(&regs, i));
+        } else {
+            match name.as_str() {
+                "mxcsr" => println!("{:<10}{:#010x}  {}", name, regs.mxcsr(), decode_mxcsr(regs.mxcsr())),
+                "fcw" | "fctrl" => println!("{:<10}{:#06x}", name, regs.fcw()),
+                "fsw" | "fstat" => println!("{:<10}{:#06x}  TOP: {}", name, regs.fsw(), regs.top()),
+                "ftw" | "ftag" => println!("{:<10}{:#04x}", name, regs.ftw()),
+                "fop" => println!("{:<10}{:#06x}", name, regs.fop()),
+                _ => return Err(failure::format_err!("Invalid register \"{}\"", name)),
+            }
+        }
+        Ok(())
+    }
+
+    /// `info float`: The x87 stack, control words and MXCSR
+    pub fn show_float(&self) -> Result<(), failure::Error> {
+        let regs = self.fp_registers()?;
+        for i in 0..8 {
+            println!("st{:<8}{}", i, format_st(&regs, i));
+        }
+        println!();
+        println!("fctrl     {:#06x}", regs.fcw());
+        println!("fstat     {:#06x}  TOP: {}", regs.fsw(), regs.top());
+        println!("ftag      {:#04x}", regs.ftw());
+        println!("fop       {:#06x}", regs.fop());
+        println!("mxcsr     {:#010x}  {}", regs.mxcsr(), decode_mxcsr(regs.mxcsr()));
+        Ok(())
+    }
+
+    /// `info vector`: Vector registers at their widest, as 128 bit lanes
+    pub fn show_vector(&self) -> Result<(), failure::Error> {
+        let regs = self.fp_registers()?;
+        let (kind, count) = if regs.has_avx512() {
+            (VecKind::Zmm, 32)
+        } else if regs.has_avx() {
+            (VecKind::Ymm, 16)
+        } else {
+            (VecKind::Xmm, 16)
+        };
+        let prefix = match kind { VecKind::Zmm => "zmm", VecKind::Ymm => "ymm", _ => "xmm" };
+
+        for n in 0..count {
+            let bytes = regs.vector(kind, n)?;
+            println!("{:<10}{}", format!("{}{}", prefix, n), format_lanes(&bytes, Lane::Int128));
+        }
+        if kind == VecKind::Zmm {
+            for n in 0..8 {
+                let bytes = regs.vector(VecKind::K, n)?;
+                println!("{:<10}{:#018x}", format!("k{}", n), u64::from_le_bytes(to_array8(&bytes)));
+            }
+        }
+        println!("{:<10}{:#010x}  {}", "mxcsr", regs.mxcsr(), decode_mxcsr(regs.mxcsr()));
+        Ok(())
+    }
+}
+
+fn format_st(regs: &FpRegs, i: usize) -> String {
+    let raw = regs.st_raw(i);
+    let hex: String = raw.iter().rev().map(|b| format!("{:02x}", b)).collect();
+    if regs.st_valid(i) {
+        format!("0x{}  {}", hex, regs.st(i))
+    } else {
+        format!("0x{}  <empty>", hex)
+    }
+}
+
+fn to_array8(bytes: &[u8]) -> [u8; 8] {
+    let mut a = [0u8; 8];
+    a.copy_from_slice(&bytes[..8]);
+    a
+}
@@ -0,0 +1,335 @@
+/* Launching the inferior
+ * Everything execve() needs is built before the fork. That way the child
+ * only makes system calls between fork and exec: chdir, redirect stdio,
+ * setrlimit, personality, PTRACE_TRACEME and execve.
+ */
+
+use nix::errno::Errno;
+use nix::sys::ptrace;
+
+use std::ffi::{ CString, OsStr, OsString };
+use std::os::unix::ffi::OsStrExt;
+use std::path::{ Path, PathBuf };
+
+use super::{ ffi, Inferior };
+
+/// Personality flags for `set personality <name> on|off`
+pub static PERSONALITY_FLAGS: &[(&str, u64)] = &[
+    ("read-implies-exec", ffi::READ_IMPLIES_EXEC),
+    ("addr-compat-layout", ffi::ADDR_COMPAT_LAYOUT),
+    ("addr-limit-32bit", ffi::ADDR_LIMIT_32BIT),
+    ("mmap-page-zero", ffi::MMAP_PAGE_ZERO),
+];
+
+/// Resource limits for `set rlimit <name> <soft> [hard]`
+pub static RLIMITS: &[(&str, libc::__rlimit_resource_t)] = &[
+    ("core", libc::RLIMIT_CORE),
+    ("stack", libc::RLIMIT_STACK),
+    ("nofile", libc::RLIMIT_NOFILE),
+    ("as", libc::RLIMIT_AS),
+    ("data", libc::RLIMIT_DATA),
+    ("fsize", libc::RLIMIT_FSIZE),
+    ("cpu", libc::RLIMIT_CPU),
+];
+
+/// A limit applied in the child before execve
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rlimit {
+    pub resource: libc::__rlimit_resource_t,
+    pub soft: u64,
+    /// Keep the current hard limit when not given, unless it's below `soft`
+    pub hard: Option<u64>,
+}
+
+/// "unlimited" or a number with an optional k/m/g suffix
+pub fn parse_rlimit_value(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+    if s == "unlimited" || s == "infinity" {
+        return Some(libc::RLIM_INFINITY);
+    }
+    let (num, mult) = match s.chars().last()? {
+        'k' => (&s[..s.len() - 1], 1 << 10),
+        'm' => (&s[..s.len() - 1], 1 << 20),
+        'g' => (&s[..s.len() - 1], 1 << 30),
+        _ => (&s[..], 1),
+    };
+    num.parse::<u64>().ok()?.checked_mul(mult)
+}
+
+fn rlimit_name(resource: libc::__rlimit_resource_t) -> &'static str {
+    RLIMITS.iter().find(|(_, r)| *r == resource).map(|(name, _)| *name).unwrap_or("?")
+}
+
+fn rlimit_value(val: u64) -> String {
+    if val == libc::RLIM_INFINITY { String::from("unlimited") } else { val.to_string() }
+}
+
+/* Strings for execve and the NULL terminated array pointing into them,
+ * made before the fork. The strings only need to stay alive. */
+struct ExecArray {
+    _strings: Vec<CString>,
+    ptrs: Vec<*const libc::c_char>,
+}
+
+impl ExecArray {
+    fn new(strings: Vec<CString>) -> ExecArray {
+        let ptrs = strings.iter()
+            .map(|s| s.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect();
+        ExecArray { _strings: strings, ptrs }
+    }
+}
+
+/// execve() arguments and process setup for the child
+pub(crate) struct Launch {
+    path: CString,
+    argv: ExecArray,
+    envp: ExecArray,
+    cwd: CString,
+    stdin: Option<CString>,
+    stdout: Option<CString>,
+    personality: u64,
+    disable_randomization: bool,
+    /// With the message to show if setting it fails, made before the fork
+    rlimits: Vec<(Rlimit, CString)>,
+}
+
+impl Inferior {
+    /// `set args`: Arguments after argv[0] for the next `run`
+    pub fn set_args(&mut self, args: Vec<String>) { self.args = args; }
+
+    /// `set env NAME=VALUE`
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.env.insert(OsString::from(name), OsString::from(value));
+    }
+
+    /// `unset env [NAME]`: Without a name the environment is emptied
+    pub fn unset_env(&mut self, name: Option<&str>) {
+        match name {
+            Some(name) => { self.env.remove(OsStr::new(name)); },
+            None => self.env.clear(),
+        }
+    }
+
+    /// `set cwd <dir>`
+    pub fn set_cwd(&mut self, dir: &str) -> Result<(), failure::Error> {
+        let dir = PathBuf::from(dir);
+        if !dir.is_dir() {
+            return Err(failure::format_err!("{}: No such directory.", dir.display()));
+        }
+        self.cwd = dir.canonicalize()?;
+        Ok(())
+    }
+
+    /// `run ... < in > out`: Files for the inferior's stdin and stdout
+    pub fn set_redirects(&mut self, stdin: Option<PathBuf>, stdout: Option<PathBuf>) {
+        self.stdin = stdin;
+        self.stdout = stdout;
+    }
+
+    /// `set disable-randomization on|off`
+    pub fn set_disable_randomization(&mut self, val: bool) { self.disable_randomization = val; }
+
+    /// `set personality <name> on|off`
+    pub fn set_personality_flag(&mut self, name: &str, val: bool) -> Result<(), failure::Error> {
+        let flag = PERSONALITY_FLAGS.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, flag)| *flag)
+            .ok_or_else(|| failure::format_err!("Unknown personality flag \"{}\"", name))?;
+        if val { self.personality |= flag; } else { self.personality &= !flag; }
+        Ok(())
+    }
+
+    /// `set rlimit <name> <soft|unlimited> [hard]`
+    pub fn set_rlimit(&mut self, name: &str, soft: u64, hard: Option<u64>) -> Result<(), failure::Error> {
+        let resource = RLIMITS.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, r)| *r)
+            .ok_or_else(|| failure::format_err!("Unknown resource limit \"{}\"", name))?;
+        if let Some(hard) = hard {
+            if soft > hard {
+                return Err(failure::format_err!("Soft limit is above the hard limit"));
+            }
+        }
+        self.rlimits.retain(|r| r.resource != resource);
+        self.rlimits.push(Rlimit { resource, soft, hard });
+        Ok(())
+    }
+
+    /// `info launch`: Settings the next `run` uses
+    pub fn show_launch(&self) {
+        println!("Program:             {}", if self.location.is_empty() { "(none)" } else { self.location.as_str() });
+        println!("Arguments:           {}", self.args.join(" "));
+        println!("Working directory:   {}", self.cwd.display());
+        println!("Environment:         {} variables", self.env.len());
+        if let Some(stdin) = &self.stdin { println!("stdin:               {}", stdin.display()); }
+        if let Some(stdout) = &self.stdout { println!("stdout:              {}", stdout.display()); }
+        println!("Disable ASLR:        {}", if self.disable_randomization { "on" } else { "off" });
+
+        let flags: Vec<&str> = PERSONALITY_FLAGS.iter()
+            .filter(|(_, flag)| self.personality & flag != 0)
+            .map(|(name, _)| *name)
+            .collect();
+        println!("Personality:         {}", if flags.is_empty() { String::from("default") } else { flags.join(", ") });
+
+        for r in &self.rlimits {
+            println!("RLIMIT_{:<13}{} (hard {})",
+                     rlimit_name(r.resource).to_uppercase(),
+                     rlimit_value(r.soft),
+                     r.hard.map(rlimit_value).unwrap_or_else(|| String::from("unchanged")));
+        }
+        if self.state != super::InferiorState::None && self.state != super::InferiorState::Dead {
+            println!("Inferior ASLR:       {}", if self.aslr { "enabled" } else { "disabled" });
+        }
+    }
+
+    /* Is address space randomization in effect for `pid` */
+    pub(crate) fn aslr_enabled(&self) -> bool {
+        let system = std::fs::read_to_string("/proc/sys/kernel/randomize_va_space")
+            .map(|s| s.trim() != "0")
+            .unwrap_or(true);
+        let persona = std::fs::read_to_string(format!("/proc/{}/personality", self.pid))
+            .ok()
+            .and_then(|s| u64::from_str_radix(s.trim(), 16).ok())
+            .unwrap_or(0);
+        system && persona & ffi::ADDR_NO_RANDOMIZE == 0
+    }
+
+    /* Build everything the child needs from the launch settings */
+    pub(crate) fn launch(&self) -> Result<Launch, failure::Error> {
+        let path = cstring(self.location.as_bytes())?;
+
+        let mut argv = vec![path.clone()];
+        for arg in &self.args {
+            argv.push(cstring(arg.as_bytes())?);
+        }
+
+        let mut env: Vec<(&OsString, &OsString)> = self.env.iter().collect();
+        env.sort();
+        let mut envp = Vec::new();
+        for (name, value) in env {
+            let mut var = name.as_bytes().to_vec();
+            var.push(b'=');
+            var.extend_from_slice(value.as_bytes());
+            envp.push(cstring(&var)?);
+        }
+
+        let mut rlimits = Vec::new();
+        for r in &self.rlimits {
+            let what = format!("setrlimit RLIMIT_{}", rlimit_name(r.resource).to_uppercase());
+            rlimits.push((*r, cstring(what.as_bytes())?));
+        }
+
+        Ok(Launch {
+            path,
+            argv: ExecArray::new(argv),
+            envp: ExecArray::new(envp),
+            cwd: cstring(self.cwd.as_os_str().as_bytes())?,
+            stdin: redirect_path(&self.stdin)?,
+            stdout: redirect_path(&self.stdout)?,
+            personality: self.personality,
+            disable_randomization: self.disable_randomization,
+            rlimits,
+        })
+    }
+}
+
+impl Launch {
+    /* Runs in the forked child and never returns */
+    pub(crate) fn exec(&self) -> ! {
+        unsafe {
+            if libc::chdir(self.cwd.as_ptr()) != 0 {
+                child_fail(b"chdir");
+            }
+        }
+        if let Some(stdin) = &self.stdin {
+            redirect(stdin, libc::STDIN_FILENO, libc::O_RDONLY);
+        }
+        if let Some(stdout) = &self.stdout {
+            redirect(stdout, libc::STDOUT_FILENO, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC);
+        }
+
+        for (r, what) in &self.rlimits {
+            set_rlimit(r, what);
+        }
+        // Flags rdbg itself was started with must not leak into the
+        // inferior, so every flag we manage is set or cleared explicitly
+        let managed = PERSONALITY_FLAGS.iter()
+            .fold(ffi::ADDR_NO_RANDOMIZE, |mask, (_, flag)| mask | flag);
+        let mut persona = match ffi::get_personality() {
+            Ok(old) => (old & !managed) | self.personality,
+            Err(_) => child_fail(b"personality"),
+        };
+        if self.disable_randomization {
+            persona |= ffi::ADDR_NO_RANDOMIZE;
+        }
+        if ffi::set_personality(persona).is_err() {
+            child_fail(b"personality");
+        }
+
+        // Begin Tracing
+        if ptrace::traceme().is_err() {
+            child_fail(b"PTRACE_TRACEME");
+        }
+
+        unsafe {
+            libc::execve(self.path.as_ptr(), self.argv.ptrs.as_ptr(), self.envp.ptrs.as_ptr());
+        }
+        child_fail(b"execve");
+    }
+}
+
+/* setrlimit in the child */
+fn set_rlimit(r: &Rlimit, what: &CString) {
+    unsafe {
+        let mut cur = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        if libc::getrlimit(r.resource, &mut cur) != 0 {
+            child_fail(b"getrlimit");
+        }
+        let hard = match r.hard {
+            Some(hard) => hard,
+            None if cur.rlim_max < r.soft => r.soft,
+            None => cur.rlim_max,
+        };
+        let new = libc::rlimit { rlim_cur: r.soft, rlim_max: hard };
+        if libc::setrlimit(r.resource, &new) != 0 {
+            child_fail(what.as_bytes());
+        }
+    }
+}
+
+/* Open `path` onto `fd` in the child */
+fn redirect(path: &CString, fd: libc::c_int, flags: libc::c_int) {
+    unsafe {
+        let file = libc::open(path.as_ptr(), flags, 0o666);
+        if file < 0 || libc::dup2(file, fd) < 0 {
+            child_fail(path.as_bytes());
+        }
+        libc::close(file);
+    }
+}
+
+/* Report why the child couldn't start. The parent sees exit code 127.
+ * Nothing but write and _exit, another thread may have held the malloc
+ * or stdio locks when we forked. */
+fn child_fail(what: &[u8]) -> ! {
+    let err = Errno::last().desc();
+    for part in &[b"rdbg: " as &[u8], what, b": ", err.as_bytes(), b"\n"] {
+        unsafe { libc::write(libc::STDERR_FILENO, part.as_ptr() as *const libc::c_void, part.len()); }
+    }
+    unsafe { libc::_exit(127) }
+}
+
+
+fn redirect_path(path: &Option<PathBuf>) -> Result<Option<CString>, failure::Error> {
+    match path {
+        Some(path) => Ok(Some(cstring(Path::new(path).as_os_str().as_bytes())?)),
+        None => Ok(None),
+    }
+}
+
+fn cstring(bytes: &[u8]) -> Result<CString, failure::Error> {
+    CString::new(bytes)
+        .map_err(|_| failure::format_err!("\"{}\" contains a NUL byte", String::from_utf8_lossy(bytes)))
+}
@@ -0,0 +1,305 @@
+/* DWARF line tables
+ * Decodes the .debug_line programs of a file (DWARF 2 to 5) into rows of
+ * address, file and line, enough to turn `file.c:line` into an address.
+ */
+
+/// One row of the line table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineRow {
+    /// Link time address
+    pub address: u64,
+    pub file: String,
+    pub line: u32,
+    /// Recommended place for a breakpoint
+    pub is_stmt: bool,
+    /// First address past the end of a sequence, not an instruction
+    pub end_sequence: bool,
+}
+
+/* DW_LNCT_* content types and DW_FORM_* forms of DWARF 5 entry formats */
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+/* Little endian reader over a DWARF section, also used for CFI */
+pub(crate) struct Reader<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> { self.bytes(1).map(|b| b[0]) }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        let b = self.bytes(8)?;
+        let mut word = [0u8; 8];
+        word.copy_from_slice(b);
+        Some(u64::from_le_bytes(word))
+    }
+
+    /* 4 or 8 bytes depending on the unit's format */
+    pub(crate) fn offset(&mut self, dwarf64: bool) -> Option<u64> {
+        if dwarf64 { self.u64() } else { self.u32().map(u64::from) }
+    }
+
+    pub(crate) fn uleb(&mut self) -> Option<u64> {
+        let mut val = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 { val |= u64::from(b & 0x7f) << shift; }
+            shift += 7;
+            if b & 0x80 == 0 { return Some(val) }
+        }
+    }
+
+    pub(crate) fn sleb(&mut self) -> Option<i64> {
+        let mut val = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 { val |= i64::from(b & 0x7f) << shift; }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 { val |= -1 << shift; }
+                return Some(val);
+            }
+        }
+    }
+
+    pub(crate) fn cstr(&mut self) -> Option<String> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        self.pos += len + 1;
+        Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+}
+
+fn string_at(section: &[u8], offset: u64) -> Option<String> {
+    let mut r = Reader { data: section, pos: offset as usize };
+    r.cstr()
+}
+
+/* DWARF 5 directory or file entry, as (path, directory index) */
+fn read_entry(r: &mut Reader, formats: &[(u64, u64)], dwarf64: bool,
+              line_str: &[u8], debug_str: &[u8]) -> Option<(String, u64)> {
+    let mut path = String::new();
+    let mut dir = 0;
+    for &(content, form) in formats {
+        let mut text = None;
+        let mut num = None;
+        match form {
+            DW_FORM_STRING => text = r.cstr(),
+            DW_FORM_LINE_STRP => text = string_at(line_str, r.offset(dwarf64)?),
+            DW_FORM_STRP => text = string_at(debug_str, r.offset(dwarf64)?),
+            DW_FORM_UDATA => num = r.uleb(),
+            DW_FORM_DATA1 => num = r.u8().map(u64::from),
+            DW_FORM_DATA2 => num = r.u16().map(u64::from),
+            DW_FORM_DATA4 => num = r.u32().map(u64::from),
+            DW_FORM_DATA8 => num = r.u64(),
+            DW_FORM_DATA16 => { r.bytes(16)?; },
+            DW_FORM_BLOCK => { let len = r.uleb()? as usize; r.bytes(len)?; },
+            _ => return None,
+        }
+        match content {
+            DW_LNCT_PATH => path = text.unwrap_or_default(),
+            DW_LNCT_DIRECTORY_INDEX => dir = num.unwrap_or(0),
+            _ => {},
+        }
+    }
+    Some((path, dir))
+}
+
+fn entry_formats(r: &mut Reader) -> Option<Vec<(u64, u64)>> {
+    let count = r.u8()?;
+    (0..count).map(|_| Some((r.uleb()?, r.uleb()?))).collect()
+}
+
+fn join(dir: Option<&String>, file: String) -> String {
+    match dir {
+        Some(dir) if !file.starts_with('/') && !dir.is_empty() => format!("{}/{}", dir, file),
+        _ => file,
+    }
+}
+
+/// Rows of every line program in `debug_line`. `line_str` and `debug_str`
+/// are .debug_line_str and .debug_str, only used by DWARF 5.
+pub fn parse_line_programs(debug_line: &[u8], line_str: &[u8], debug_str: &[u8]) -> Vec<LineRow> {
+    let mut rows = Vec::new();
+    let mut pos = 0;
+    while pos < debug_line.len() {
+        match parse_unit(debug_line, pos, line_str, debug_str, &mut rows) {
+            Some(next) if next > pos => pos = next,
+            _ => break,
+        }
+    }
+    rows
+}
+
+/* Decode the unit at `start` into `rows`, returning where the next starts */
+fn parse_unit(data: &[u8], start: usize, line_str: &[u8], debug_str: &[u8],
+              rows: &mut Vec<LineRow>) -> Option<usize> {
+    let mut r = Reader { data, pos: start };
+    let (length, dwarf64) = match r.u32()? {
+        0xffff_ffff => (r.u64()?, true),
+        len => (u64::from(len), false),
+    };
+    let end = r.pos.checked_add(length as usize)?;
+    if end > data.len() { return None }
+
+    let version = r.u16()?;
+    if !(2..=5).contains(&version) { return Some(end) }
+    if version >= 5 {
+        r.u8()?;  // address_size
+        r.u8()?;  // segment_selector_size
+    }
+    let header_length = r.offset(dwarf64)? as usize;
+    let program = r.pos.checked_add(header_length)?;
+
+    let min_inst = u64::from(r.u8()?);
+    if version >= 4 { r.u8()?; }  // maximum_operations_per_instruction
+    let default_is_stmt = r.u8()? != 0;
+    let line_base = i64::from(r.u8()? as i8);
+    let line_range = u64::from(r.u8()?);
+    let opcode_base = r.u8()?;
+    let opcode_lengths = r.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+    if line_range == 0 { return Some(end) }
+
+    let mut dirs: Vec<String> = Vec::new();
+    let mut files: Vec<String> = Vec::new();
+    if version >= 5 {
+        let formats = entry_formats(&mut r)?;
+        for _ in 0..r.uleb()? {
+            dirs.push(read_entry(&mut r, &formats, dwarf64, line_str, debug_str)?.0);
+        }
+        let formats = entry_formats(&mut r)?;
+        for _ in 0..r.uleb()? {
+            let (name, dir) = read_entry(&mut r, &formats, dwarf64, line_str, debug_str)?;
+            files.push(join(dirs.get(dir as usize), name));
+        }
+    } else {
+        // Directory 0 and file 0 are the compilation unit's own
+        dirs.push(String::new());
+        loop {
+            let dir = r.cstr()?;
+            if dir.is_empty() { break }
+            dirs.push(dir);
+        }
+        files.push(String::new());
+        loop {
+            let name = r.cstr()?;
+            if name.is_empty() { break }
+            let dir = r.uleb()?;
+            r.uleb()?;  // mtime
+            r.uleb()?;  // length
+            files.push(join(dirs.get(dir as usize), name));
+        }
+    }
+
+    r.pos = program;
+    let (mut address, mut file, mut line, mut is_stmt) = (0u64, 1u64, 1i64, default_is_stmt);
+    let file_name = |files: &Vec<String>, idx: u64| files.get(idx as usize).cloned().unwrap_or_default();
+    while r.pos < end {
+        let op = r.u8()?;
+        let mut emit = false;
+        let mut end_sequence = false;
+        if op >= opcode_base {
+            let adj = u64::from(op - opcode_base);
+            address = address.wrapping_add((adj / line_range) * min_inst);
+            line += line_base + (adj % line_range) as i64;
+            emit = true;
+        } else {
+            match op {
+                0 => {
+                    let len = r.uleb()? as usize;
+                    let next = r.pos.checked_add(len)?;
+                    match r.u8()? {
+                        1 => { emit = true; end_sequence = true; },
+                        2 => address = r.u64()?,
+                        3 => {
+                            let name = r.cstr()?;
+                            let dir = r.uleb()?;
+                            files.push(join(dirs.get(dir as usize), name));
+                        },
+                        _ => {},
+                    }
+                    r.pos = next;
+                },
+                1 => emit = true,
+                2 => address = address.wrapping_add(r.uleb()? * min_inst),
+                3 => line += r.sleb()?,
+                4 => file = r.uleb()?,
+                5 => { r.uleb()?; },
+                6 => is_stmt = !is_stmt,
+                7 => {},
+                8 => address = address.wrapping_add(((255 - u64::from(opcode_base)) / line_range) * min_inst),
+                9 => address = address.wrapping_add(u64::from(r.u16()?)),
+                _ => {
+                    for _ in 0..opcode_lengths[op as usize - 1] {
+                        r.uleb()?;
+                    }
+                },
+            }
+        }
+
+        if emit {
+            rows.push(LineRow {
+                address,
+                file: file_name(&files, file),
+                line: line as u32,
+                is_stmt,
+                end_sequence,
+            });
+        }
+        if end_sequence {
+            address = 0;
+            file = 1;
+            line = 1;
+            is_stmt = default_is_stmt;
+        }
+    }
+    Some(end)
+}
+
+/// Does the path in the line table name the file the user gave
+pub fn file_matches(path: &str, wanted: &str) -> bool {
+    path == wanted || path.ends_with(&format!("/{}", wanted))
+}
+
+/// Address for `file:line`, or for the closest line after it that has
+/// code, the same as gdb
+pub fn line_address(rows: &[LineRow], file: &str, line: u32) -> Option<u64> {
+    let candidates: Vec<&LineRow> = rows.iter()
+        .filter(|row| row.is_stmt && !row.end_sequence && row.line >= line && file_matches(&row.file, file))
+        .collect();
+    let best = candidates.iter().map(|row| row.line).min()?;
+    candidates.iter()
+        .filter(|row| row.line == best)
+        .map(|row| row.address)
+        .min()
+}
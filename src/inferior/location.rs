@@ -0,0 +1,185 @@
+/* Locations
+ * What break, x, disas, until and jump take:
+ *
+ *   0x401000, 401000        an address
+ *   *<expr>                 an expression, `*$rsp + 8`
+ *   main, main+0x10         a symbol in any loaded module
+ *   libc.so.6!malloc+4      a symbol in one module
+ *   libc.so.6+0x1234        an offset into a module
+ *   main.c:42               a source line
+ *
+ * Everything is turned into an address with the module bases of the
+ * running process.
+ */
+
+use std::fmt;
+use std::path::Path;
+
+use super::expr::{ self, Expr };
+use super::pending::{ module_matches, parse_module_location, ModuleLocation, ModuleTarget };
+use super::symbols::{ line_offset, SymbolTable };
+use super::{ parse_addr, Inferior };
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    Address(usize),
+    Expr(Expr),
+    /// Symbol and offset, looked up in every module
+    Symbol(String, usize),
+    Module(ModuleLocation),
+    /// Source file and line
+    Line(String, u32),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Address(addr) => write!(f, "{:#x}", addr),
+            Location::Expr(e) => write!(f, "*{}", e),
+            Location::Symbol(name, 0) => write!(f, "{}", name),
+            Location::Symbol(name, off) => write!(f, "{}+{:#x}", name, off),
+            Location::Module(loc) => write!(f, "{}", loc),
+            Location::Line(file, line) => write!(f, "{}:{}", file, line),
+        }
+    }
+}
+
+/* Could this be a symbol, C++ `ns::name` included */
+fn is_symbol_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == '.' => {},
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || "_.$@:".contains(c))
+}
+
+/* Bare hex like `401000`, as long as it has a digit so `add` is a symbol */
+fn is_bare_address(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_hexdigit()) && s.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Parse a location
+pub fn parse_location(s: &str) -> Result<Location, failure::Error> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(failure::format_err!("Please provide a location"));
+    }
+
+    if let Some(expr) = s.strip_prefix('*') {
+        return Ok(Location::Expr(expr::parse(expr)?));
+    }
+    if s.starts_with("0x") || s.starts_with("0X") {
+        if let Ok(addr) = expr::parse_number(s) {
+            return Ok(Location::Address(addr as usize));
+        }
+    }
+    if is_bare_address(s) {
+        return Ok(Location::Address(parse_addr(s)?));
+    }
+
+    if let Some(pos) = s.rfind(':') {
+        if let Ok(line) = s[pos + 1..].parse::<u32>() {
+            if pos > 0 && !s[..pos].ends_with(':') {
+                return Ok(Location::Line(s[..pos].to_string(), line));
+            }
+        }
+    }
+
+    if let Some(loc) = parse_module_location(s) {
+        // `name+off` is a symbol unless the name looks like a file
+        return Ok(match loc.target {
+            ModuleTarget::Offset(off) if !loc.module.contains('.') && is_symbol_name(&loc.module) => {
+                Location::Symbol(loc.module, off)
+            },
+            _ => Location::Module(loc),
+        });
+    }
+    if is_symbol_name(s) {
+        return Ok(Location::Symbol(s.to_string(), 0));
+    }
+
+    Ok(Location::Expr(expr::parse(s)?))
+}
+
+impl Inferior {
+    /* Loaded modules, the executable first */
+    pub(crate) fn modules_by_priority(&self) -> Vec<(String, usize)> {
+        let exe = Path::new(&self.location).file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut modules: Vec<(String, usize)> = self.modules.iter().cloned().collect();
+        modules.sort_by_key(|(name, base)| (*name != exe, *base));
+        modules
+    }
+
+    /* Runtime address of `file:line`, searching every module */
+    fn find_line(&self, file: &str, line: u32) -> Result<usize, failure::Error> {
+        let mut err = failure::format_err!("No source file named {}", file);
+        for (_, base) in self.modules_by_priority() {
+            let path = match self.module_path(base) {
+                Some(path) => path,
+                None => continue,
+            };
+            match line_offset(&path, file, line) {
+                Ok(off) => return Ok(base + off),
+                // Keep the error from the module that has the file
+                Err(e) => if e.to_string().contains(file) { err = e },
+            }
+        }
+        Err(err)
+    }
+
+    /// Runtime address of a location
+    pub fn resolve_location(&self, loc: &Location) -> Result<usize, failure::Error> {
+        match loc {
+            Location::Address(addr) => Ok(*addr),
+            Location::Expr(e) => Ok(e.eval(self)? as usize),
+            Location::Symbol(name, off) => {
+                if let Some(addr) = self.find_symbol(name) {
+                    return Ok(addr + off);
+                }
+                // `hello+0x10` with hello being the program
+                self.modules.iter()
+                    .find(|(module, _)| module == name)
+                    .map(|(_, base)| base + off)
+                    .ok_or_else(|| failure::format_err!("No symbol \"{}\" in current context", name))
+            },
+            Location::Module(loc) => {
+                let (_, base) = self.modules.iter()
+                    .find(|(file, _)| module_matches(file, &loc.module))
+                    .cloned()
+                    .ok_or_else(|| failure::format_err!("No module \"{}\" loaded", loc.module))?;
+                match &loc.target {
+                    ModuleTarget::Offset(off) => Ok(base + off),
+                    ModuleTarget::Symbol(name, off) => Ok(base + self.module_symbol_offset(base, name)? + off),
+                }
+            },
+            Location::Line(file, line) => self.find_line(file, *line),
+        }
+    }
+
+    /* Before the program runs only its own file is known. Symbols and
+     * lines in it become breakpoints waiting for it to load. */
+    pub(crate) fn location_in_executable(&self, loc: &Location) -> Result<ModuleLocation, failure::Error> {
+        let path = Path::new(&self.location);
+        let module = match path.file_name() {
+            Some(name) if !self.location.is_empty() => name.to_string_lossy().into_owned(),
+            _ => return Err(failure::format_err!("No executable file now")),
+        };
+
+        let target = match loc {
+            Location::Module(loc) => return Ok(loc.clone()),
+            Location::Symbol(name, off) => {
+                if SymbolTable::from_elf(&self.parser).find(name).is_none() {
+                    return Err(failure::format_err!(
+                        "No symbol \"{}\" in {}, use module!symbol for shared libraries", name, module));
+                }
+                ModuleTarget::Symbol(name.clone(), *off)
+            },
+            Location::Line(file, line) => ModuleTarget::Offset(line_offset(path, file, *line)?),
+            _ => return Err(failure::format_err!("The program is not being run")),
+        };
+        Ok(ModuleLocation { module, target })
+    }
+}
@@ -0,0 +1,244 @@
+/* Memory map of the inferior
+ * A snapshot of /proc/pid/maps taken at every stop, sorted by address so
+ * the mapping holding an address is a binary search away.
+ */
+
+use colored::*;
+use procfs::{ MemoryMap, MMapPath };
+
+use std::path::Path;
+
+use super::Inferior;
+
+/// What a mapping holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// Executable mapping of a file
+    Code,
+    /// Any other mapping of a file
+    Module,
+    Heap,
+    /// The main stack or a thread's
+    Stack,
+    /// Anonymous memory
+    Anon,
+    /// vdso, vvar and vsyscall
+    Kernel,
+}
+
+impl Region {
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Code => "code",
+            Region::Module => "module",
+            Region::Heap => "heap",
+            Region::Stack => "stack",
+            Region::Anon => "anon",
+            Region::Kernel => "kernel",
+        }
+    }
+
+    /// Colors used by `vmmap`, `x` and `hexdump`
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Region::Code => Some(Color::Red),
+            Region::Heap => Some(Color::Green),
+            Region::Stack => Some(Color::Magenta),
+            _ => None,
+        }
+    }
+}
+
+/// Classify a mapping
+pub fn classify(map: &MemoryMap) -> Region {
+    match &map.pathname {
+        MMapPath::Path(_) if map.perms.contains('x') => Region::Code,
+        MMapPath::Path(_) => Region::Module,
+        MMapPath::Heap => Region::Heap,
+        MMapPath::Stack | MMapPath::TStack(_) => Region::Stack,
+        MMapPath::Vdso | MMapPath::Vvar | MMapPath::Vsyscall => Region::Kernel,
+        MMapPath::Anonymous => Region::Anon,
+        MMapPath::Other(_) if map.perms.contains('x') => Region::Code,
+        MMapPath::Other(_) => Region::Module,
+    }
+}
+
+/// Name shown for a mapping
+pub fn map_name(map: &MemoryMap) -> String {
+    match &map.pathname {
+        MMapPath::Path(p) => p.to_string_lossy().into_owned(),
+        MMapPath::Heap => String::from("[ Heap ]"),
+        MMapPath::Stack => String::from("[ Stack ]"),
+        MMapPath::TStack(tid) => format!("[ Stack:{} ]", tid),
+        MMapPath::Vdso => String::from("[ vdso ]"),
+        MMapPath::Vvar => String::from("[ vvar ]"),
+        MMapPath::Vsyscall => String::from("[ vsyscall ]"),
+        MMapPath::Anonymous => String::from("[ Anonymous ]"),
+        MMapPath::Other(o) => o.clone(),
+    }
+}
+
+/// File name of a file backed mapping
+pub fn map_file_name(map: &MemoryMap) -> Option<String> {
+    match &map.pathname {
+        MMapPath::Path(p) => Path::new(p).file_name().map(|n| n.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMapList {
+    map: Vec<MemoryMap>,
+}
+
+impl MemoryMapList {
+    pub fn new() -> MemoryMapList {
+        MemoryMapList { map: Vec::new() }
+    }
+
+    pub fn from_maps(mut map: Vec<MemoryMap>) -> MemoryMapList {
+        map.sort_by_key(|m| m.address.0);
+        MemoryMapList { map }
+    }
+
+    pub fn len(&self) -> usize { self.map.len() }
+    pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, MemoryMap> {
+        self.map.iter()
+    }
+
+    /// The mapping holding `addr`
+    pub fn find(&self, addr: usize) -> Option<&MemoryMap> {
+        let addr = addr as u64;
+        let idx = match self.map.binary_search_by_key(&addr, |m| m.address.0) {
+            Ok(idx) => idx,
+            Err(0) => return None,
+            Err(idx) => idx - 1,
+        };
+        let m = &self.map[idx];
+        if addr < m.address.1 { Some(m) } else { None }
+    }
+
+    pub fn region(&self, addr: usize) -> Option<Region> {
+        self.find(addr).map(classify)
+    }
+
+    /// Is `addr` mapped with every permission in `perms` (`r`, `w`, `x`)
+    pub fn has_perms(&self, addr: usize, perms: &str) -> bool {
+        match self.find(addr) {
+            Some(m) => perms.chars().all(|p| m.perms.contains(p)),
+            None => false,
+        }
+    }
+
+    /// End of the run of back to back mappings holding `addr`
+    pub fn mapped_end(&self, addr: usize) -> Option<usize> {
+        let mut end = self.find(addr)?.address.1 as usize;
+        while let Some(m) = self.find(end) {
+            end = m.address.1 as usize;
+        }
+        Some(end)
+    }
+
+    /// Check that all of `addr..addr+len` is mapped, naming the first
+    /// address that isn't
+    pub fn check_range(&self, addr: usize, len: usize) -> Result<(), failure::Error> {
+        let end = addr.checked_add(std::cmp::max(len, 1))
+            .ok_or_else(|| failure::format_err!("Address range {:#x}+{:#x} wraps around", addr, len))?;
+        let mut cur = addr;
+        while cur < end {
+            match self.find(cur) {
+                Some(m) => cur = m.address.1 as usize,
+                None => return Err(failure::format_err!("Address {:#x} is not mapped", cur)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a MemoryMapList {
+    type Item = &'a MemoryMap;
+    type IntoIter = std::slice::Iter<'a, MemoryMap>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+impl Inferior {
+    /* Take a new snapshot of /proc/pid/maps */
+    pub(crate) fn refresh_maps(&mut self) {
+        if let Ok(maps) = self.procfs.maps() {
+            self.mem = MemoryMapList::from_maps(maps);
+        }
+    }
+
+    pub fn memory_map(&self) -> &MemoryMapList { &self.mem }
+
+    /// Mapping holding `addr`
+    pub fn validate_addr(&mut self, addr: usize) -> Result<MemoryMap, failure::Error> {
+        if self.mem.find(addr).is_none() {
+            self.refresh_maps();
+        }
+        self.mem.find(addr)
+            .cloned()
+            .ok_or_else(|| failure::format_err!("Address {:#x} is not mapped", addr))
+    }
+
+    /// Error unless `addr..addr+len` is mapped. The snapshot is from the
+    /// last stop, so a miss is checked again against the live maps.
+    pub fn check_mapped(&self, addr: usize, len: usize) -> Result<(), failure::Error> {
+        if self.mem.check_range(addr, len).is_ok() {
+            return Ok(());
+        }
+        let live = self.procfs.maps()
+            .map_err(|e| failure::format_err!("Unable to read the memory map: {}", e))?;
+        MemoryMapList::from_maps(live).check_range(addr, len)
+    }
+
+    /// `len` cut short where the mappings running on from `addr` end, so
+    /// a huge count reads what is there instead of allocating all of it
+    pub fn clamp_to_mapped(&self, addr: usize, len: usize) -> Result<usize, failure::Error> {
+        let end = match self.mem.mapped_end(addr) {
+            Some(end) => end,
+            None => {
+                let live = self.procfs.maps()
+                    .map_err(|e| failure::format_err!("Unable to read the memory map: {}", e))?;
+                MemoryMapList::from_maps(live).mapped_end(addr)
+                    .ok_or_else(|| failure::format_err!("Address {:#x} is not mapped", addr))?
+            },
+        };
+        Ok(std::cmp::min(len, end - addr))
+    }
+
+    /// `vmmap [addr|filter]`: All mappings, the one holding an address, or
+    /// those whose name or region type contains `filter`
+    pub fn show_memory_map(&self, filter: &str) {
+        let filter = filter.trim();
+        let addr = filter.strip_prefix("0x").and_then(|hex| usize::from_str_radix(hex, 16).ok());
+
+        println!("{:<16}{:<16}{:<12}{:<6}{:<8}Path", "Start", "End", "Offset", "Perm", "Type");
+        for m in &self.mem {
+            let region = classify(m);
+            let name = map_name(m);
+            let shown = match addr {
+                Some(addr) => self.mem.find(addr).is_some_and(|found| found.address == m.address),
+                None => filter.is_empty() || name.contains(filter) || region.name() == filter,
+            };
+            if !shown { continue }
+
+            let line = format!("{:<#16x}{:<#16x}{:<#12x}{:<6}{:<8}{}",
+                               m.address.0, m.address.1, m.offset, m.perms, region.name(), name);
+            match region.color() {
+                Some(color) => println!("{}", line.color(color)),
+                None => println!("{}", line),
+            }
+        }
+        if let Some(addr) = addr {
+            if self.mem.find(addr).is_none() {
+                println!("Address {:#x} is not mapped", addr);
+            }
+        }
+    }
+}
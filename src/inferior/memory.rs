@@ -0,0 +1,214 @@
+/* Inferior memory access
+ * Reads and writes try, in order:
+ *   process_vm_readv/writev: one syscall for the whole range
+ *   /proc/pid/mem:           also writes read-only pages (text)
+ *   PTRACE_PEEK/POKEDATA:    a word at a time
+ * Ranges are split at page boundaries, so a range running into an
+ * unmapped page returns what was readable before it.
+ */
+
+use nix::sys::ptrace;
+use nix::sys::uio::{ IoVec, RemoteIoVec, process_vm_readv, process_vm_writev };
+
+use libc::c_void;
+
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+
+use super::Inferior;
+
+fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        n if n > 0 => n as usize,
+        _ => 4096,
+    }
+}
+
+/* `addr..addr+len` cut at page boundaries */
+fn split_pages(addr: usize, len: usize) -> Vec<RemoteIoVec> {
+    let page = page_size();
+    let mut iovs = Vec::new();
+    let mut cur = addr;
+    let end = addr.saturating_add(len);
+    while cur < end {
+        let next = std::cmp::min((cur / page + 1) * page, end);
+        iovs.push(RemoteIoVec { base: cur, len: next - cur });
+        cur = next;
+    }
+    iovs
+}
+
+impl Inferior {
+    /// Read up to `len` bytes at `addr`. Fewer bytes come back when the
+    /// range runs into memory that can't be read, an error only when
+    /// nothing could be read at all. Breakpoints show up as 0xcc, see
+    /// `memory_view` for the original bytes.
+    pub fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, failure::Error> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = vec![0u8; len];
+        let mut done = self.readv(addr, &mut buf);
+        if done < len {
+            done += self.read_proc_mem(addr + done, &mut buf[done..]);
+        }
+        if done < len {
+            done += self.read_peek(addr + done, &mut buf[done..]);
+        }
+
+        if done == 0 {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr));
+        }
+        buf.truncate(done);
+        Ok(buf)
+    }
+
+    /// `read_memory` with the original bytes put back where our
+    /// breakpoints are
+    pub fn memory_view(&self, addr: usize, len: usize) -> Result<Vec<u8>, failure::Error> {
+        let mut buf = self.read_memory(addr, len)?;
+        for (&bp_addr, bp) in &self.breakpoints {
+            if !bp.enabled || bp_addr < addr || bp_addr >= addr + buf.len() { continue }
+            if let Some(orig) = bp.orig_byte {
+                buf[bp_addr - addr] = orig;
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Read exactly `len` bytes or fail
+    pub fn read_exact(&self, addr: usize, len: usize) -> Result<Vec<u8>, failure::Error> {
+        let buf = self.memory_view(addr, len)?;
+        if buf.len() < len {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr + buf.len()));
+        }
+        Ok(buf)
+    }
+
+    /// Little endian u64 at `addr`
+    pub fn read_u64(&self, addr: usize) -> Result<u64, failure::Error> {
+        let bytes = self.read_exact(addr, 8)?;
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&bytes);
+        Ok(u64::from_le_bytes(word))
+    }
+
+    /// NUL terminated string at `addr`, at most `max` bytes of it
+    pub fn read_string(&self, addr: usize, max: usize) -> Result<String, failure::Error> {
+        let bytes = self.memory_view(addr, max)?;
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    /// Write `data` at `addr`, returning how many bytes made it. Read-only
+    /// mappings such as code are written through /proc/pid/mem or ptrace.
+    pub fn write_memory(&self, addr: usize, data: &[u8]) -> Result<usize, failure::Error> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let mut done = self.writev(addr, data);
+        if done < data.len() {
+            done += self.write_proc_mem(addr + done, &data[done..]);
+        }
+        if done < data.len() {
+            done += self.write_poke(addr + done, &data[done..]);
+        }
+
+        if done == 0 {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr));
+        }
+        Ok(done)
+    }
+
+    fn readv(&self, addr: usize, buf: &mut [u8]) -> usize {
+        let remote = split_pages(addr, buf.len());
+        let local = [IoVec::from_mut_slice(buf)];
+        process_vm_readv(self.pid, &local, &remote).unwrap_or(0)
+    }
+
+    fn writev(&self, addr: usize, data: &[u8]) -> usize {
+        let remote = split_pages(addr, data.len());
+        let local = [IoVec::from_slice(data)];
+        process_vm_writev(self.pid, &local, &remote).unwrap_or(0)
+    }
+
+    /* A page at a time so a bad page only stops the rest */
+    fn read_proc_mem(&self, addr: usize, buf: &mut [u8]) -> usize {
+        let file = match OpenOptions::new().read(true).open(format!("/proc/{}/mem", self.pid)) {
+            Ok(file) => file,
+            Err(_) => return 0,
+        };
+
+        let mut done = 0;
+        for iov in split_pages(addr, buf.len()) {
+            match file.read_at(&mut buf[done..done + iov.len], iov.base as u64) {
+                Ok(n) if n > 0 => done += n,
+                _ => break,
+            }
+            if done < iov.base - addr + iov.len { break }
+        }
+        done
+    }
+
+    fn write_proc_mem(&self, addr: usize, data: &[u8]) -> usize {
+        let file = match OpenOptions::new().write(true).open(format!("/proc/{}/mem", self.pid)) {
+            Ok(file) => file,
+            Err(_) => return 0,
+        };
+
+        let mut done = 0;
+        for iov in split_pages(addr, data.len()) {
+            match file.write_at(&data[done..done + iov.len], iov.base as u64) {
+                Ok(n) if n > 0 => done += n,
+                _ => break,
+            }
+            if done < iov.base - addr + iov.len { break }
+        }
+        done
+    }
+
+    /* Whole words. A short tail is read from the word ending with it, a
+     * word starting at the tail could run into an unmapped page. */
+    fn read_peek(&self, addr: usize, buf: &mut [u8]) -> usize {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = std::cmp::min(8, buf.len() - done);
+            let at = match (addr + done + n).checked_sub(8) {
+                Some(at) => at,
+                None => break,
+            };
+            let word = match ptrace::read(self.tid, at as ptrace::AddressType) {
+                Ok(word) => word.to_le_bytes(),
+                Err(_) => break,
+            };
+            buf[done..done + n].copy_from_slice(&word[8 - n..]);
+            done += n;
+        }
+        done
+    }
+
+    /* Whole words, merging the tail with what's already there */
+    fn write_poke(&self, addr: usize, data: &[u8]) -> usize {
+        let mut done = 0;
+        while done < data.len() {
+            let at = (addr + done) as ptrace::AddressType;
+            let n = std::cmp::min(8, data.len() - done);
+            let mut word = if n < 8 {
+                match ptrace::read(self.tid, at) {
+                    Ok(word) => word.to_le_bytes(),
+                    Err(_) => break,
+                }
+            } else {
+                [0u8; 8]
+            };
+            word[..n].copy_from_slice(&data[done..done + n]);
+
+            let res = ptrace::write(self.tid, at, i64::from_le_bytes(word) as *mut c_void);
+            if res.is_err() { break }
+            done += n;
+        }
+        done
+    }
+}
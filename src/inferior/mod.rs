@@ -8,38 +8,34 @@
 #[allow(unused)]
 
 use ansi_term::Colour::*;
-use colored::*;
 //use chrono::{Utc, TimeZone, NaiveTime, NaiveDateTime, DateTime, Local};
 
 use elfkit::Elf;
 
-use hex;
-//use libc::c_void;
+use libc::c_void;
 use libc::user_regs_struct;
 
 use nix::errno::Errno::*;
 use nix::Error;
 use nix::Error::Sys;
 //use nix::sys::mman::*;
-//use nix::sys::uio::{IoVec, RemoteIoVec, process_vm_readv, process_vm_writev};
 use nix::sys::{ptrace, signal};
 use nix::sys::wait::*;
 use nix::ucontext::UContext;
 use nix::unistd::{
-    execve,
     fork,
     getcwd,
     ForkResult,
     Pid
 };
 
-use procfs::{ Process, MemoryMap, MMapPath };
+use procfs::Process;
 
 //use std::cell::RefCell;
 //use std::boxed::FnBox;
 use std::collections::{HashSet, HashMap};
 use std::default::Default;
-use std::ffi::{ CString, OsString };
+use std::ffi::OsString;
 //use std::fs::File;
 //use std::fmt;
 //use std::rc::Rc;
@@ -48,20 +44,57 @@ use std::io::stdout;
 //use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
-//use std::path::Path;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 use std::unimplemented;
 
 pub mod ffi;
+pub mod expr;
+pub mod debugreg;
+pub mod disas;
+pub mod step;
+pub mod threads;
+pub mod fork;
+pub mod signals;
+pub mod syscalls;
+pub mod launch;
+pub mod memory;
+pub mod examine;
+pub mod patch;
+pub mod registers;
+pub mod fpregs;
+pub mod maps;
+pub mod solib;
+pub mod symbols;
+pub mod pending;
+pub mod lines;
+pub mod location;
+pub mod demangle;
+pub mod cfi;
+pub mod unwind;
+
+use self::expr::{Expr, ExprContext};
+use self::debugreg::{HwBreakpoint, HW_SLOTS};
+use self::fork::FollowForkMode;
+use self::signals::SignalPolicy;
+use self::syscalls::Catchpoint;
+use self::launch::Launch;
+use self::maps::MemoryMapList;
+use self::solib::SharedLibrary;
+use self::symbols::SymbolTable;
+use self::demangle::DemangleMode;
+use self::cfi::CallFrameTable;
+use self::unwind::SelectedFrame;
+use self::location::{ parse_location, Location };
 
 /// Tracks if an exit has been requested via the Ctrl+C/Ctrl+Break handler
 static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
 
 /// Function invoked on module loads
 /// (debugger, module filename, module base)
-type ModLoadFn = Box<dyn Fn(&mut Inferior, &str, usize)>;
+pub type ModLoadFn = Box<dyn Fn(&mut Inferior, &str, usize)>;
 
 /// Function invoked on debug events
 //type DebugEventFunc = Box<dyn Fn(&mut Inferior, &DEBUG_EVENT)>;
@@ -91,7 +124,7 @@ pub enum BreakpointType {
     Single,  // Delete BP after hit
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum InferiorState {
     Startup,
     Running,
@@ -113,10 +146,15 @@ pub enum InferiorState {
 /// Structure to represent breakpoints
 #[derive(Clone)]
 pub struct Breakpoint {
+    /// Number used to refer to this breakpoint from the console, 0 for
+    /// internal breakpoints the user never sees
+    id: usize,
+
     /// Offset from module base
     offset: usize,
 
-    /// Tracks if this breakpoint is currently active
+    /// Tracks if this breakpoint is currently active. For breakpoints
+    /// waiting on a module, whether to activate it once the module loads.
     enabled: bool,
 
     /// Original byte that was at this location, only set if breakpoint was
@@ -140,48 +178,70 @@ pub struct Breakpoint {
 
     /// Number of times this breakpoint has been hit
     freq: u64,
+
+    /// Only stop when this evaluates to non-zero
+    condition: Option<Expr>,
+
+    /// Hits are ignored until `freq` goes past this
+    ignore: u64,
+
+    /// `offset` is unknown until the module loads and `funcname` is looked
+    /// up in it
+    resolve: bool,
 }
 
-#[derive(Clone)]
-pub struct MemoryMapList {
-    //index: usize,
-    map: Vec<MemoryMap>,
+/// What to do after a SIGTRAP was matched against the breakpoints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BreakpointAction {
+    /// Not one of ours
+    Unknown,
+    /// Report the hit and return to the prompt
+    Stop,
+    /// Condition or ignore count said to keep going
+    Resume,
 }
 
-#[allow(dead_code)]
-impl MemoryMapList {
-    fn new() -> MemoryMapList {
-        // MemoryMapList { index: 0, map: Vec::new() }
-        MemoryMapList { map: Vec::new() }
+impl Breakpoint {
+    pub fn id(&self) -> usize               { self.id }
+    pub fn enabled(&self) -> bool           { self.enabled }
+    pub fn typ(&self) -> BreakpointType     { self.typ }
+    pub fn freq(&self) -> u64               { self.freq }
+    pub fn modname(&self) -> &str           { &self.modname }
+    pub fn offset(&self) -> usize           { self.offset }
+    pub fn funcname(&self) -> &str          { &self.funcname }
+    pub fn funcoff(&self) -> usize          { self.funcoff }
+    pub fn condition(&self) -> Option<&Expr> { self.condition.as_ref() }
+    pub fn ignore(&self) -> u64             { self.ignore.saturating_sub(self.freq) }
+
+    /// `module+offset` and `funcname+funcoff` for display
+    pub fn location(&self) -> String {
+        let mut loc = String::new();
+        if !self.modname.is_empty() {
+            loc.push_str(&format!("{}+{:#x}", self.modname, self.offset));
+        }
+        if !self.funcname.is_empty() {
+            loc.push_str(&format!(" <{}+{:#x}>", self.funcname, self.funcoff));
+        }
+        loc
     }
 }
 
-// impl Iterator for MemoryMapList {
-//     type Item = MemoryMap;
-
-//     fn next(&mut self) -> Option<MemoryMap> {
-//         if self.index >= self.map.len() {
-//             None
-//         } else {
-//             self.index += 1;
-//             Some(self.map[self.index-1])
-//             // self.map.get(self.index-1)
-//         }
-//     }
-// }
-
 #[allow(dead_code)]
 pub struct Inferior {
     /* Process Information */
     pub pid: Pid,
     pub tids: HashMap<u32, Pid>,  // Threads
+    pub tid: Pid,  // Thread registers and stepping act on
     pub attached: bool,
 
     /* Startup data */
     pub location: String,
-    pub args: HashMap<u32, String>,
-    pub env: HashMap<OsString, OsString>,
+    pub args: Vec<String>,  // argv[1..]
+    pub env: HashMap<OsString, OsString>,  // Given to processes we run
+    pub process_env: HashMap<OsString, OsString>,  // Of the process we attached to, from /proc
     pub cwd: PathBuf,
+    pub stdin: Option<PathBuf>,
+    pub stdout: Option<PathBuf>,
 
     /* procfs */
     procfs: Process,
@@ -189,13 +249,35 @@ pub struct Inferior {
 
     /* Process State */
     pub state: InferiorState,
-    aslr: bool,
-    //mem: MemoryMapList,
+    stop_regs: Option<(Pid, user_regs_struct)>,  // Registers when we last resumed, to show what changed
+    thread_states: HashMap<Pid, InferiorState>,
+    pending_signals: HashMap<Pid, signal::Signal>,  // Signals to deliver on the next resume
+    signal_policies: HashMap<signal::Signal, SignalPolicy>,  // stop/print/pass per signal
+
+    /* Fork handling */
+    follow_fork_mode: FollowForkMode,
+    detach_on_fork: bool,
+    held_processes: Vec<Pid>,  // Not followed but kept stopped (detach-on-fork off)
+    early_children: HashSet<Pid>,  // Fork children that stopped before the fork event
+    vfork_parent: Option<Vec<Pid>>,  // Followed a vfork child, parent threads wait for its exec
+    vfork_child: bool,  // Breakpoints are out while a vfork child shares our memory
+    aslr: bool,  // Randomization in effect for the current inferior
+    disable_randomization: bool,
+    personality: u64,  // Extra personality(2) flags for launch
+    rlimits: Vec<launch::Rlimit>,  // Applied in the child before execve
+    mem: MemoryMapList,  // /proc/pid/maps as of the last stop
 
     /* Breakpoints */
     breakpoints: HashMap<usize, Breakpoint>,
-    target_breakpoints: HashMap<String, Vec<Breakpoint>>,
+    target_breakpoints: HashMap<String, Vec<Breakpoint>>,  // Waiting for a module to load, see pending.rs
     breakpoint_bounds: HashMap<String, (usize, usize)>,  // Track minimum and maximum addresses for breakpoints per module
+    next_bp_id: usize,
+    hw_breakpoints: [Option<HwBreakpoint>; HW_SLOTS],  // DR0-DR3
+    catchpoints: Vec<Catchpoint>,  // catch syscall
+
+    /* Syscall tracing */
+    trace_syscalls: bool,
+    syscall_entry: HashMap<Pid, (u64, String)>,  // Threads inside a syscall, with the call and its arguments
 
     /* Callbacks */
     module_load_callbacks: Option<Arc<Vec<ModLoadFn>>>,  // Invoked when a module is loaded
@@ -209,6 +291,14 @@ pub struct Inferior {
 
     /* Shared Libraries */
     modules: HashSet<(String, usize)>,
+    r_debug: Option<usize>,  // The dynamic linker's struct r_debug
+    r_brk: Option<usize>,  // Called by ld.so around every change to the link_map list
+    solib_entry: Option<usize>,  // Entry point breakpoint, until r_debug is known
+    solibs: Vec<SharedLibrary>,  // link_map as of the last change
+    symtabs: HashMap<PathBuf, Arc<SymbolTable>>,  // Symbol tables by file path
+    demangle: DemangleMode,  // How symbol names are shown
+    frame_tables: HashMap<PathBuf, Arc<CallFrameTable>>,  // CFI by file path
+    selected_frame: Option<SelectedFrame>,  // frame, up and down
 
     /* TIDs actively single stepping mapped to the PC they stepped from */
     single_step: HashMap<u32, usize>,
@@ -244,26 +334,58 @@ impl Default for Inferior {
         Inferior {
             pid: Pid::this(),
             tids: HashMap::new(),
+            tid: Pid::this(),
             attached: false,
 
             location: String::new(),
-            args: HashMap::new(),
-            env: HashMap::new(),
+            args: Vec::new(),
+            env: std::env::vars_os().collect(),
+            process_env: HashMap::new(),
             cwd: getcwd().unwrap(),
+            stdin: None,
+            stdout: None,
 
             procfs: Process::myself().expect("Unable to get procfs data"),
             parser: Elf::default(),
 
-            //mem: MemoryMapList::new(),
+            mem: MemoryMapList::new(),
 
             state: InferiorState::None,
+            stop_regs: None,
+            thread_states: HashMap::new(),
+            pending_signals: HashMap::new(),
+            signal_policies: signals::default_signal_policies(),
+
+            follow_fork_mode: FollowForkMode::Parent,
+            detach_on_fork: true,
+            held_processes: Vec::new(),
+            early_children: HashSet::new(),
+            vfork_parent: None,
+            vfork_child: false,
             aslr: true,  // Get System Information for this...
+            disable_randomization: true,
+            personality: 0,
+            rlimits: Vec::new(),
 
             breakpoints: HashMap::new(),
             target_breakpoints: HashMap::new(),
             breakpoint_bounds: HashMap::new(),
+            next_bp_id: 1,
+            hw_breakpoints: [None, None, None, None],
+            catchpoints: Vec::new(),
+
+            trace_syscalls: false,
+            syscall_entry: HashMap::new(),
 
             modules: HashSet::new(),
+            r_debug: None,
+            r_brk: None,
+            solib_entry: None,
+            solibs: Vec::new(),
+            symtabs: HashMap::new(),
+            demangle: DemangleMode::On,
+            frame_tables: HashMap::new(),
+            selected_frame: None,
             module_load_callbacks: Some(Arc::new(Vec::with_capacity(25))),
             // debug_event_callbacks: Some(Vec::new()),
 
@@ -293,15 +415,27 @@ impl Inferior {
 
     pub fn parse(&mut self) {
         println!("Parsing binary file: {}", self.location);
+        match symbols::load_elf(Path::new(&self.location)) {
+            Ok(elf) => self.parser = elf,
+            Err(e) => {
+                println!("Unable to parse binary: {}", e);
+                self.parser = Elf::default();
+            }
+        }
+        // The file may have been rebuilt since its symbols were read
+        self.symtabs.remove(Path::new(&self.location));
+        self.frame_tables.remove(Path::new(&self.location));
     }
 
-    /* Start new process */
-    pub fn start(&mut self, file: String, args: &[String]) {
-        if args.len() == 0 { println!("No arguments provided"); }
-        println!("Executing: {} Args: {}", file, args[0]);
+    /* Start new process with the launch settings (args, env, cwd, redirects) */
+    pub fn start(&mut self, file: String) -> Result<(), failure::Error> {
+        let path = std::fs::canonicalize(&file)
+            .map_err(|e| failure::format_err!("{}: {}", file, e))?;
+        self.location = path.to_string_lossy().into_owned();
+        println!("Executing: {} Args: {}", self.location, self.args.join(" "));
 
-        self.location = file;
-        // self.args: ***FIXME***, // Implement this
+        let launch = self.launch()?;
+        self.process_env.clear();
 
         // Parse binary and proceed
         self.parse();
@@ -311,47 +445,167 @@ impl Inferior {
         stdio_flush();
 
         match fork() {
-            Ok(ForkResult::Child) => self.attach_self(),
+            Ok(ForkResult::Child) => self.attach_self(&launch),
             Ok(ForkResult::Parent { child }) => {
                 self.pid = child;
+                self.tid = child;
                 self.attached = true;
-                self.prefetch_inferior_data();
-                self.wait();
+                self.wait_exec_stop();
             }
             Err(e) => {
                 self.state = InferiorState::Dead;
-                println!("Fork failed: {}", e);
+                return Err(failure::format_err!("Fork failed: {}", e));
             }
         }
+        Ok(())
     }
 
-    /* Attach to a PID */
-    #[allow(dead_code)]
-    pub fn attach(&mut self, _pid: u32) {
-        // println!("Attaching to pid {}", _pid);
-        unimplemented!();
+    /* The child stops with SIGTRAP once execve() is done */
+    fn wait_exec_stop(&mut self) {
+        match waitpid(self.pid, None) {
+            Ok(WaitStatus::Stopped(_, signal::SIGTRAP)) => {},
+            Ok(WaitStatus::Exited(_pid, code)) => {
+                println!("Process exited. Pid: {} Code: {}", _pid, code);
+                self.state = InferiorState::Dead;
+                return;
+            },
+            Ok(status) => println!("Unexpected status at startup: {:?}", status),
+            Err(e) => {
+                println!("Unable to wait for the inferior: {}", e);
+                self.state = InferiorState::Dead;
+                return;
+            }
+        }
+
+        if let Err(e) = ptrace::setoptions(self.pid, self.ptrace_options()) {
+            println!("Unable to set ptrace options: {}", e);
+        }
+        self.tids.clear();
+        self.thread_states.clear();
+        let pid = self.pid;
+        self.add_thread(pid);
+        self.thread_states.insert(pid, InferiorState::Stopped);
+        self.state = InferiorState::Stopped;
+        self.prefetch_inferior_data();
+        self.aslr = self.aslr_enabled();
+
+        // Left over from a previous run, they're set again as their
+        // modules come in
+        self.clear_solibs();
+        self.reset_breakpoints();
+        self.refresh_modules();
+        self.init_solib_tracking();
     }
 
-    pub fn attach_self(&mut self) {
-        println!("attach_self");
+    /* Attach to a PID */
+    pub fn attach(&mut self, pid: u32) -> Result<(), failure::Error> {
+        println!("Attaching to pid {}", pid);
+
+        if !PathBuf::from(format!("/proc/{}", pid)).exists() {
+            return Err(failure::format_err!("No process with pid {}", pid));
+        }
+        self.pid = Pid::from_raw(pid as i32);
+        self.tid = self.pid;
+
+        // Seize every thread. Threads can be spawned while we are walking
+        // the task list, so keep going until a pass turns up nothing new.
+        let mut seized: HashSet<u32> = HashSet::new();
+        loop {
+            let new: Vec<u32> = task_ids(self.pid)?
+                .into_iter()
+                .filter(|tid| !seized.contains(tid))
+                .collect();
+            if new.is_empty() { break }
+
+            for tid in new {
+                let thread = Pid::from_raw(tid as i32);
+                match ffi::seize(thread, self.ptrace_options()) {
+                    Ok(()) => {},
+                    // Thread exited before we got to it
+                    Err(Sys(ESRCH)) if thread != self.pid => continue,
+                    Err(e) => {
+                        self.detach_threads();
+                        return Err(failure::format_err!("Unable to attach to {}: {}", tid, e));
+                    }
+                }
+                seized.insert(tid);
+                self.add_thread(thread);
+
+                if let Err(e) = ffi::interrupt(thread) {
+                    self.detach_threads();
+                    return Err(failure::format_err!("Unable to stop {}: {}", tid, e));
+                }
+                // A signal that arrived first is delivered on the next resume
+                if let Some(sig) = wait_for_stop(thread) {
+                    self.pending_signals.insert(thread, sig);
+                }
+                self.thread_states.insert(thread, InferiorState::Stopped);
+            }
+        }
+
+        self.attached = true;
+        self.state = InferiorState::Stopped;
+        self.start_time = Instant::now();
+
+        // Get Process Information
+        self.prefetch_inferior_data();
+        self.process_env = self.procfs.environ().unwrap_or_default();
+        if let Ok(exe) = self.procfs.exe() {
+            self.location = exe.to_string_lossy().into_owned();
+        }
+        if let Ok(cwd) = std::fs::read_link(format!("/proc/{}/cwd", pid)) {
+            self.cwd = cwd;
+        }
+        self.aslr = self.aslr_enabled();
+        // Before the modules, the executable's symbols come from the parser
+        self.parse();
+        self.clear_solibs();
+        self.refresh_modules();
+        self.init_solib_tracking();
 
-        let cmd = CString::new(self.location.clone()).unwrap();
+        println!("Attached to process {} ({} threads)", self.pid, self.tids.len());
+        Ok(())
+    }
 
-        // *** For now don't deal with ASLR (CHANGE LATER) ***
-        ffi::disable_aslr();
-        self.aslr = false;
+    /* Remove all breakpoints and let the inferior run free */
+    pub fn detach(&mut self) -> Result<(), failure::Error> {
+        if !self.attached {
+            return Err(failure::format_err!("Not attached to a process"));
+        }
 
-        // Begin Tracing
-        println!("Setting traceme()");
-        ptrace::traceme()
-            .ok()
-            .expect("Unable to set PTRACE_TRACEME");
+        self.remove_breakpoints();
+        self.breakpoints.clear();
+        self.clear_hw_breakpoints();
+        self.detach_threads();
+
+        println!("Detached from process {}", self.pid);
+        self.attached = false;
+        self.state = InferiorState::None;
+        self.process_env.clear();
+        self.clear_solibs();
+        Ok(())
+    }
 
-        // Execute with arguments
-        println!("execve(\"{}\")", cmd.clone().into_string().unwrap());
-        execve(&cmd, &[], &[]).expect("Failed to run execve()");
-        unreachable!()
+    /* Let go of every thread. Signals that arrived while we held them
+     * are handed over instead of being dropped. */
+    fn detach_threads(&mut self) {
+        if self.tids.is_empty() {
+            let sig = self.pending_signals.remove(&self.pid);
+            let _ = ffi::detach(self.pid, sig);
+        }
+        for (_, tid) in self.tids.drain() {
+            // Threads that already exited are gone from ptrace anyway
+            let sig = self.pending_signals.remove(&tid);
+            let _ = ffi::detach(tid, sig);
+        }
+        self.thread_states.clear();
+        self.pending_signals.clear();
+        self.release_held_processes();
+    }
 
+    /* Child side of `start`, becomes the inferior */
+    fn attach_self(&self, launch: &Launch) -> ! {
+        launch.exec()
     }
 
     pub fn wait(&mut self) {
@@ -364,36 +618,88 @@ impl Inferior {
                 break;
             }
 
-            match waitpid(self.pid, None) {
-                Ok(WaitStatus::Stopped(_pid, signal::SIGTRAP)) => {
-                    println!("Process STOP encountered.");
-                    self.state = InferiorState::Stopped;
-                    break;
+            let status = waitpid(Pid::from_raw(-1), Some(WaitPidFlag::__WALL));
+            match status {
+                Ok(WaitStatus::Stopped(tid, signal::SIGTRAP)) => {
+                    self.tid = tid;
+                    let action = match self.handle_hw_breakpoint(tid) {
+                        BreakpointAction::Unknown => self.handle_breakpoint(),
+                        action => action,
+                    };
+                    match action {
+                        BreakpointAction::Resume => self.cont_thread(tid),
+                        action => {
+                            if action == BreakpointAction::Unknown {
+                                println!("Process STOP encountered.");
+                            }
+                            self.stop_all_threads(tid);
+                            break;
+                        }
+                    }
+                },
+                // Child of a fork beat the parent's event in
+                Ok(WaitStatus::Stopped(pid, _)) if self.is_new_process(pid) => {
+                    self.child_stopped_early(pid);
+                },
+                Ok(WaitStatus::PtraceEvent(pid, _, ffi::PTRACE_EVENT_STOP)) if self.is_new_process(pid) => {
+                    self.child_stopped_early(pid);
+                },
+                // First stop of a new thread
+                Ok(WaitStatus::Stopped(tid, signal::SIGSTOP)) if self.thread_starting(tid) => {
+                    self.start_thread(tid);
+                },
+                Ok(WaitStatus::PtraceEvent(tid, _, ffi::PTRACE_EVENT_STOP)) if self.thread_starting(tid) => {
+                    self.start_thread(tid);
+                },
+                Ok(WaitStatus::Stopped(tid, sig)) => {
+                    if self.handle_signal(tid, sig) {
+                        self.stop_all_threads(tid);
+                        break;
+                    }
+                    self.cont_thread(tid);
+                },
+                // Group stop after a stopping signal was passed on
+                Ok(WaitStatus::PtraceEvent(tid, _, ffi::PTRACE_EVENT_STOP)) => {
+                    self.cont_thread(tid);
+                },
+                Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_CLONE)) => {
+                    self.handle_clone(tid);
+                },
+                Ok(WaitStatus::PtraceEvent(tid, _, event @ libc::PTRACE_EVENT_FORK)) |
+                Ok(WaitStatus::PtraceEvent(tid, _, event @ libc::PTRACE_EVENT_VFORK)) => {
+                    self.handle_fork(tid, event);
+                },
+                Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_VFORK_DONE)) => {
+                    self.handle_vfork_done();
+                    let _ = self.resume_thread(tid, None);
+                },
+                Ok(WaitStatus::PtraceEvent(_tid, _, libc::PTRACE_EVENT_EXEC)) => {
+                    self.handle_exec();
+                    let pid = self.pid;
+                    self.cont_thread(pid);
                 },
                 Ok(WaitStatus::PtraceEvent(_pid, signal::SIGTRAP, 0)) => {
                     println!("SIGTRAP encountered.");
                     self.state = InferiorState::Running;
                     break;
                 },
-                Ok(WaitStatus::Signaled(_pid, sig, core)) => {
-                    println!("Signal: {} Pid: {}", sig, _pid);
-                    if core { println!("Process generated core dump!!!!!!"); }
-                    unimplemented!();
-                },
+                Ok(WaitStatus::Signaled(tid, sig, core)) => self.handle_signaled(tid, sig, core),
                 Ok(WaitStatus::PtraceEvent(_pid, sig, event)) => {
                     println!("Signal: {} Event: {} Pid: {}", sig, event, _pid);
                     unimplemented!();
                 },
-                Ok(WaitStatus::PtraceSyscall(_pid)) => {
-                    println!("Process stopped by execution of a system call. `:PTRACDE_O_TRACESYSGOOD` is in effect");
-                    unimplemented!();
+                Ok(WaitStatus::PtraceSyscall(tid)) => {
+                    if self.handle_syscall(tid, true) {
+                        self.stop_all_threads(tid);
+                        break;
+                    }
+                    self.cont_thread(tid);
                 },
                 Ok(WaitStatus::Continued(_pid)) => {
                     println!("Process encountered WaitStatus::Continued.");
                 },
-                Ok(WaitStatus::Exited(_pid, code)) => println!("Process exited. Pid: {} Code: {}", _pid, code),
+                Ok(WaitStatus::Exited(tid, code)) => self.handle_exit(tid, code),
                 Ok(WaitStatus::StillAlive) => continue,
-                Ok(_) => println!("Unhandled event in waitpid. Implement feature."),
                 Err(_) => self.handle_error(),
             }
             if self.state == InferiorState::Dead { break }
@@ -411,119 +717,495 @@ impl Inferior {
 
     pub fn resume(&mut self) {
         println!("Continuing execution...");
-        ptrace::cont(self.pid, None)
-            .ok()
-            .expect("Failed to continue process execution.");
-        self.state = InferiorState::Running;
+        self.save_stop_registers();
+        self.cont();
         self.wait();
     }
 
-    pub fn registers(&mut self) -> user_regs_struct {
-        ptrace::getregs(self.pid).expect("Failed to fetch register information.")
-    }
-
-    pub fn show_memory_map(&self) {
-        let maps = self.procfs.maps().expect("Unable to fetch memory map of process");
-        println!("{:<16}{:<16}{:<12}{:<4}  Path", "Start", "End", "Offset", "Perm");
-
-        for m in &maps {
-            match &m.pathname {
-                MMapPath::Path(p) => {
-                    if m.perms.contains("x") {
-                        println!("{}", format!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                               m.address.0,
-                                               m.address.1,
-                                               m.offset,
-                                               m.perms, p.to_str().unwrap()).red());
-                    } else {
-                        println!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                 m.address.0,
-                                 m.address.1,
-                                 m.offset,
-                                 m.perms, p.to_str().unwrap());
-                    }
+    /* Restart every thread, stepping the current one over a breakpoint at
+     * its PC first */
+    fn cont(&mut self) {
+        if let Err(e) = self.step_over_breakpoint() {
+            println!("Unable to step over breakpoint: {}", e);
+        }
+        if self.state == InferiorState::Dead { return }
+
+        for tid in self.thread_pids() {
+            if self.thread_states.get(&tid) == Some(&InferiorState::Running) { continue }
+            if tid != self.tid {
+                // Other threads may be sitting on a breakpoint too
+                let current = self.tid;
+                self.tid = tid;
+                let res = self.step_over_breakpoint();
+                self.tid = current;
+                if let Err(e) = res {
+                    println!("Unable to step thread {} over breakpoint: {}", tid, e);
                 }
-                MMapPath::Heap => println!("{}", format!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                                         m.address.0,
-                                                         m.address.1,
-                                                         m.offset,
-                                                         m.perms, "[ Heap ]").green()),
-                MMapPath::Stack => println!("{}", format!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                                         m.address.0,
-                                                         m.address.1,
-                                                         m.offset,
-                                                         m.perms, "[ Stack ]").purple()),
-                MMapPath::Vdso => println!("{}", format!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                                          m.address.0,
-                                                          m.address.1,
-                                                          m.offset,
-                                                          m.perms, "[ vdso ]").red()),
-                MMapPath::Vvar => println!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                           m.address.0,
-                                           m.address.1,
-                                           m.offset,
-                                           m.perms, "[ vvar ]"),
-                MMapPath::Vsyscall => println!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                           m.address.0,
-                                           m.address.1,
-                                           m.offset,
-                                           m.perms, "[ vsyscall ]"),
-                MMapPath::Anonymous => println!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                           m.address.0,
-                                           m.address.1,
-                                           m.offset,
-                                           m.perms, "[ Anonymous ]"),
-                MMapPath::TStack(u) => println!("{}", format!("{:<#16x}{:<#16x}{:<#126x}{:<4}  {:<}",
-                                                         m.address.0,
-                                                         m.address.1,
-                                                         m.offset,
-                                                         m.perms, u)),
-                MMapPath::Other(o) => println!("{}", format!("{:<#16x}{:<#16x}{:<#12x}{:<4}  {:<}",
-                                                         m.address.0,
-                                                         m.address.1,
-                                                         m.offset,
-                                                         m.perms, o).red()),
+            }
+            let sig = self.pending_signals.remove(&tid);
+            if let Err(e) = self.resume_thread(tid, sig) {
+                println!("Failed to continue thread {}: {}", tid, e);
+                continue;
+            }
+            self.thread_states.insert(tid, InferiorState::Running);
+        }
+        self.state = InferiorState::Running;
+    }
+
+    /* Restart a single thread that reported an event nobody needs to see,
+     * the other threads were never stopped */
+    fn cont_thread(&mut self, tid: Pid) {
+        let current = self.tid;
+        self.tid = tid;
+        if let Err(e) = self.step_over_breakpoint() {
+            println!("Unable to step over breakpoint: {}", e);
+        }
+        self.tid = current;
+        if self.state == InferiorState::Dead { return }
+
+        let sig = self.pending_signals.remove(&tid);
+        if let Err(e) = self.resume_thread(tid, sig) {
+            println!("Failed to continue thread {}: {}", tid, e);
+        }
+        self.thread_states.insert(tid, InferiorState::Running);
+        self.state = InferiorState::Running;
+    }
+
+    pub fn registers(&mut self) -> user_regs_struct {
+        ptrace::getregs(self.tid).expect("Failed to fetch register information.")
+    }
+
+    /* Set a software breakpoint on each location */
+    pub fn set_breakpoint(&mut self, bps: Vec<&str>) -> Result<Vec<usize>, failure::Error> {
+        if bps.is_empty() {
+            return Err(failure::format_err!("Please provide a location to break on"));
+        }
+
+        let mut ids = Vec::new();
+        for a in bps {
+            let loc = parse_location(a)?;
+
+            // Module relative, or nothing is mapped yet: wait for the
+            // module to load
+            if let Location::Module(module) = &loc {
+                ids.push(self.set_module_breakpoint(module)?);
+                continue;
+            }
+            if self.state != InferiorState::Stopped {
+                let module = self.location_in_executable(&loc)?;
+                ids.push(self.set_module_breakpoint(&module)?);
+                continue;
+            }
+
+            let addr = self.resolve_location(&loc)?;
+            let (funcname, funcoff) = match &loc {
+                Location::Symbol(name, off) => (name.clone(), *off),
+                _ => self.symbol_at(addr)
+                    .map(|(name, off)| (self.display_symbol(&name), off))
+                    .unwrap_or_default(),
             };
+            if self.breakpoints.contains_key(&addr) {
+                println!("Breakpoint already set at {:#x}", addr);
+                continue;
+            }
+
+            let (modname, base) = self.module_from_addr(addr)
+                .unwrap_or((String::new(), 0));
+            if !modname.is_empty() {
+                self.note_breakpoint_bounds(&modname, addr - base);
+            }
+            let id = self.alloc_bp_id();
+            self.breakpoints.insert(addr, Breakpoint {
+                id,
+                offset:    addr - base,
+                enabled:   false,
+                typ:       BreakpointType::Freq,
+                orig_byte: None,
+                funcname:  Arc::new(funcname),
+                funcoff,
+                modname:   Arc::new(modname),
+                freq:      0,
+                callback:  None,
+                condition: None,
+                ignore:    0,
+                resolve:   false,
+            });
+
+            if let Err(e) = self.activate_bp(addr) {
+                self.breakpoints.remove(&addr);
+                return Err(failure::format_err!("Unable to set breakpoint at {:#x}: {}", addr, e));
+            }
+            println!("Breakpoint {} at {:#x}{}", id, addr, self.addr_label(addr));
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    fn alloc_bp_id(&mut self) -> usize {
+        let id = self.next_bp_id;
+        self.next_bp_id += 1;
+        id
+    }
+
+    /// All breakpoints ordered by number, with their address
+    pub fn breakpoints(&self) -> Vec<(usize, &Breakpoint)> {
+        let mut bps: Vec<(usize, &Breakpoint)> = self.breakpoints.iter()
+            .filter(|(_, bp)| bp.id != 0)
+            .map(|(&addr, bp)| (addr, bp))
+            .collect();
+        bps.sort_by_key(|(_, bp)| bp.id);
+        bps
+    }
+
+    fn breakpoint_addr(&self, id: usize) -> Result<usize, failure::Error> {
+        self.breakpoints.iter()
+            .find(|(_, bp)| bp.id == id)
+            .map(|(&addr, _)| addr)
+            .ok_or_else(|| failure::format_err!("No breakpoint number {}", id))
+    }
+
+    /* Software breakpoint `id`, set or waiting for its module */
+    fn breakpoint_mut(&mut self, id: usize) -> Result<&mut Breakpoint, failure::Error> {
+        if let Ok(addr) = self.breakpoint_addr(id) {
+            return Ok(self.breakpoints.get_mut(&addr).unwrap());
+        }
+        self.pending_breakpoint_mut(id)
+            .ok_or_else(|| failure::format_err!("No breakpoint number {}", id))
+    }
+
+    /* Remove a breakpoint and forget about it */
+    pub fn delete_breakpoint(&mut self, id: usize) -> Result<(), failure::Error> {
+        if let Some(slot) = self.hw_slot(id) {
+            return self.delete_hw_breakpoint(slot);
+        }
+        if let Some(idx) = self.catchpoint_index(id) {
+            self.catchpoints.remove(idx);
+            return Ok(());
+        }
+        if self.delete_pending_breakpoint(id) {
+            return Ok(());
         }
+        let addr = self.breakpoint_addr(id)?;
+        self.deactivate_bp(addr)?;
+        self.breakpoints.remove(&addr);
+        Ok(())
     }
 
-    pub fn validate_addr(&mut self, _addr: usize) -> Result<MemoryMap, Error> {
-        let map = self.procfs.maps().unwrap()[0].clone();
-        Ok(map)
+    /// `delete` without arguments
+    pub fn delete_all_breakpoints(&mut self) {
+        let ids: Vec<usize> = self.breakpoints().iter().map(|(_, bp)| bp.id).collect();
+        for id in ids {
+            if let Err(e) = self.delete_breakpoint(id) {
+                println!("Unable to delete breakpoint {}: {}", id, e);
+            }
+        }
+        for slot in 0..HW_SLOTS {
+            if self.hw_breakpoints[slot].is_some() {
+                if let Err(e) = self.delete_hw_breakpoint(slot) {
+                    println!("Unable to delete hardware breakpoint: {}", e);
+                }
+            }
+        }
+        self.catchpoints.clear();
+        // Internal breakpoints waiting for their module stay
+        for bps in self.target_breakpoints.values_mut() {
+            bps.retain(|bp| bp.id == 0);
+        }
+    }
+
+    /* Pull the int3 out but keep the breakpoint around */
+    pub fn disable_breakpoint(&mut self, id: usize) -> Result<(), failure::Error> {
+        if let Some(slot) = self.hw_slot(id) {
+            return self.enable_hw_breakpoint(slot, false);
+        }
+        if let Some(idx) = self.catchpoint_index(id) {
+            self.catchpoints[idx].enabled = false;
+            return Ok(());
+        }
+        if let Some(bp) = self.pending_breakpoint_mut(id) {
+            bp.enabled = false;
+            return Ok(());
+        }
+        let addr = self.breakpoint_addr(id)?;
+        self.deactivate_bp(addr)?;
+        Ok(())
+    }
+
+    pub fn enable_breakpoint(&mut self, id: usize) -> Result<(), failure::Error> {
+        if let Some(slot) = self.hw_slot(id) {
+            return self.enable_hw_breakpoint(slot, true);
+        }
+        if let Some(idx) = self.catchpoint_index(id) {
+            self.catchpoints[idx].enabled = true;
+            return Ok(());
+        }
+        if let Some(bp) = self.pending_breakpoint_mut(id) {
+            bp.enabled = true;
+            return Ok(());
+        }
+        let addr = self.breakpoint_addr(id)?;
+        self.activate_bp(addr)?;
+        Ok(())
+    }
+
+    /* Only stop at breakpoint `id` when `cond` is true, `None` removes it */
+    pub fn set_condition(&mut self, id: usize, cond: Option<Expr>) -> Result<(), failure::Error> {
+        let bp = self.breakpoint_mut(id)?;
+        match &cond {
+            Some(c) => println!("Breakpoint {} condition: {}", id, c),
+            None => println!("Breakpoint {} now unconditional.", id),
+        }
+        bp.condition = cond;
+        Ok(())
+    }
+
+    /* Skip the next `count` hits of breakpoint `id` */
+    pub fn ignore_breakpoint(&mut self, id: usize, count: u64) -> Result<(), failure::Error> {
+        let bp = self.breakpoint_mut(id)?;
+        bp.ignore = bp.freq + count;
+        println!("Will ignore next {} crossings of breakpoint {}.", count, id);
+        Ok(())
+    }
+
+    /* Delete the breakpoint set at `addr` */
+    pub fn clear_breakpoint(&mut self, addr: usize) -> Result<(), failure::Error> {
+        let id = match self.breakpoints.get(&addr) {
+            Some(bp) => bp.id,
+            None => return Err(failure::format_err!("No breakpoint at {:#x}", addr)),
+        };
+        self.delete_breakpoint(id)?;
+        println!("Deleted breakpoint {}", id);
+        Ok(())
+    }
+
+    pub fn show_breakpoints(&self) {
+        let bps = self.breakpoints();
+        let hws = self.hw_breakpoints();
+        let pending = self.pending_breakpoints();
+        if bps.is_empty() && hws.is_empty() && pending.is_empty() && self.catchpoints.is_empty() {
+            println!("No breakpoints.");
+            return;
+        }
+
+        // Rows keyed by breakpoint number so both kinds list in order
+        let mut rows: Vec<(usize, String)> = Vec::new();
+        for (addr, bp) in bps {
+            let module = if bp.modname.is_empty() { String::new() }
+                         else { format!("{}+{:#x}", bp.modname, bp.offset) };
+            let func = if bp.funcname.is_empty() { String::new() }
+                       else { format!("{}+{:#x}", bp.funcname, bp.funcoff) };
+            let mut row = format!("{:<5}{:<8}{:<5}{:<#20x}{:<32}{:<24}{}",
+                                  bp.id,
+                                  format!("{:?}", bp.typ).to_lowercase(),
+                                  if bp.enabled { "y" } else { "n" },
+                                  addr, module, func, bp.freq);
+            if let Some(cond) = &bp.condition {
+                row.push_str(&format!("\n        stop only if {}", cond));
+            }
+            if bp.ignore > bp.freq {
+                row.push_str(&format!("\n        will ignore next {} hits", bp.ignore - bp.freq));
+            }
+            rows.push((bp.id, row));
+        }
+        for bp in pending {
+            let module = if bp.resolve { format!("{}!{}", bp.modname, bp.funcname) }
+                         else { format!("{}+{:#x}", bp.modname, bp.offset) };
+            let func = if bp.resolve && bp.funcoff != 0 { format!("+{:#x}", bp.funcoff) }
+                       else { String::new() };
+            let mut row = format!("{:<5}{:<8}{:<5}{:<20}{:<32}{:<24}{}",
+                                  bp.id,
+                                  format!("{:?}", bp.typ).to_lowercase(),
+                                  if bp.enabled { "y" } else { "n" },
+                                  "<PENDING>", module, func, bp.freq);
+            if let Some(cond) = &bp.condition {
+                row.push_str(&format!("\n        stop only if {}", cond));
+            }
+            rows.push((bp.id, row));
+        }
+        for (slot, hw) in hws {
+            rows.push((hw.id, format!("{:<5}{:<8}{:<5}{:<#20x}{:<32}{:<24}{}",
+                                      hw.id,
+                                      hw.kind.name(),
+                                      if hw.enabled { "y" } else { "n" },
+                                      hw.addr,
+                                      format!("DR{} len {}", slot, hw.len),
+                                      "", hw.freq)));
+        }
+        for catch in &self.catchpoints {
+            rows.push((catch.id, format!("{:<5}{:<8}{:<5}{:<20}{:<32}{:<24}{}",
+                                         catch.id,
+                                         "catch",
+                                         if catch.enabled { "y" } else { "n" },
+                                         "", catch.describe(), "", catch.freq)));
+        }
+        rows.sort_by_key(|(id, _)| *id);
+
+        println!("{:<5}{:<8}{:<5}{:<20}{:<32}{:<24}Hits",
+                 "Num", "Type", "Enb", "Address", "Module", "Function");
+        for (_, row) in rows {
+            println!("{}", row);
+        }
     }
 
-    pub fn set_breakpoint(&mut self, _bps: Vec<&str>) -> Result<(), hex::FromHexError> {
-        unimplemented!();
-        // Iter `Vec<&str>` and convert to hex address.
-        // let mut hexaddr: Vec<u8> = Vec::new();
-        // for a in bpvec {
-        //     match hex::decode(a) {
-        //         Ok(v) => hexaddr = v,
-        //         Err(err) => {
-        //             println!("Unable to parse {}. Error: {}", a, err);
-        //             ()
-        //         }
-        //     }
-        // }
-        // Ok(())
+    /* Patch an int3 over the breakpoint address, saving the original byte */
+    pub fn activate_bp(&mut self, addr: usize) -> Result<(), failure::Error> {
+        match self.breakpoints.get(&addr) {
+            Some(bp) if !bp.enabled => {},
+            _ => return Ok(()),
+        }
+
+        let orig = self.read_byte(addr)?;
+        self.write_byte(addr, 0xcc)?;
+
+        let bp = self.breakpoints.get_mut(&addr).unwrap();
+        bp.orig_byte = Some(orig);
+        bp.enabled = true;
+        Ok(())
     }
 
-    pub fn activate_bp(&mut self) {
-        unimplemented!();
+    /* Restore the original byte under the breakpoint */
+    pub fn deactivate_bp(&mut self, addr: usize) -> Result<(), failure::Error> {
+        let orig = match self.breakpoints.get(&addr) {
+            Some(bp) if bp.enabled => bp.orig_byte,
+            _ => return Ok(()),
+        };
+
+        if let Some(orig) = orig {
+            self.write_byte(addr, orig)?;
+        }
+        self.breakpoints.get_mut(&addr).unwrap().enabled = false;
+        Ok(())
     }
 
-    // pub fn readv(&self, addr: usize, len: usize) {
-    //     // Read Chunks using `process_vm_readv` instead of `ptrace`
-    //     //let mut local: IoVec<&mut [u8]>;
-    //     let local = IoVec::from_slice(&[len as u8]);
-    //     let remote = RemoteIoVec{ base: addr, len: len };
-    //     let result = process_vm_readv(self.pid, &IoVec::as_slice(local), &[remote]).unwrap();
-    //     println!("readv result: {}", result);
-    // }
+    /* Check if a SIGTRAP came from one of our breakpoints and report it */
+    fn handle_breakpoint(&mut self) -> BreakpointAction {
+        let mut regs = match ptrace::getregs(self.tid) {
+            Ok(regs) => regs,
+            Err(_) => return BreakpointAction::Unknown,
+        };
+
+        // RIP is one past the int3
+        let addr = (regs.rip - 1) as usize;
+        let condition = match self.breakpoints.get(&addr) {
+            Some(bp) if bp.enabled => bp.condition.clone(),
+            _ => return BreakpointAction::Unknown,
+        };
+
+        // Rewind so the original instruction runs when we continue
+        regs.rip = addr as u64;
+        if let Err(e) = ptrace::setregs(self.tid, regs) {
+            println!("Unable to rewind RIP: {}", e);
+        }
+
+        // Dynamic linker events, unless a breakpoint of the user's or a
+        // step is sitting on the same address
+        if self.handle_solib_event(addr) {
+            let ours = self.breakpoints.get(&addr)
+                .is_none_or(|bp| bp.id == 0 && bp.typ == BreakpointType::Freq);
+            if ours { return BreakpointAction::Resume }
+        }
+
+        if let Some(cond) = condition {
+            match cond.eval(&*self) {
+                Ok(0) => return BreakpointAction::Resume,
+                Ok(_) => {},
+                Err(e) => println!("Error in breakpoint condition \"{}\": {}", cond, e),
+            }
+        }
+
+        let bp = self.breakpoints.get_mut(&addr).unwrap();
+        bp.freq += 1;
+        if bp.freq <= bp.ignore {
+            return BreakpointAction::Resume;
+        }
+        let (id, freq, typ, callback) = (bp.id, bp.freq, bp.typ, bp.callback);
+        let location = bp.location();
+
+        // Internal breakpoints (stepping) are reported by whoever set them
+        if id != 0 {
+            println!("Breakpoint {} hit at {:#x} {} [hits: {}]", id, addr, location, freq);
+        }
+
+        if typ == BreakpointType::Single {
+            let _ = self.deactivate_bp(addr);
+            self.breakpoints.remove(&addr);
+        }
 
-    // Fix `func`
-    pub fn register_modload_callback(&mut self, _func: &str) {
-        unimplemented!();
+        if let Some(callback) = callback {
+            if !callback(self, i32::from(self.tid) as u32, addr, freq) {
+                println!("Breakpoint callback requested termination");
+                let _ = ptrace::kill(self.pid);
+                self.state = InferiorState::Dead;
+            }
+        }
+        BreakpointAction::Stop
+    }
+
+    /* Single step over an armed breakpoint at the current PC and re-arm it */
+    fn step_over_breakpoint(&mut self) -> Result<(), failure::Error> {
+        let pc = ptrace::getregs(self.tid)?.rip as usize;
+        if self.breakpoint_at(pc) {
+            self.step_instruction()?;
+        }
+        Ok(())
+    }
+
+    /* Is there an armed software breakpoint or execute hardware breakpoint
+     * at `pc`. Execute breakpoints fault before the instruction, so they
+     * would fire again straight away if not stepped over */
+    fn breakpoint_at(&self, pc: usize) -> bool {
+        let sw = match self.breakpoints.get(&pc) {
+            Some(bp) => bp.enabled,
+            None => false,
+        };
+        sw || self.hw_exec_slot(pc).is_some()
+    }
+
+    /* Module name and base of the file mapping holding `addr` */
+    fn module_from_addr(&self, addr: usize) -> Option<(String, usize)> {
+        let name = maps::map_file_name(self.mem.find(addr)?)?;
+        self.modules.iter().find(|(n, _)| *n == name).cloned()
+    }
+
+    /* Write the original bytes back for every active breakpoint */
+    fn remove_breakpoints(&mut self) {
+        let active: Vec<(usize, u8)> = self.breakpoints.iter()
+            .filter(|(_, bp)| bp.enabled)
+            .filter_map(|(&addr, bp)| bp.orig_byte.map(|b| (addr, b)))
+            .collect();
+
+        for (addr, byte) in active {
+            if let Err(e) = self.write_byte(addr, byte) {
+                println!("Unable to remove breakpoint at {:#x}: {}", addr, e);
+            }
+            if let Some(bp) = self.breakpoints.get_mut(&addr) {
+                bp.enabled = false;
+            }
+        }
+    }
+
+    fn read_byte(&self, addr: usize) -> Result<u8, failure::Error> {
+        Ok(self.read_memory(addr, 1)?[0])
+    }
+
+    fn write_byte(&self, addr: usize, byte: u8) -> Result<(), failure::Error> {
+        self.write_memory(addr, &[byte])?;
+        Ok(())
+    }
+
+    /// Registers a function to be called every time a module is mapped
+    /// into the target, with its file name and base. Modules loaded before
+    /// this call aren't reported.
+    pub fn register_modload_callback(&mut self, func: ModLoadFn) -> Result<(), failure::Error> {
+        let callbacks = self.module_load_callbacks.get_or_insert_with(Default::default);
+        match Arc::get_mut(callbacks) {
+            Some(callbacks) => {
+                callbacks.push(func);
+                Ok(())
+            },
+            // Only shared while the callbacks run
+            None => Err(failure::format_err!("Module load callbacks can't be registered from a module load callback")),
+        }
     }
 
     /// Registers a breakpoint for a specific file
@@ -543,20 +1225,15 @@ impl Inferior {
         if !self.target_breakpoints.contains_key(&**module) {
             self.target_breakpoints.insert(module.to_string(), Vec::new());
         }
-
-        if !self.breakpoint_bounds.contains_key(&**module) {
-            self.breakpoint_bounds.insert(module.to_string(), (!0, 0));
-        }
-
-        let mmbp = self.breakpoint_bounds.get_mut(&**module).unwrap();
-        mmbp.0 = std::cmp::min(mmbp.0, offset as usize);
-        mmbp.1 = std::cmp::max(mmbp.1, offset as usize);
+        self.note_breakpoint_bounds(&module, offset);
 
         // Append this breakpoint
+        let id = self.alloc_bp_id();
         self.target_breakpoints.get_mut(&**module).unwrap().push(
             Breakpoint {
+                id,
                 offset:    offset as usize,
-                enabled:   false,
+                enabled:   true,
                 typ:       typ,
                 orig_byte: None,
                 funcname:  name.clone(),
@@ -564,8 +1241,21 @@ impl Inferior {
                 modname:   module.clone(),
                 freq:      0,
                 callback,
+                condition: None,
+                ignore:    0,
+                resolve:   false,
             }
         );
+
+        // Already loaded, e.g. registered from a module load callback
+        let loaded = self.modules.iter()
+            .find(|(file, _)| pending::module_matches(file, &module))
+            .cloned();
+        if let Some((file, base)) = loaded {
+            if self.state == InferiorState::Stopped {
+                self.arm_pending_breakpoints(&file, base);
+            }
+        }
     }
 
     pub fn set_always_freq(&mut self, val: bool) { self.always_freq = val; }
@@ -589,90 +1279,92 @@ impl Inferior {
 
     fn process_procfs(&mut self) {
         self.procfs = Process::new(i32::from(self.pid)).expect("Unable to parse procfs data.");
+    }
 
-        // Environment
-        self.env = self.procfs.environ().unwrap();
+    /* Follow the file backed mappings in /proc with the module list.
+     * Once the dynamic linker's r_debug is known the list follows
+     * link_map instead, see solib.rs */
+    fn refresh_modules(&mut self) {
+        self.refresh_maps();
+        if self.r_debug.is_some() { return }
+
+        // The first mapping of a file is its base
+        let current: HashSet<(String, usize)> = self.mem.iter()
+            .filter(|m| m.offset == 0)
+            .filter_map(|m| maps::map_file_name(m).map(|name| (name, m.address.0 as usize)))
+            .collect();
+
+        let gone: Vec<usize> = self.modules.difference(&current).map(|(_, base)| *base).collect();
+        for base in gone {
+            self.unregister_module(base);
+        }
+        let new: Vec<usize> = current.difference(&self.modules).map(|(_, base)| *base).collect();
+        for base in new {
+            self.register_module(base);
+        }
     }
 
-    /// Resolves the file name of a given memory mapped file in the target
-    /// process
-    #[allow(dead_code)]
-    fn filename_from_module_base(&self, _base: usize) -> String {
-        unimplemented!();
-        // Use GetMappedFileNameW() to get the mapped file name
-        // let mut buf = [0u16; 4096];
-        // let fnlen = unsafe {
-        //     GetMappedFileNameW(self.process_handle(),
-        //                        _base as *mut _, buf.as_mut_ptr(), buf.len() as u32)
-        // };
-        // assert!(fnlen != 0 && (fnlen as usize) < buf.len(),
-        //         "GetMappedFileNameW() failed");
+}
 
-        // // Convert the name to utf-8 and lowercase it
-        // let path = String::from_utf16(&buf[..fnlen as usize]).unwrap()
-        //     .to_lowercase();
 
-        // // Get the filename from the path
-        // Path::new(&path).file_name().unwrap().to_str().unwrap().into()
+/* Registers and memory for breakpoint conditions */
+impl ExprContext for Inferior {
+    fn register(&self, name: &str) -> Result<u64, failure::Error> {
+        let regs = self.frame_registers()?;
+        registers::read_register(&regs, name)
+            .ok_or_else(|| failure::format_err!("Invalid register \"${}\"", name))
     }
 
-    /// Add the module loaded at `_base` in the target process to our module
-    /// list
-    #[allow(dead_code)]
-    fn register_module(&mut self, _base: usize) {
-        let filename = self.filename_from_module_base(_base);
-
-        // Insert into the module list
-        self.modules.insert((filename.into(), _base));
+    fn memory(&self, addr: u64, size: usize) -> Result<u64, failure::Error> {
+        let bytes = self.read_exact(addr as usize, size)?;
+        Ok(bytes.iter().rev().fold(0, |v, &b| (v << 8) | b as u64))
     }
+}
 
-  /// Remove the module loaded at `_base` in the target process from our
-    /// module list
-    #[allow(dead_code)]
-    fn unregister_module(&mut self, _base: usize) {
-        let mut to_remove = None;
-
-        // Find the corresponding module to this base
-        for module in self.modules.iter() {
-            if module.1 == _base {
-                to_remove = Some(module.clone());
-            }
-        }
-
-        if let Some(to_remove) = to_remove {
-            if self.breakpoint_bounds.contains_key(&to_remove.0) {
-                // If there are breakpoints in this module, unregister those too
-
-                // Get minimum and maximum offsets into the module where
-                // breakpoints are applied
-                let minmax = self.breakpoint_bounds[&to_remove.0];
+/* Helpers */
 
-                let start_addr = _base + minmax.0;
-                let end_addr   = _base + minmax.1;
+pub fn stdio_flush() {
+    stdout().flush().expect("Failed to flush stdout");
+    stderr().flush().expect("Failed to flush stderr");
+}
 
-                // Remove any breakpoints which are present in this range
-                self.breakpoints.retain(|&k, _| {
-                    k < start_addr || k > end_addr
-                });
-            }
+/* Parse a hex address with or without the `0x` prefix */
+pub fn parse_addr(s: &str) -> Result<usize, std::num::ParseIntError> {
+    let s = s.trim();
+    let s = s.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(s, 16)
+}
 
-            // Remove the module and breakpoint info for the module
-            self.modules.remove(&to_remove);
-        } else {
-            // Got unregister module for unknown DLL
-            // Our database is out of sync with reality
-            panic!("Unexpected library unload of base 0x{:x}\n", _base);
+/* Thread ids of a process, from /proc/<pid>/task */
+fn task_ids(pid: Pid) -> Result<Vec<u32>, std::io::Error> {
+    let mut tids = Vec::new();
+    for entry in std::fs::read_dir(format!("/proc/{}/task", pid))? {
+        if let Ok(tid) = entry?.file_name().to_string_lossy().parse::<u32>() {
+            tids.push(tid);
         }
     }
-
+    tids.sort();
+    Ok(tids)
 }
 
+/* Patch a single byte in `pid`, keeping the rest of the word */
+fn write_byte_to(pid: Pid, addr: usize, byte: u8) -> Result<(), Error> {
+    let word = ptrace::read(pid, addr as ptrace::AddressType)?;
+    let word = (word & !0xff) | byte as i64;
+    ptrace::write(pid, addr as ptrace::AddressType, word as *mut c_void)
+}
 
-/* Helpers */
-
-pub fn stdio_flush() {
-    stdout().flush().expect("Failed to flush stdout");
-    stderr().flush().expect("Failed to flush stderr");
+/* Wait for a freshly interrupted thread to report its stop. A signal
+ * stop instead of our interrupt's PTRACE_EVENT_STOP returns the signal. */
+fn wait_for_stop(tid: Pid) -> Option<signal::Signal> {
+    loop {
+        match waitpid(tid, Some(WaitPidFlag::__WALL)) {
+            Ok(WaitStatus::Stopped(_, sig)) => return Some(sig),
+            Ok(WaitStatus::PtraceEvent(..)) => return None,
+            Ok(WaitStatus::Exited(..)) | Ok(WaitStatus::Signaled(..)) | Err(_) => return None,
+            Ok(_) => continue,
+        }
+    }
 }
 
 /* Get elapsed time in seconds */
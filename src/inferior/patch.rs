@@ -0,0 +1,106 @@
+/* Changing the inferior's state
+ * `set $reg = ..` goes through PTRACE_SETREGS, memory through the memory
+ * writer so read-only text can be patched too. Bytes under our software
+ * breakpoints are saved as the breakpoint's original byte and the int3
+ * stays in place.
+ */
+
+use nix::sys::ptrace;
+
+use super::expr::Expr;
+use super::registers::write_register;
+use super::{ Inferior, InferiorState };
+
+impl Inferior {
+    fn check_writable(&self) -> Result<(), failure::Error> {
+        if self.state != InferiorState::Stopped {
+            return Err(failure::format_err!("The program is not being run or is not stopped"));
+        }
+        Ok(())
+    }
+
+    /// `set $reg = value` on the current thread
+    pub fn set_register(&mut self, name: &str, value: u64) -> Result<(), failure::Error> {
+        self.check_writable()?;
+        if self.outer_frame_selected() {
+            return Err(failure::format_err!("Registers can only be changed in frame 0"));
+        }
+        let mut regs = ptrace::getregs(self.tid)?;
+        write_register(&mut regs, name, value)?;
+        ptrace::setregs(self.tid, regs)?;
+        Ok(())
+    }
+
+    /// `set <lvalue> = <expr>`, the target being `$reg` or `*(type*)addr`
+    pub fn assign(&mut self, target: &Expr, value: &Expr) -> Result<(), failure::Error> {
+        let value = value.eval(&*self)?;
+        match target {
+            Expr::Reg(name) => self.set_register(name, value),
+            Expr::Deref(addr, size) => {
+                let addr = addr.eval(&*self)? as usize;
+                self.patch_memory(addr, &value.to_le_bytes()[..*size])
+            },
+            _ => Err(failure::format_err!("Left operand of assignment is not an lvalue")),
+        }
+    }
+
+    /// Write all of `data` at `addr` or fail. Breakpoints in the range keep
+    /// their int3 and remember the new byte for when they're removed.
+    pub fn patch_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), failure::Error> {
+        self.check_writable()?;
+        self.check_mapped(addr, data.len())?;
+
+        // Armed breakpoints keep their int3, what was asked for becomes
+        // the byte they restore
+        let covered: Vec<usize> = self.breakpoints.iter()
+            .filter(|(&bp_addr, bp)| bp.enabled && bp_addr >= addr && bp_addr < addr + data.len())
+            .map(|(&bp_addr, _)| bp_addr)
+            .collect();
+        let mut bytes = data.to_vec();
+        for &bp_addr in &covered {
+            bytes[bp_addr - addr] = 0xcc;
+        }
+
+        // Only what actually reached memory changes the table
+        let done = self.write_memory(addr, &bytes)?;
+        for bp_addr in covered.into_iter().filter(|&bp_addr| bp_addr - addr < done) {
+            if let Some(bp) = self.breakpoints.get_mut(&bp_addr) {
+                bp.orig_byte = Some(data[bp_addr - addr]);
+            }
+        }
+        if done < data.len() {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr + done));
+        }
+        Ok(())
+    }
+
+    /// `fill <addr> <len> <byte>`
+    pub fn fill_memory(&mut self, addr: usize, len: usize, byte: u8) -> Result<(), failure::Error> {
+        // Before allocating `len` bytes of it
+        let mapped = self.clamp_to_mapped(addr, len)?;
+        if mapped < len {
+            return Err(failure::format_err!("Cannot access memory at address {:#x}", addr + mapped));
+        }
+        self.patch_memory(addr, &vec![byte; len])
+    }
+}
+
+/// Bytes for `write <addr> <hex bytes>`: `41 42 43`, `414243` or `0x41 0x42`
+pub fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, failure::Error> {
+    let mut bytes = Vec::new();
+    for word in s.split_whitespace() {
+        let word = word.trim_start_matches("0x").trim_start_matches("\\x");
+        if !word.is_ascii() || word.len() % 2 != 0 {
+            return Err(failure::format_err!("Invalid hex bytes \"{}\"", word));
+        }
+        for i in (0..word.len()).step_by(2) {
+            let byte = u8::from_str_radix(&word[i..i + 2], 16)
+                .map_err(|_| failure::format_err!("Invalid hex byte \"{}\"", &word[i..i + 2]))?;
+            bytes.push(byte);
+        }
+    }
+    if bytes.is_empty() {
+        return Err(failure::format_err!("No bytes to write"));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,248 @@
+/* Deferred breakpoints
+ * `break libfoo.so!func` and `break libfoo.so+0x1234` name a module rather
+ * than an address. Until a matching module is mapped they wait in
+ * `target_breakpoints` under the name they were given, and `enabled` on a
+ * waiting breakpoint says whether to arm it once the module shows up.
+ * When the module is unloaded its breakpoints go back to waiting.
+ */
+
+use procfs::MMapPath;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{ parse_addr, Breakpoint, BreakpointType, Inferior, InferiorState };
+
+/// Where in a module to break
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModuleTarget {
+    /// Bytes from the module base
+    Offset(usize),
+    /// A symbol and an offset into it
+    Symbol(String, usize),
+}
+
+/// `module!symbol[+off]` or `module+off`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleLocation {
+    pub module: String,
+    pub target: ModuleTarget,
+}
+
+impl fmt::Display for ModuleLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.target {
+            ModuleTarget::Offset(off) => write!(f, "{}+{:#x}", self.module, off),
+            ModuleTarget::Symbol(name, 0) => write!(f, "{}!{}", self.module, name),
+            ModuleTarget::Symbol(name, off) => write!(f, "{}!{}+{:#x}", self.module, name, off),
+        }
+    }
+}
+
+fn parse_offset(s: &str) -> Option<usize> {
+    let s = s.trim();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        usize::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Parse a module relative location, None for anything else
+pub fn parse_module_location(s: &str) -> Option<ModuleLocation> {
+    let s = s.trim();
+    if let Some(pos) = s.find('!') {
+        let (module, sym) = (&s[..pos], &s[pos + 1..]);
+        let (name, off) = match sym.rfind('+') {
+            Some(p) => (&sym[..p], parse_offset(&sym[p + 1..])?),
+            None => (sym, 0),
+        };
+        if module.is_empty() || name.is_empty() { return None }
+        return Some(ModuleLocation {
+            module: module.to_string(),
+            target: ModuleTarget::Symbol(name.to_string(), off),
+        });
+    }
+
+    let pos = s.rfind('+')?;
+    let (module, off) = (&s[..pos], parse_offset(&s[pos + 1..])?);
+    // `0x1000+0x10` and `$rip+4` are expressions
+    if module.is_empty() || module.starts_with('$') || parse_addr(module).is_ok() {
+        return None;
+    }
+    Some(ModuleLocation { module: module.to_string(), target: ModuleTarget::Offset(off) })
+}
+
+/// Does the loaded file `file` answer to `wanted`. Version suffixes can be
+/// left off, `libc.so` and `libc` both match `libc.so.6`.
+pub fn module_matches(file: &str, wanted: &str) -> bool {
+    file == wanted || (file.starts_with(wanted) && file[wanted.len()..].starts_with('.'))
+}
+
+impl Inferior {
+    /* Widen the range of offsets breakpoints use in `module` */
+    pub(crate) fn note_breakpoint_bounds(&mut self, module: &str, offset: usize) {
+        let bounds = self.breakpoint_bounds.entry(module.to_string()).or_insert((!0, 0));
+        bounds.0 = std::cmp::min(bounds.0, offset);
+        bounds.1 = std::cmp::max(bounds.1, offset);
+    }
+
+    /* Path of the file mapped at `base` */
+    pub(crate) fn module_path(&self, base: usize) -> Option<PathBuf> {
+        match &self.mem.find(base)?.pathname {
+            MMapPath::Path(p) => Some(p.clone()),
+            _ => None,
+        }
+    }
+
+    /// `break module!symbol` or `break module+off`. Set right away if the
+    /// module is loaded, otherwise when it is.
+    pub fn set_module_breakpoint(&mut self, loc: &ModuleLocation) -> Result<usize, failure::Error> {
+        let (name, offset) = match &loc.target {
+            ModuleTarget::Symbol(name, off) => (name.clone(), *off),
+            ModuleTarget::Offset(off) => (String::new(), *off),
+        };
+        let symbol = !name.is_empty();
+
+        let id = self.alloc_bp_id();
+        let bp = Breakpoint {
+            id,
+            offset,
+            enabled:   true,
+            typ:       BreakpointType::Freq,
+            orig_byte: None,
+            funcname:  Arc::new(name),
+            funcoff:   if symbol { offset } else { 0 },
+            modname:   Arc::new(loc.module.clone()),
+            freq:      0,
+            callback:  None,
+            condition: None,
+            ignore:    0,
+            resolve:   symbol,
+        };
+
+        let loaded = self.modules.iter()
+            .find(|(file, _)| module_matches(file, &loc.module))
+            .cloned();
+        match loaded {
+            Some((file, base)) if self.state == InferiorState::Stopped => {
+                let addr = self.arm_breakpoint(bp, &file, base)?;
+                println!("Breakpoint {} at {:#x} ({})", id, addr, loc);
+            },
+            _ => {
+                if !symbol {
+                    self.note_breakpoint_bounds(&loc.module, offset);
+                }
+                self.target_breakpoints.entry(loc.module.clone()).or_default().push(bp);
+                println!("Breakpoint {} ({}) pending.", id, loc);
+            },
+        }
+        Ok(id)
+    }
+
+    /* Put a waiting breakpoint in `file` loaded at `base`, looking its
+     * symbol up first if it has one. Returns the address. */
+    fn arm_breakpoint(&mut self, mut bp: Breakpoint, file: &str, base: usize) -> Result<usize, failure::Error> {
+        if bp.resolve {
+            bp.offset = self.module_symbol_offset(base, &bp.funcname)? + bp.funcoff;
+            bp.resolve = false;
+        }
+
+        let addr = base + bp.offset;
+        if self.breakpoints.contains_key(&addr) {
+            return Err(failure::format_err!("Breakpoint already set at {:#x}", addr));
+        }
+
+        let enabled = bp.enabled;
+        bp.enabled = false;
+        bp.orig_byte = None;
+        bp.modname = Arc::new(file.to_string());
+        self.note_breakpoint_bounds(file, bp.offset);
+        self.breakpoints.insert(addr, bp);
+        if enabled {
+            if let Err(e) = self.activate_bp(addr) {
+                self.breakpoints.remove(&addr);
+                return Err(e);
+            }
+        }
+        Ok(addr)
+    }
+
+    /* `file` was just mapped at `base`, arm what's been waiting for it */
+    pub(crate) fn arm_pending_breakpoints(&mut self, file: &str, base: usize) {
+        let keys: Vec<String> = self.target_breakpoints.keys()
+            .filter(|wanted| module_matches(file, wanted))
+            .cloned()
+            .collect();
+
+        for key in keys {
+            let waiting = self.target_breakpoints.remove(&key).unwrap_or_default();
+            let mut failed = Vec::new();
+            for bp in waiting {
+                let id = bp.id;
+                match self.arm_breakpoint(bp.clone(), file, base) {
+                    Ok(addr) => if id != 0 && self.verbose {
+                        println!("Breakpoint {} armed at {:#x} in {}", id, addr, file);
+                    },
+                    Err(e) => {
+                        println!("Unable to set breakpoint {} in {}: {}", id, file, e);
+                        // Keep it listed as pending rather than losing it
+                        failed.push(bp);
+                    },
+                }
+            }
+            if !failed.is_empty() {
+                self.target_breakpoints.entry(key).or_default().extend(failed);
+            }
+        }
+    }
+
+    /* `file` at `base` is going away. Its breakpoints wait for it to come
+     * back; the code is already gone so there's nothing to write. */
+    pub(crate) fn unarm_module_breakpoints(&mut self, file: &str, base: usize) {
+        let (min, max) = match self.breakpoint_bounds.get(file) {
+            Some(&bounds) => bounds,
+            None => return,
+        };
+        let (start, end) = (base + min, base + max);
+
+        let addrs: Vec<usize> = self.breakpoints.iter()
+            .filter(|(&addr, bp)| addr >= start && addr <= end && *bp.modname == file)
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in addrs {
+            let mut bp = self.breakpoints.remove(&addr).unwrap();
+            if bp.id == 0 { continue }
+            bp.orig_byte = None;
+            self.target_breakpoints.entry(file.to_string()).or_default().push(bp);
+        }
+    }
+
+    /* Breakpoints waiting for a module, by number */
+    pub(crate) fn pending_breakpoints(&self) -> Vec<&Breakpoint> {
+        let mut bps: Vec<&Breakpoint> = self.target_breakpoints.values()
+            .flat_map(|bps| bps.iter())
+            .filter(|bp| bp.id != 0)
+            .collect();
+        bps.sort_by_key(|bp| bp.id);
+        bps
+    }
+
+    pub(crate) fn pending_breakpoint_mut(&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.target_breakpoints.values_mut()
+            .flat_map(|bps| bps.iter_mut())
+            .find(|bp| bp.id == id)
+    }
+
+    /* Forget a waiting breakpoint, false if there's none numbered `id` */
+    pub(crate) fn delete_pending_breakpoint(&mut self, id: usize) -> bool {
+        for bps in self.target_breakpoints.values_mut() {
+            if let Some(idx) = bps.iter().position(|bp| bp.id == id) {
+                bps.remove(idx);
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -1,5 +1,237 @@
-use std::unimplemented;
+/* General purpose register model
+ * Every name resolves to a field of `user_regs_struct` plus a size and bit
+ * offset, so sub-registers (eax, ax, al, ah, r8d, ...) read and write
+ * the right bits of their full register.
+ */
 
-pub struct registers {
-    unimplemented!()
+use colored::*;
+use libc::user_regs_struct;
+use nix::sys::ptrace;
+
+use super::fpregs::is_fp_register;
+use super::Inferior;
+
+/// Registers in the order `info registers` shows them
+pub static GENERAL_REGISTERS: &[&str] = &[
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "rip", "eflags",
+    "cs", "ss", "ds", "es", "fs", "gs",
+    "fs_base", "gs_base",
+];
+
+/// Sub-registers: name, full register, size in bytes, bit offset.
+/// `sp` and `bp`'s 16 bit halves aren't here because `$sp` is the stack
+/// pointer, same as gdb.
+static SUB_REGISTERS: &[(&str, &str, usize, u32)] = &[
+    ("eax", "rax", 4, 0), ("ax", "rax", 2, 0), ("al", "rax", 1, 0), ("ah", "rax", 1, 8),
+    ("ebx", "rbx", 4, 0), ("bx", "rbx", 2, 0), ("bl", "rbx", 1, 0), ("bh", "rbx", 1, 8),
+    ("ecx", "rcx", 4, 0), ("cx", "rcx", 2, 0), ("cl", "rcx", 1, 0), ("ch", "rcx", 1, 8),
+    ("edx", "rdx", 4, 0), ("dx", "rdx", 2, 0), ("dl", "rdx", 1, 0), ("dh", "rdx", 1, 8),
+    ("esi", "rsi", 4, 0), ("si", "rsi", 2, 0), ("sil", "rsi", 1, 0),
+    ("edi", "rdi", 4, 0), ("di", "rdi", 2, 0), ("dil", "rdi", 1, 0),
+    ("ebp", "rbp", 4, 0), ("bpl", "rbp", 1, 0),
+    ("esp", "rsp", 4, 0), ("spl", "rsp", 1, 0),
+    ("r8d", "r8", 4, 0), ("r8w", "r8", 2, 0), ("r8b", "r8", 1, 0), ("r8l", "r8", 1, 0),
+    ("r9d", "r9", 4, 0), ("r9w", "r9", 2, 0), ("r9b", "r9", 1, 0), ("r9l", "r9", 1, 0),
+    ("r10d", "r10", 4, 0), ("r10w", "r10", 2, 0), ("r10b", "r10", 1, 0), ("r10l", "r10", 1, 0),
+    ("r11d", "r11", 4, 0), ("r11w", "r11", 2, 0), ("r11b", "r11", 1, 0), ("r11l", "r11", 1, 0),
+    ("r12d", "r12", 4, 0), ("r12w", "r12", 2, 0), ("r12b", "r12", 1, 0), ("r12l", "r12", 1, 0),
+    ("r13d", "r13", 4, 0), ("r13w", "r13", 2, 0), ("r13b", "r13", 1, 0), ("r13l", "r13", 1, 0),
+    ("r14d", "r14", 4, 0), ("r14w", "r14", 2, 0), ("r14b", "r14", 1, 0), ("r14l", "r14", 1, 0),
+    ("r15d", "r15", 4, 0), ("r15w", "r15", 2, 0), ("r15b", "r15", 1, 0), ("r15l", "r15", 1, 0),
+    ("eip", "rip", 4, 0), ("ip", "rip", 2, 0),
+];
+
+/// RFLAGS bits and their names
+pub static RFLAGS_BITS: &[(u32, &str)] = &[
+    (0, "CF"), (2, "PF"), (4, "AF"), (6, "ZF"), (7, "SF"), (8, "TF"),
+    (9, "IF"), (10, "DF"), (11, "OF"), (14, "NT"), (16, "RF"), (17, "VM"),
+    (18, "AC"), (19, "VIF"), (20, "VIP"), (21, "ID"),
+];
+
+/// Where a register name lives in `user_regs_struct`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegRef {
+    /// Full register holding it
+    pub full: &'static str,
+    /// Size in bytes
+    pub size: usize,
+    /// Bit offset into the full register (8 for ah/bh/ch/dh)
+    pub shift: u32,
+}
+
+impl RegRef {
+    fn mask(&self) -> u64 {
+        if self.size >= 8 { !0 } else { (1u64 << (self.size * 8)) - 1 }
+    }
+}
+
+/// Resolve a register name, full or sub-register, as well as the
+/// `pc`, `sp`, `fp` and `rflags` aliases
+pub fn lookup(name: &str) -> Option<RegRef> {
+    let name = name.trim_start_matches('$').to_lowercase();
+    let full = match name.as_str() {
+        "pc" => "rip",
+        "sp" => "rsp",
+        "fp" => "rbp",
+        "rflags" => "eflags",
+        "orig_rax" => "orig_rax",
+        n => match GENERAL_REGISTERS.iter().find(|r| **r == n) {
+            Some(r) => *r,
+            None => {
+                return SUB_REGISTERS.iter()
+                    .find(|(sub, _, _, _)| *sub == n)
+                    .map(|&(_, full, size, shift)| RegRef { full, size, shift });
+            },
+        },
+    };
+    Some(RegRef { full, size: full_size(full), shift: 0 })
+}
+
+/* Architectural size, the struct has 8 bytes for everything */
+fn full_size(name: &str) -> usize {
+    match name {
+        "eflags" => 4,
+        "cs" | "ss" | "ds" | "es" | "fs" | "gs" => 2,
+        _ => 8,
+    }
+}
+
+fn field<'a>(regs: &'a mut user_regs_struct, name: &str) -> Option<&'a mut u64> {
+    Some(match name {
+        "rax" => &mut regs.rax, "rbx" => &mut regs.rbx, "rcx" => &mut regs.rcx, "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi, "rdi" => &mut regs.rdi, "rbp" => &mut regs.rbp, "rsp" => &mut regs.rsp,
+        "r8" => &mut regs.r8, "r9" => &mut regs.r9, "r10" => &mut regs.r10, "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12, "r13" => &mut regs.r13, "r14" => &mut regs.r14, "r15" => &mut regs.r15,
+        "rip" => &mut regs.rip,
+        "eflags" => &mut regs.eflags,
+        "orig_rax" => &mut regs.orig_rax,
+        "cs" => &mut regs.cs, "ss" => &mut regs.ss, "ds" => &mut regs.ds,
+        "es" => &mut regs.es, "fs" => &mut regs.fs, "gs" => &mut regs.gs,
+        "fs_base" => &mut regs.fs_base, "gs_base" => &mut regs.gs_base,
+        _ => return None,
+    })
+}
+
+/// Value of a register or sub-register
+pub fn read_register(regs: &user_regs_struct, name: &str) -> Option<u64> {
+    let reg = lookup(name)?;
+    let mut regs = *regs;
+    let full = *field(&mut regs, reg.full)?;
+    Some((full >> reg.shift) & reg.mask())
+}
+
+/// Set a register, a sub-register only replaces its own bits
+pub fn write_register(regs: &mut user_regs_struct, name: &str, value: u64) -> Result<(), failure::Error> {
+    let reg = lookup(name).ok_or_else(|| failure::format_err!("Invalid register \"${}\"", name))?;
+    if reg.size < 8 && value & !reg.mask() != 0 && (value as i64) >> (reg.size * 8 - 1) != -1 {
+        return Err(failure::format_err!("Value {:#x} doesn't fit in ${} ({} bytes)", value, name, reg.size));
+    }
+    let full = field(regs, reg.full).unwrap();
+    let mask = reg.mask() << reg.shift;
+    *full = (*full & !mask) | ((value << reg.shift) & mask);
+    Ok(())
+}
+
+/// `[ ZF PF IF ]` for the set bits of RFLAGS
+pub fn decode_rflags(flags: u64) -> String {
+    let set: Vec<&str> = RFLAGS_BITS.iter()
+        .filter(|(bit, _)| flags & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    format!("[ {} ]", set.join(" "))
+}
+
+/// One line of `info registers` and the TUI Registers panel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterRow {
+    pub name: String,
+    pub value: u64,
+    pub size: usize,
+    /// Different from the last time the inferior stopped
+    pub changed: bool,
+}
+
+impl RegisterRow {
+    /// Zero padded hex to the register's size
+    pub fn hex(&self) -> String {
+        format!("{:#0width$x}", self.value, width = self.size * 2 + 2)
+    }
+
+    /// Flags for RFLAGS, signed decimal for the rest
+    pub fn natural(&self) -> String {
+        match self.name.as_str() {
+            "eflags" | "rflags" => decode_rflags(self.value),
+            "rip" | "pc" | "rsp" | "rbp" | "sp" | "fp" | "fs_base" | "gs_base" => self.hex(),
+            _ => {
+                let shift = 64 - self.size as u32 * 8;
+                (((self.value << shift) as i64) >> shift).to_string()
+            },
+        }
+    }
+}
+
+/// Rows for `names`, all general purpose registers when empty. `prev`
+/// are the registers at the last stop.
+pub fn register_rows(regs: &user_regs_struct, prev: Option<&user_regs_struct>, names: &[&str])
+                     -> Result<Vec<RegisterRow>, failure::Error> {
+    let names = if names.is_empty() { GENERAL_REGISTERS } else { names };
+    names.iter()
+        .map(|name| {
+            let name = name.trim_start_matches('$').to_lowercase();
+            let reg = lookup(&name).ok_or_else(|| failure::format_err!("Invalid register \"{}\"", name))?;
+            let value = read_register(regs, &name).unwrap();
+            let changed = prev.and_then(|p| read_register(p, &name)).is_some_and(|v| v != value);
+            Ok(RegisterRow { name, value, size: reg.size, changed })
+        })
+        .collect()
+}
+
+impl Inferior {
+    /* Remember the registers before resuming so the next stop can tell
+     * which ones changed. The selected frame is gone once we run. */
+    pub(crate) fn save_stop_registers(&mut self) {
+        self.clear_selected_frame();
+        self.stop_regs = ptrace::getregs(self.tid).ok().map(|regs| (self.tid, regs));
+    }
+
+    /// Registers of the current thread in the selected frame, `names`
+    /// empty for all of them
+    pub fn register_rows(&self, names: &[&str]) -> Result<Vec<RegisterRow>, failure::Error> {
+        let regs = self.frame_registers()?;
+        let prev = match &self.stop_regs {
+            Some((tid, prev)) if *tid == self.tid && !self.outer_frame_selected() => Some(prev),
+            _ => None,
+        };
+        register_rows(&regs, prev, names)
+    }
+
+    /// `info registers [name...]`, vector and x87 names are shown by
+    /// `show_fp_register`
+    pub fn show_registers(&self, names: &[&str]) -> Result<(), failure::Error> {
+        if names.is_empty() {
+            return self.print_register_rows(&[]);
+        }
+        for name in names {
+            if is_fp_register(name) {
+                self.show_fp_register(name)?;
+            } else {
+                self.print_register_rows(&[name])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_register_rows(&self, names: &[&str]) -> Result<(), failure::Error> {
+        for row in self.register_rows(names)? {
+            let line = format!("{:<10}{:<20}{}", row.name, row.hex(), row.natural());
+            if row.changed {
+                println!("{}", line.red().bold());
+            } else {
+                println!("{}", line);
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+/* Signal handling policy
+ * Like gdb's `handle`, every signal has three switches:
+ *   stop:  return to the prompt when the inferior receives it
+ *   print: say that it was received
+ *   pass:  deliver it to the inferior when it's resumed
+ */
+
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::{ Inferior, InferiorState };
+
+/// What to do when the inferior receives a signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignalPolicy {
+    pub stop: bool,
+    pub print: bool,
+    pub pass: bool,
+}
+
+impl SignalPolicy {
+    /// Apply a `handle` keyword. `stop` implies `print` and `noprint`
+    /// implies `nostop`, same as gdb.
+    pub fn apply(&mut self, action: &str) -> Result<(), failure::Error> {
+        match action {
+            "stop" => { self.stop = true; self.print = true; },
+            "nostop" => self.stop = false,
+            "print" => self.print = true,
+            "noprint" => { self.print = false; self.stop = false; },
+            "pass" | "noignore" => self.pass = true,
+            "nopass" | "ignore" => self.pass = false,
+            _ => return Err(failure::format_err!("Unrecognized signal action: \"{}\"", action)),
+        }
+        Ok(())
+    }
+}
+
+/// gdb's defaults: signals used for timers, job control and I/O go
+/// straight through, SIGINT and SIGTRAP belong to the debugger
+pub fn default_signal_policies() -> HashMap<Signal, SignalPolicy> {
+    Signal::iterator()
+        .map(|sig| {
+            let policy = match sig {
+                Signal::SIGALRM | Signal::SIGURG | Signal::SIGCHLD | Signal::SIGWINCH |
+                Signal::SIGIO | Signal::SIGVTALRM | Signal::SIGPROF | Signal::SIGPWR =>
+                    SignalPolicy { stop: false, print: false, pass: true },
+                Signal::SIGINT | Signal::SIGTRAP =>
+                    SignalPolicy { stop: true, print: true, pass: false },
+                _ => SignalPolicy { stop: true, print: true, pass: true },
+            };
+            (sig, policy)
+        })
+        .collect()
+}
+
+/// Words `handle` takes as actions
+pub static SIGNAL_ACTIONS: &[&str] = &[
+    "stop", "nostop", "print", "noprint", "pass", "noignore", "nopass", "ignore",
+];
+
+/// Signal from a name (`SIGSEGV`, `SEGV`) or number. Action words aren't
+/// signals, `handle SIGUSR1 stop` must not read `stop` as SIGSTOP.
+pub fn parse_signal(s: &str) -> Option<Signal> {
+    let s = s.trim();
+    if let Ok(num) = s.parse::<i32>() {
+        return Signal::from_c_int(num).ok();
+    }
+    if SIGNAL_ACTIONS.contains(&s.to_lowercase().as_str()) {
+        return None;
+    }
+    let name = s.to_uppercase();
+    if name.starts_with("SIG") {
+        Signal::from_str(&name).ok()
+    } else {
+        Signal::from_str(&format!("SIG{}", name)).ok()
+    }
+}
+
+/// Short description for `info signals` and stop reports
+pub fn describe_signal(sig: Signal) -> &'static str {
+    match sig {
+        Signal::SIGHUP => "Hangup",
+        Signal::SIGINT => "Interrupt",
+        Signal::SIGQUIT => "Quit",
+        Signal::SIGILL => "Illegal instruction",
+        Signal::SIGTRAP => "Trace/breakpoint trap",
+        Signal::SIGABRT => "Aborted",
+        Signal::SIGBUS => "Bus error",
+        Signal::SIGFPE => "Arithmetic exception",
+        Signal::SIGKILL => "Killed",
+        Signal::SIGUSR1 => "User defined signal 1",
+        Signal::SIGSEGV => "Segmentation fault",
+        Signal::SIGUSR2 => "User defined signal 2",
+        Signal::SIGPIPE => "Broken pipe",
+        Signal::SIGALRM => "Alarm clock",
+        Signal::SIGTERM => "Terminated",
+        Signal::SIGSTKFLT => "Stack fault",
+        Signal::SIGCHLD => "Child status changed",
+        Signal::SIGCONT => "Continued",
+        Signal::SIGSTOP => "Stopped (signal)",
+        Signal::SIGTSTP => "Stopped (user)",
+        Signal::SIGTTIN => "Stopped (tty input)",
+        Signal::SIGTTOU => "Stopped (tty output)",
+        Signal::SIGURG => "Urgent I/O condition",
+        Signal::SIGXCPU => "CPU time limit exceeded",
+        Signal::SIGXFSZ => "File size limit exceeded",
+        Signal::SIGVTALRM => "Virtual timer expired",
+        Signal::SIGPROF => "Profiling timer expired",
+        Signal::SIGWINCH => "Window size changed",
+        Signal::SIGIO => "I/O possible",
+        Signal::SIGPWR => "Power fail/restart",
+        Signal::SIGSYS => "Bad system call",
+    }
+}
+
+impl Inferior {
+    pub fn signal_policy(&self, sig: Signal) -> SignalPolicy {
+        self.signal_policies.get(&sig).cloned()
+            .unwrap_or(SignalPolicy { stop: true, print: true, pass: true })
+    }
+
+    /// `handle <signal...|all> <actions...>`
+    pub fn handle_signals(&mut self, sigs: &[Signal], actions: &[&str]) -> Result<(), failure::Error> {
+        if sigs.is_empty() {
+            return Err(failure::format_err!("Please provide a signal to handle"));
+        }
+
+        for &sig in sigs {
+            let mut policy = self.signal_policy(sig);
+            for action in actions {
+                policy.apply(action)?;
+            }
+            self.signal_policies.insert(sig, policy);
+        }
+        self.show_signals(sigs);
+        Ok(())
+    }
+
+    /// `info signals [signal]`
+    pub fn show_signals(&self, sigs: &[Signal]) {
+        println!("{:<14}{:<6}{:<7}{:<17}Description", "Signal", "Stop", "Print", "Pass to program");
+
+        let all: Vec<Signal> = Signal::iterator().collect();
+        let sigs = if sigs.is_empty() { &all[..] } else { sigs };
+        for &sig in sigs {
+            let policy = self.signal_policy(sig);
+            println!("{:<14}{:<6}{:<7}{:<17}{}",
+                     sig.as_ref(),
+                     yes_no(policy.stop),
+                     yes_no(policy.print),
+                     yes_no(policy.pass),
+                     describe_signal(sig));
+        }
+    }
+
+    /* `tid` stopped with `sig`. Reports and queues it by policy, returns
+     * true if the user should get the prompt back. */
+    pub(crate) fn handle_signal(&mut self, tid: Pid, sig: Signal) -> bool {
+        let policy = self.signal_policy(sig);
+
+        if policy.print {
+            if self.tids.len() > 1 {
+                println!("Thread {} ({}) received signal {}, {}.",
+                         self.thread_num(tid).unwrap_or(0), tid, sig.as_ref(), describe_signal(sig));
+            } else {
+                println!("Program received signal {}, {}.", sig.as_ref(), describe_signal(sig));
+            }
+        }
+        if policy.pass {
+            self.pending_signals.insert(tid, sig);
+        }
+        policy.stop
+    }
+
+    /* `tid` was killed by `sig` */
+    pub(crate) fn handle_signaled(&mut self, tid: Pid, sig: Signal, core: bool) {
+        if tid == self.pid {
+            println!("Program terminated with signal {}, {}.", sig.as_ref(), describe_signal(sig));
+            if core { println!("Process generated core dump!!!!!!"); }
+            self.process_exited();
+            self.tids.clear();
+            self.thread_states.clear();
+            self.pending_signals.clear();
+            self.state = InferiorState::Dead;
+        } else {
+            println!("[Thread {} killed by signal {}]", tid, sig.as_ref());
+            self.remove_thread(tid);
+        }
+    }
+}
+
+fn yes_no(val: bool) -> &'static str {
+    if val { "Yes" } else { "No" }
+}
@@ -0,0 +1,381 @@
+/* Shared library tracking
+ * The dynamic linker keeps a `struct r_debug` whose address it stores in
+ * the DT_DEBUG entry of the executable's dynamic section. `r_map` is the
+ * head of the link_map list of loaded objects and `r_brk` is a function
+ * it calls before and after changing that list. An internal breakpoint on
+ * `r_brk` tells us whenever a library comes or goes.
+ *
+ * DT_DEBUG is only filled in once ld.so has run, so after an exec we break
+ * at the program's entry point first and pick `r_debug` up from there.
+ */
+
+use procfs::MMapPath;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::maps::{ map_file_name, map_name };
+use super::{ Breakpoint, BreakpointType, Inferior };
+
+/* auxv entries */
+const AT_PHDR: u64 = 3;
+const AT_PHNUM: u64 = 5;
+const AT_ENTRY: u64 = 9;
+
+/* Program header types */
+const PT_DYNAMIC: u32 = 2;
+const PT_PHDR: u32 = 6;
+const PHDR_SIZE: usize = 56;
+
+/* Dynamic section tags */
+const DT_NULL: u64 = 0;
+const DT_DEBUG: u64 = 21;
+const MAX_DYNAMIC: usize = 512;
+
+/* r_debug.r_state */
+const RT_CONSISTENT: u64 = 0;
+
+/* Longest link_map chain we walk, in case it's corrupt */
+const MAX_LINK_MAP: usize = 4096;
+const MAX_PATH: usize = 4096;
+
+/// A loaded object as `info sharedlibrary` shows it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedLibrary {
+    /// File name, the key used for breakpoints and `modules`
+    pub name: String,
+    /// Path as the dynamic linker has it
+    pub path: String,
+    /// Lowest mapping of the object
+    pub base: usize,
+    /// End of its highest mapping
+    pub end: usize,
+    /// Whether symbols were read for it
+    pub syms: bool,
+}
+
+/// Key/value pairs of an auxiliary vector as found in /proc/pid/auxv
+pub fn parse_auxv(data: &[u8]) -> HashMap<u64, u64> {
+    let mut auxv = HashMap::new();
+    for entry in data.chunks_exact(16) {
+        let key = le_u64(&entry[..8]);
+        if key == 0 { break }
+        auxv.insert(key, le_u64(&entry[8..]));
+    }
+    auxv
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+impl Inferior {
+    /* Forget the old image's modules. The list goes back to being built
+     * from the maps until `init_solib_tracking` */
+    pub(crate) fn clear_solibs(&mut self) {
+        self.r_debug = None;
+        self.r_brk = None;
+        self.solib_entry = None;
+        self.solibs.clear();
+        self.modules.clear();
+    }
+
+    /* Start following the dynamic linker, after an exec or an attach. Goes
+     * after `reset_breakpoints`, which drops internal breakpoints. */
+    pub(crate) fn init_solib_tracking(&mut self) {
+        let auxv = match std::fs::read(format!("/proc/{}/auxv", self.pid)) {
+            Ok(data) => parse_auxv(&data),
+            Err(e) => {
+                println!("Unable to read auxv, shared libraries won't be tracked: {}", e);
+                return;
+            }
+        };
+
+        // Statically linked, the maps are all there is
+        let dynamic = match self.dynamic_section(&auxv) {
+            Some(dynamic) => dynamic,
+            None => return,
+        };
+
+        match self.find_r_debug(dynamic) {
+            Some(r_debug) => self.set_r_debug(r_debug),
+            None => {
+                // ld.so hasn't run yet, wait for it at the entry point
+                if let Some(&entry) = auxv.get(&AT_ENTRY) {
+                    let entry = entry as usize;
+                    match self.insert_solib_breakpoint(entry) {
+                        Ok(()) => self.solib_entry = Some(entry),
+                        Err(e) => println!("Unable to break at entry point {:#x}: {}", entry, e),
+                    }
+                }
+            },
+        }
+    }
+
+    /* Runtime address of the executable's dynamic section */
+    fn dynamic_section(&self, auxv: &HashMap<u64, u64>) -> Option<usize> {
+        let phdr = *auxv.get(&AT_PHDR)? as usize;
+        let phnum = *auxv.get(&AT_PHNUM)? as usize;
+        let phdrs = self.read_exact(phdr, phnum * PHDR_SIZE).ok()?;
+
+        let mut bias = None;
+        let mut dynamic = None;
+        for ph in phdrs.chunks(PHDR_SIZE) {
+            let typ = u32::from_le_bytes([ph[0], ph[1], ph[2], ph[3]]);
+            let vaddr = le_u64(&ph[16..]) as usize;
+            match typ {
+                PT_PHDR => bias = Some(phdr.wrapping_sub(vaddr)),
+                PT_DYNAMIC => dynamic = Some(vaddr),
+                _ => {},
+            }
+        }
+
+        // Without PT_PHDR the executable isn't position independent
+        Some(dynamic?.wrapping_add(bias.unwrap_or(0)))
+    }
+
+    /* Value of DT_DEBUG, zero until ld.so has set it */
+    fn find_r_debug(&self, dynamic: usize) -> Option<usize> {
+        for i in 0..MAX_DYNAMIC {
+            let entry = dynamic + i * 16;
+            let tag = self.read_u64(entry).ok()?;
+            match tag {
+                DT_NULL => return None,
+                DT_DEBUG => {
+                    let val = self.read_u64(entry + 8).ok()?;
+                    return if val == 0 { None } else { Some(val as usize) };
+                },
+                _ => {},
+            }
+        }
+        None
+    }
+
+    /* Internal breakpoint that never shows up in `info breakpoints` */
+    fn insert_solib_breakpoint(&mut self, addr: usize) -> Result<(), failure::Error> {
+        if self.breakpoints.contains_key(&addr) {
+            return Ok(());
+        }
+        self.breakpoints.insert(addr, Breakpoint {
+            id:        0,
+            offset:    addr,
+            enabled:   false,
+            typ:       BreakpointType::Freq,
+            orig_byte: None,
+            funcname:  Arc::new(String::new()),
+            funcoff:   0,
+            modname:   Arc::new(String::new()),
+            freq:      0,
+            callback:  None,
+            condition: None,
+            ignore:    0,
+            resolve:   false,
+        });
+        if let Err(e) = self.activate_bp(addr) {
+            self.breakpoints.remove(&addr);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn remove_solib_breakpoint(&mut self, addr: usize) {
+        match self.breakpoints.get(&addr) {
+            Some(bp) if bp.id == 0 && bp.typ == BreakpointType::Freq => {},
+            _ => return,
+        }
+        if let Err(e) = self.deactivate_bp(addr) {
+            println!("Unable to remove internal breakpoint at {:#x}: {}", addr, e);
+        }
+        self.breakpoints.remove(&addr);
+    }
+
+    fn set_r_debug(&mut self, r_debug: usize) {
+        self.r_debug = Some(r_debug);
+        match self.read_u64(r_debug + 16) {
+            Ok(r_brk) if r_brk != 0 => {
+                let r_brk = r_brk as usize;
+                match self.insert_solib_breakpoint(r_brk) {
+                    Ok(()) => self.r_brk = Some(r_brk),
+                    Err(e) => println!("Unable to break on the dynamic linker at {:#x}: {}", r_brk, e),
+                }
+            },
+            Ok(_) => {},
+            Err(e) => println!("Unable to read r_debug at {:#x}: {}", r_debug, e),
+        }
+        self.sync_link_map();
+    }
+
+    /* Called for every breakpoint hit before it's reported. True if it was
+     * one of ours, the breakpoint at `addr` may be gone afterwards. */
+    pub(crate) fn handle_solib_event(&mut self, addr: usize) -> bool {
+        if self.solib_entry == Some(addr) {
+            self.solib_entry = None;
+            self.remove_solib_breakpoint(addr);
+
+            let auxv = std::fs::read(format!("/proc/{}/auxv", self.pid))
+                .map(|data| parse_auxv(&data))
+                .unwrap_or_default();
+            let r_debug = self.dynamic_section(&auxv)
+                .and_then(|dynamic| self.find_r_debug(dynamic));
+            match r_debug {
+                Some(r_debug) => self.set_r_debug(r_debug),
+                None => println!("No r_debug from the dynamic linker, shared libraries won't be tracked"),
+            }
+            return true;
+        }
+
+        if self.r_brk == Some(addr) {
+            let state = self.r_debug.and_then(|r_debug| self.read_u64(r_debug + 24).ok());
+            // r_brk is called before and after each change, only the list
+            // after it is complete
+            if state == Some(RT_CONSISTENT) {
+                self.sync_link_map();
+            }
+            return true;
+        }
+        false
+    }
+
+    /* Walk link_map and bring `modules` in line with it */
+    fn sync_link_map(&mut self) {
+        let r_debug = match self.r_debug {
+            Some(r_debug) => r_debug,
+            None => return,
+        };
+        self.refresh_maps();
+
+        let mut libs = Vec::new();
+        let mut lm = self.read_u64(r_debug + 8).unwrap_or(0) as usize;
+        for _ in 0..MAX_LINK_MAP {
+            if lm == 0 { break }
+            let name = self.read_u64(lm + 8).unwrap_or(0) as usize;
+            let ld = self.read_u64(lm + 16).unwrap_or(0) as usize;
+            let next = self.read_u64(lm + 24).unwrap_or(0) as usize;
+
+            // The dynamic section is always mapped, unlike l_addr which is
+            // zero for the executable and the vdso
+            if let Some(lib) = self.shared_library(ld, name) {
+                libs.push(lib);
+            }
+            lm = next;
+        }
+
+        let loaded: Vec<usize> = libs.iter()
+            .map(|lib| lib.base)
+            .filter(|base| !self.modules.iter().any(|(_, b)| b == base))
+            .collect();
+        let unloaded: Vec<usize> = self.modules.iter()
+            .map(|(_, base)| *base)
+            .filter(|base| !libs.iter().any(|lib| lib.base == *base))
+            .collect();
+
+        self.solibs = libs;
+        for base in unloaded {
+            self.unregister_module(base);
+        }
+        for base in loaded {
+            self.register_module(base);
+        }
+
+        // Symbols are read as modules are registered
+        let syms: Vec<bool> = self.solibs.iter()
+            .map(|lib| self.module_symbols(lib.base).is_some_and(|table| !table.is_empty()))
+            .collect();
+        for (lib, syms) in self.solibs.iter_mut().zip(syms) {
+            lib.syms = syms;
+        }
+    }
+
+    /* Describe the object whose dynamic section is at `ld` */
+    fn shared_library(&self, ld: usize, name: usize) -> Option<SharedLibrary> {
+        let map = self.mem.find(ld)?;
+        let (base, end) = match &map.pathname {
+            MMapPath::Path(_) => {
+                let same = self.mem.iter().filter(|m| m.pathname == map.pathname);
+                let base = same.clone().map(|m| m.address.0).min()?;
+                let end = same.map(|m| m.address.1).max()?;
+                (base as usize, end as usize)
+            },
+            _ => (map.address.0 as usize, map.address.1 as usize),
+        };
+
+        let mut path = if name != 0 {
+            self.read_string(name, MAX_PATH).unwrap_or_default()
+        } else {
+            String::new()
+        };
+        if path.is_empty() {
+            path = map_name(map);
+        }
+
+        let name = self.filename_from_module_base(base);
+        Some(SharedLibrary { name, path, base, end, syms: false })
+    }
+
+    /// Objects the dynamic linker has loaded, the executable first
+    pub fn shared_libraries(&self) -> &[SharedLibrary] { &self.solibs }
+
+    /// `info sharedlibrary`
+    pub fn show_shared_libraries(&self) {
+        if self.solibs.is_empty() {
+            println!("No shared libraries loaded at this time.");
+            return;
+        }
+        println!("{:<20}{:<20}{:<12}Shared Object Library", "From", "To", "Syms Read");
+        for lib in &self.solibs {
+            println!("{:<#20x}{:<#20x}{:<12}{}",
+                     lib.base, lib.end, if lib.syms { "Yes" } else { "No" }, lib.path);
+        }
+    }
+
+    /// File name of the object mapped at `base`, the name used for
+    /// `modules` and breakpoint locations
+    pub(crate) fn filename_from_module_base(&self, base: usize) -> String {
+        match self.mem.find(base) {
+            Some(map) => map_file_name(map).unwrap_or_else(|| map_name(map)),
+            None => format!("{:#x}", base),
+        }
+    }
+
+    /// Add the module loaded at `base` in the target process to our module
+    /// list
+    pub fn register_module(&mut self, base: usize) {
+        let filename = self.filename_from_module_base(base);
+        if self.verbose {
+            println!("Loaded {} at {:#x}", filename, base);
+        }
+        self.modules.insert((filename.clone(), base));
+        self.load_module_symbols(base);
+
+        // Cloned so the callbacks can have the inferior mutably
+        if let Some(callbacks) = self.module_load_callbacks.clone() {
+            for callback in callbacks.iter() {
+                callback(self, &filename, base);
+            }
+        }
+
+        // After the callbacks, which may have registered some
+        self.arm_pending_breakpoints(&filename, base);
+    }
+
+    /// Remove the module loaded at `base` in the target process from our
+    /// module list. Its breakpoints wait for it to be loaded again.
+    pub(crate) fn unregister_module(&mut self, base: usize) {
+        let module = match self.modules.iter().find(|(_, b)| *b == base) {
+            Some(module) => module.clone(),
+            None => {
+                // Our list is out of sync with the dynamic linker's
+                println!("Unexpected unload of module at {:#x}", base);
+                return;
+            }
+        };
+        if self.verbose {
+            println!("Unloaded {} from {:#x}", module.0, base);
+        }
+
+        self.unarm_module_breakpoints(&module.0, base);
+        self.modules.remove(&module);
+    }
+}
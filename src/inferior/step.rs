@@ -0,0 +1,239 @@
+/* Instruction stepping
+ * stepi, nexti, finish, until and jump. Anything that has to run more than one
+ * instruction does it with an internal breakpoint (id 0) that is removed
+ * again once the command is done.
+ */
+
+use nix::sys::{ptrace, signal};
+use nix::sys::wait::*;
+
+use std::sync::Arc;
+
+use super::{ Breakpoint, BreakpointAction, BreakpointType, Inferior, InferiorState };
+
+impl Inferior {
+    /* Execute exactly one instruction of the current thread, lifting any
+     * breakpoint at the PC out of the way first. True if a signal arrived
+     * that should stop the program */
+    pub(crate) fn step_instruction(&mut self) -> Result<bool, failure::Error> {
+        let pc = ptrace::getregs(self.tid)?.rip as usize;
+        let sw = match self.breakpoints.get(&pc) {
+            Some(bp) => bp.enabled,
+            None => false,
+        };
+        let hw = self.hw_exec_slot(pc);
+
+        let tid = i32::from(self.tid) as u32;
+        self.single_step.insert(tid, pc);
+        if sw { self.deactivate_bp(pc)?; }
+        if let Some(slot) = hw { self.mask_hw_slot(self.tid, slot)?; }
+        let sig = self.pending_signals.remove(&self.tid);
+        ptrace::step(self.tid, sig)?;
+        let status = loop {
+            match waitpid(self.tid, Some(WaitPidFlag::__WALL))? {
+                // A clone or fork on the way, the step isn't over yet
+                WaitStatus::PtraceEvent(tid, _, event) if self.handle_event_in_place(tid, event) => {
+                    ptrace::step(tid, None)?;
+                },
+                status => break status,
+            }
+        };
+        self.single_step.remove(&tid);
+
+        let mut stop = false;
+        match status {
+            WaitStatus::Exited(_pid, code) => {
+                println!("Process exited. Pid: {} Code: {}", _pid, code);
+                self.state = InferiorState::Dead;
+            },
+            WaitStatus::Signaled(tid, sig, core) => self.handle_signaled(tid, sig, core),
+            // An exec replaced the image, there's nothing to put back
+            WaitStatus::PtraceEvent(..) => {},
+            status => {
+                if sw { self.activate_bp(pc)?; }
+                if hw.is_some() { self.program_thread_debugregs(self.tid)?; }
+                if let WaitStatus::Stopped(tid, sig) = status {
+                    if sig != signal::SIGTRAP {
+                        stop = self.handle_signal(tid, sig);
+                    }
+                }
+            },
+        }
+        Ok(stop)
+    }
+
+    /// `stepi`: Single step `count` instructions
+    pub fn stepi(&mut self, count: usize) -> Result<(), failure::Error> {
+        self.check_stopped()?;
+        self.save_stop_registers();
+
+        for _ in 0..count {
+            let stop = self.step_instruction()?;
+            if self.state == InferiorState::Dead { return Ok(()) }
+            if stop || self.stepped_into_stop()? { break }
+        }
+        self.refresh_maps();
+        self.print_pc();
+        Ok(())
+    }
+
+    /// `nexti`: Like `stepi` but runs over calls
+    pub fn nexti(&mut self, count: usize) -> Result<(), failure::Error> {
+        self.check_stopped()?;
+        self.save_stop_registers();
+
+        for _ in 0..count {
+            let regs = ptrace::getregs(self.tid)?;
+            let insn = self.disassemble(regs.rip as usize, 1);
+
+            match insn.first() {
+                Some(insn) if insn.is_call() => {
+                    // Run until the call returns to the next instruction.
+                    // Recursion can hit the same address deeper in the stack,
+                    // so only accept it once RSP is back where it was.
+                    let next = insn.addr + insn.len();
+                    loop {
+                        if !self.run_until(next)? { return Ok(()) }
+                        if ptrace::getregs(self.tid)?.rsp >= regs.rsp { break }
+                    }
+                },
+                _ => {
+                    let stop = self.step_instruction()?;
+                    if self.state == InferiorState::Dead { return Ok(()) }
+                    if stop || self.stepped_into_stop()? { break }
+                },
+            }
+        }
+        self.refresh_maps();
+        self.print_pc();
+        Ok(())
+    }
+
+    /// `finish`: Run until the current function returns and show RAX
+    pub fn finish(&mut self) -> Result<(), failure::Error> {
+        self.check_stopped()?;
+        self.save_stop_registers();
+
+        let regs = ptrace::getregs(self.tid)?;
+        let ret = self.return_address()?;
+        println!("Run till exit from {:#x}", regs.rip);
+
+        loop {
+            if !self.run_until(ret)? { return Ok(()) }
+            // Returned out of this frame and not just a recursive call
+            if ptrace::getregs(self.tid)?.rsp > regs.rsp { break }
+        }
+
+        let rax = ptrace::getregs(self.tid)?.rax;
+        self.print_pc();
+        println!("Value returned: rax = {:#x} ({})", rax, rax as i64);
+        Ok(())
+    }
+
+    /// `until`: Run until `addr` is reached
+    pub fn until(&mut self, addr: usize) -> Result<(), failure::Error> {
+        self.check_stopped()?;
+        self.save_stop_registers();
+
+        if self.run_until(addr)? {
+            self.print_pc();
+        }
+        Ok(())
+    }
+
+    /// `jump`: Resume execution at `addr`
+    pub fn jump(&mut self, addr: usize) -> Result<(), failure::Error> {
+        self.check_stopped()?;
+        self.check_mapped(addr, 1)?;
+        self.save_stop_registers();
+
+        let mut regs = ptrace::getregs(self.tid)?;
+        regs.rip = addr as u64;
+        ptrace::setregs(self.tid, regs)?;
+
+        println!("Continuing at {:#x}.", addr);
+        self.cont();
+        self.wait();
+        Ok(())
+    }
+
+    /* Continue with an internal breakpoint at `addr`. Returns true if the
+     * inferior stopped at `addr`, false if something else stopped it first */
+    fn run_until(&mut self, addr: usize) -> Result<bool, failure::Error> {
+        let internal = !self.breakpoints.contains_key(&addr);
+        if internal {
+            self.breakpoints.insert(addr, Breakpoint {
+                id:        0,
+                offset:    addr,
+                enabled:   false,
+                typ:       BreakpointType::Single,
+                orig_byte: None,
+                funcname:  Arc::new(String::new()),
+                funcoff:   0,
+                modname:   Arc::new(String::new()),
+                freq:      0,
+                callback:  None,
+                condition: None,
+                ignore:    0,
+                resolve:   false,
+            });
+            self.activate_bp(addr)?;
+        }
+
+        self.cont();
+        self.wait();
+
+        // Stopped somewhere else, don't leave our breakpoint behind
+        if internal && self.breakpoints.get(&addr).map(|bp| bp.id) == Some(0) {
+            if self.state != InferiorState::Dead {
+                self.deactivate_bp(addr)?;
+            }
+            self.breakpoints.remove(&addr);
+        }
+
+        if self.state != InferiorState::Stopped {
+            return Ok(false);
+        }
+        Ok(ptrace::getregs(self.tid)?.rip as usize == addr)
+    }
+
+    /* Return address of the innermost frame, from the unwinder */
+    fn return_address(&self) -> Result<usize, failure::Error> {
+        self.backtrace(2)?.get(1)
+            .map(|frame| frame.pc)
+            .ok_or_else(|| failure::format_err!("\"finish\" not meaningful in the outermost frame."))
+    }
+
+    /* After a step, stop early if we landed on a breakpoint or a signal */
+    fn stepped_into_stop(&mut self) -> Result<bool, failure::Error> {
+        let pc = ptrace::getregs(self.tid)?.rip as usize;
+        if let Some(bp) = self.breakpoints.get(&pc) {
+            if bp.enabled && bp.id != 0 {
+                println!("Breakpoint {} at {:#x}", bp.id, pc);
+                return Ok(true);
+            }
+        }
+        Ok(self.handle_hw_breakpoint(self.tid) == BreakpointAction::Stop)
+    }
+
+    pub(crate) fn check_stopped(&self) -> Result<(), failure::Error> {
+        if self.state != InferiorState::Stopped {
+            return Err(failure::format_err!("The program is not being run."));
+        }
+        Ok(())
+    }
+
+    /* Show where we are after a step */
+    pub fn print_pc(&self) {
+        let pc = match ptrace::getregs(self.tid) {
+            Ok(regs) => regs.rip as usize,
+            Err(_) => return,
+        };
+
+        let label = self.addr_label(pc);
+        match self.disassemble(pc, 1).first() {
+            Some(insn) => println!("{:#x}{}:\t{}", pc, label, insn),
+            None => println!("{:#x}{}", pc, label),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+/* Symbols
+ * .symtab and .dynsym of the executable and of every shared library, kept
+ * per file with offsets from the file's lowest loaded page, which is the
+ * module base we keep in `modules`. Tables are loaded when a module is
+ * registered and cached by path. Names are kept mangled and demangled
+ * for display.
+ */
+
+use elfkit::{ Elf, SectionContent };
+use elfkit::types::{ SegmentType, SymbolType };
+use regex::Regex;
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::cfi::CallFrameTable;
+use super::demangle::{ demangle, symbol_matches, DemangleMode };
+use super::lines::{ line_address, parse_line_programs };
+use super::unwind::frame_table;
+use super::Inferior;
+
+/// What a symbol names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymKind {
+    Func,
+    Object,
+    Other,
+}
+
+impl SymKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SymKind::Func => "func",
+            SymKind::Object => "object",
+            SymKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// From the module base
+    pub offset: usize,
+    pub size: usize,
+    pub kind: SymKind,
+}
+
+/// Symbols of one file, sorted by offset
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new(mut symbols: Vec<Symbol>) -> SymbolTable {
+        // Functions first among symbols at the same address
+        symbols.sort_by(|a, b| (a.offset, a.kind != SymKind::Func, &a.name)
+                                .cmp(&(b.offset, b.kind != SymKind::Func, &b.name)));
+        symbols.dedup_by(|a, b| a.offset == b.offset && a.name == b.name);
+        SymbolTable { symbols }
+    }
+
+    /// .symtab and .dynsym of a loaded ELF
+    pub fn from_elf(elf: &Elf) -> SymbolTable {
+        let load = load_address(elf);
+        let mut symbols = Vec::new();
+        for section in &elf.sections {
+            let syms = match &section.content {
+                SectionContent::Symbols(syms) => syms,
+                _ => continue,
+            };
+            for sym in syms {
+                // Undefined symbols have no address
+                if sym.value == 0 || sym.value < load || sym.name.is_empty() { continue }
+                let kind = match sym.stype {
+                    SymbolType::FUNC => SymKind::Func,
+                    SymbolType::OBJECT => SymKind::Object,
+                    SymbolType::NOTYPE => SymKind::Other,
+                    _ => continue,
+                };
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(&sym.name).into_owned(),
+                    offset: (sym.value - load) as usize,
+                    size: sym.size as usize,
+                    kind,
+                });
+            }
+        }
+        SymbolTable::new(symbols)
+    }
+
+    pub fn len(&self) -> usize { self.symbols.len() }
+    pub fn is_empty(&self) -> bool { self.symbols.is_empty() }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Symbol> {
+        self.symbols.iter()
+    }
+
+    /// Symbol called `name`, or failing that one whose demangled path is
+    /// `name`
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|sym| sym.name == name)
+            .or_else(|| self.symbols.iter().find(|sym| symbol_matches(&sym.name, name)))
+    }
+
+    /// Symbol covering `offset` and how far into it `offset` is. Symbols
+    /// without a size cover everything up to the next one.
+    pub fn lookup(&self, offset: usize) -> Option<(&Symbol, usize)> {
+        // Past the last symbol starting at or before `offset`
+        let end = self.symbols
+            .binary_search_by(|sym| if sym.offset <= offset { Ordering::Less } else { Ordering::Greater })
+            .unwrap_err();
+        if end == 0 { return None }
+
+        let nearest = self.symbols[end - 1].offset;
+        let group = self.symbols[..end].iter().rposition(|sym| sym.offset != nearest).map_or(0, |i| i + 1);
+        let covers = |sym: &&Symbol| sym.size == 0 || offset - sym.offset < sym.size;
+
+        // Functions come first at the same address. Failing those, a
+        // bigger symbol further back can still hold `offset`.
+        self.symbols[group..end].iter().find(covers)
+            .or_else(|| self.symbols[..group].iter().rev().take(16).find(|sym| sym.size > 0 && covers(sym)))
+            .map(|sym| (sym, offset - sym.offset))
+    }
+}
+
+/// Parse an ELF file with all of its sections
+pub fn load_elf(path: &Path) -> Result<Elf, failure::Error> {
+    let mut file = File::open(path)
+        .map_err(|e| failure::format_err!("{}: {}", path.display(), e))?;
+    let mut elf = Elf::from_reader(&mut file)
+        .map_err(|e| failure::format_err!("{}: Unable to parse ELF: {:?}", path.display(), e))?;
+    elf.load_all(&mut file)
+        .map_err(|e| failure::format_err!("{}: Unable to load sections: {:?}", path.display(), e))?;
+    Ok(elf)
+}
+
+/* Virtual address the module base corresponds to */
+pub(crate) fn load_address(elf: &Elf) -> u64 {
+    let lowest = elf.segments.iter()
+        .filter(|seg| matches!(seg.phtype, SegmentType::LOAD))
+        .map(|seg| seg.vaddr)
+        .min()
+        .unwrap_or(0);
+    lowest & !0xfff
+}
+
+/* Contents of the section called `name` */
+fn section_data<'a>(elf: &'a Elf, name: &str) -> Option<&'a [u8]> {
+    elf.sections.iter()
+        .find(|section| section.name == name.as_bytes())
+        .and_then(|section| match &section.content {
+            SectionContent::Raw(data) => Some(&data[..]),
+            _ => None,
+        })
+}
+
+/// Offset of the code for `file:line` from the base of the module in
+/// `path`, from its DWARF line table
+pub fn line_offset(path: &Path, file: &str, line: u32) -> Result<usize, failure::Error> {
+    let elf = load_elf(path)?;
+    let debug_line = section_data(&elf, ".debug_line")
+        .ok_or_else(|| failure::format_err!("No line information in {}", path.display()))?;
+    let rows = parse_line_programs(debug_line,
+                                   section_data(&elf, ".debug_line_str").unwrap_or(&[]),
+                                   section_data(&elf, ".debug_str").unwrap_or(&[]));
+    let addr = line_address(&rows, file, line)
+        .ok_or_else(|| failure::format_err!("No line {} in file \"{}\"", line, file))?;
+    Ok((addr - load_address(&elf)) as usize)
+}
+
+impl Inferior {
+    pub fn set_demangle_mode(&mut self, mode: DemangleMode) { self.demangle = mode; }
+
+    /// A symbol name as `set demangle` says to show it
+    pub fn display_symbol(&self, name: &str) -> String {
+        demangle(name, self.demangle)
+    }
+
+    /* Symbols and CFI of the file mapped at `base`, the executable's come
+     * from `parser` */
+    pub(crate) fn load_module_symbols(&mut self, base: usize) {
+        let path = match self.module_path(base) {
+            Some(path) => path,
+            None => return,
+        };
+        if self.symtabs.contains_key(&path) { return }
+
+        let (table, frames) = if path == Path::new(&self.location) {
+            (SymbolTable::from_elf(&self.parser), frame_table(&self.parser))
+        } else {
+            match load_elf(&path) {
+                Ok(elf) => (SymbolTable::from_elf(&elf), frame_table(&elf)),
+                Err(e) => {
+                    println!("Unable to read symbols: {}", e);
+                    (SymbolTable::default(), CallFrameTable::default())
+                }
+            }
+        };
+        if self.verbose {
+            println!("Read {} symbols and {} FDEs from {}", table.len(), frames.len(), path.display());
+        }
+        self.symtabs.insert(path.clone(), Arc::new(table));
+        self.frame_tables.insert(path, Arc::new(frames));
+    }
+
+    /// Symbols of the module loaded at `base`
+    pub fn module_symbols(&self, base: usize) -> Option<Arc<SymbolTable>> {
+        self.symtabs.get(&self.module_path(base)?).cloned()
+    }
+
+    /// Runtime address of a symbol, the executable's first
+    pub fn find_symbol(&self, name: &str) -> Option<usize> {
+        self.modules_by_priority().into_iter()
+            .filter_map(|(_, base)| {
+                let sym = self.module_symbols(base)?.find(name)?.offset;
+                Some(base + sym)
+            })
+            .next()
+    }
+
+    /// Offset of `name` in the module loaded at `base`
+    pub fn module_symbol_offset(&self, base: usize, name: &str) -> Result<usize, failure::Error> {
+        let table = self.module_symbols(base)
+            .ok_or_else(|| failure::format_err!("No symbols for module at {:#x}", base))?;
+        table.find(name)
+            .map(|sym| sym.offset)
+            .ok_or_else(|| failure::format_err!("No symbol \"{}\" in {}", name, self.filename_from_module_base(base)))
+    }
+
+    /// Symbol, as named in the file, and offset into it for an address
+    pub fn symbol_at(&self, addr: usize) -> Option<(String, usize)> {
+        let (_, base) = self.module_from_addr(addr)?;
+        let table = self.module_symbols(base)?;
+        let (sym, off) = table.lookup(addr - base)?;
+        Some((sym.name.clone(), off))
+    }
+
+    /// ` <symbol+off>`, or ` <module+off>` without symbols, to follow an
+    /// address. Empty outside of modules.
+    pub fn addr_label(&self, addr: usize) -> String {
+        if let Some((name, off)) = self.symbol_at(addr) {
+            let name = self.display_symbol(&name);
+            return if off == 0 { format!(" <{}>", name) } else { format!(" <{}+{:#x}>", name, off) };
+        }
+        match self.module_from_addr(addr) {
+            Some((name, base)) => format!(" <{}+{:#x}>", name, addr - base),
+            None => String::new(),
+        }
+    }
+
+    /// `symbols [regex]`: Symbols of every loaded module, or of the
+    /// executable before it runs
+    pub fn show_symbols(&self, pattern: &str) -> Result<(), failure::Error> {
+        let re = if pattern.is_empty() { None } else { Some(Regex::new(pattern)?) };
+
+        let tables: Vec<(String, usize, Arc<SymbolTable>)> = if self.modules.is_empty() {
+            let name = Path::new(&self.location).file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            vec![(name, 0, Arc::new(SymbolTable::from_elf(&self.parser)))]
+        } else {
+            self.modules_by_priority().into_iter()
+                .filter_map(|(name, base)| self.module_symbols(base).map(|table| (name, base, table)))
+                .collect()
+        };
+
+        let mut shown = 0;
+        for (module, base, table) in tables {
+            for sym in table.iter() {
+                let name = self.display_symbol(&sym.name);
+                if let Some(re) = &re {
+                    if !re.is_match(&name) && !re.is_match(&sym.name) { continue }
+                }
+                println!("{:<#20x}{:<8}{:<8}{}!{}", base + sym.offset, sym.kind.name(), sym.size, module, name);
+                shown += 1;
+            }
+        }
+        if shown == 0 {
+            println!("No symbols found.");
+        }
+        Ok(())
+    }
+}
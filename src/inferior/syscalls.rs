@@ -0,0 +1,417 @@
+/* System call tracing
+ * With PTRACE_O_TRACESYSGOOD and PTRACE_SYSCALL every system call stops
+ * the thread twice, on entry and on exit. PTRACE_GET_SYSCALL_INFO says
+ * which one it is; on older kernels an entry stop is the one with RAX set
+ * to -ENOSYS. The call and its arguments are kept in `syscall_entry`
+ * until the exit.
+ *   catch syscall [name|number...]: stop on entry and exit
+ *   trace syscalls on|off:          log each call like strace, never stop
+ */
+
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+
+use super::{ ffi, Inferior };
+
+/// How to show a system call argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arg {
+    Int,
+    UInt,
+    Hex,
+    Fd,
+    /// NUL terminated string
+    Str,
+    /// Buffer whose length is the next argument
+    Buf,
+    OpenFlags,
+    Mode,
+    Prot,
+    MapFlags,
+    Sig,
+}
+
+use self::Arg::*;
+
+/// x86_64 system calls we know the arguments of
+static SYSCALLS: &[(u64, &str, &[Arg])] = &[
+    (0, "read", &[Fd, Hex, UInt]),
+    (1, "write", &[Fd, Buf, UInt]),
+    (2, "open", &[Str, OpenFlags, Mode]),
+    (3, "close", &[Fd]),
+    (4, "stat", &[Str, Hex]),
+    (5, "fstat", &[Fd, Hex]),
+    (6, "lstat", &[Str, Hex]),
+    (7, "poll", &[Hex, UInt, Int]),
+    (8, "lseek", &[Fd, Int, Int]),
+    (9, "mmap", &[Hex, UInt, Prot, MapFlags, Fd, Hex]),
+    (10, "mprotect", &[Hex, UInt, Prot]),
+    (11, "munmap", &[Hex, UInt]),
+    (12, "brk", &[Hex]),
+    (13, "rt_sigaction", &[Sig, Hex, Hex, UInt]),
+    (14, "rt_sigprocmask", &[Int, Hex, Hex, UInt]),
+    (15, "rt_sigreturn", &[]),
+    (16, "ioctl", &[Fd, Hex, Hex]),
+    (17, "pread64", &[Fd, Hex, UInt, Int]),
+    (18, "pwrite64", &[Fd, Buf, UInt, Int]),
+    (19, "readv", &[Fd, Hex, Int]),
+    (20, "writev", &[Fd, Hex, Int]),
+    (21, "access", &[Str, Int]),
+    (22, "pipe", &[Hex]),
+    (23, "select", &[Int, Hex, Hex, Hex, Hex]),
+    (24, "sched_yield", &[]),
+    (25, "mremap", &[Hex, UInt, UInt, Hex, Hex]),
+    (28, "madvise", &[Hex, UInt, Int]),
+    (32, "dup", &[Fd]),
+    (33, "dup2", &[Fd, Fd]),
+    (35, "nanosleep", &[Hex, Hex]),
+    (39, "getpid", &[]),
+    (41, "socket", &[Int, Int, Int]),
+    (42, "connect", &[Fd, Hex, Int]),
+    (43, "accept", &[Fd, Hex, Hex]),
+    (44, "sendto", &[Fd, Buf, UInt, Hex, Hex, Int]),
+    (45, "recvfrom", &[Fd, Hex, UInt, Hex, Hex, Hex]),
+    (49, "bind", &[Fd, Hex, Int]),
+    (50, "listen", &[Fd, Int]),
+    (56, "clone", &[Hex, Hex, Hex, Hex, Hex]),
+    (57, "fork", &[]),
+    (58, "vfork", &[]),
+    (59, "execve", &[Str, Hex, Hex]),
+    (60, "exit", &[Int]),
+    (61, "wait4", &[Int, Hex, Hex, Hex]),
+    (62, "kill", &[Int, Sig]),
+    (63, "uname", &[Hex]),
+    (72, "fcntl", &[Fd, Int, Hex]),
+    (78, "getdents", &[Fd, Hex, UInt]),
+    (79, "getcwd", &[Hex, UInt]),
+    (80, "chdir", &[Str]),
+    (82, "rename", &[Str, Str]),
+    (83, "mkdir", &[Str, Mode]),
+    (84, "rmdir", &[Str]),
+    (87, "unlink", &[Str]),
+    (89, "readlink", &[Str, Hex, UInt]),
+    (90, "chmod", &[Str, Mode]),
+    (96, "gettimeofday", &[Hex, Hex]),
+    (97, "getrlimit", &[Int, Hex]),
+    (102, "getuid", &[]),
+    (104, "getgid", &[]),
+    (107, "geteuid", &[]),
+    (108, "getegid", &[]),
+    (110, "getppid", &[]),
+    (158, "arch_prctl", &[Hex, Hex]),
+    (186, "gettid", &[]),
+    (200, "tkill", &[Int, Sig]),
+    (202, "futex", &[Hex, Int, Int, Hex, Hex, Int]),
+    (217, "getdents64", &[Fd, Hex, UInt]),
+    (218, "set_tid_address", &[Hex]),
+    (228, "clock_gettime", &[Int, Hex]),
+    (231, "exit_group", &[Int]),
+    (232, "epoll_wait", &[Fd, Hex, Int, Int]),
+    (234, "tgkill", &[Int, Int, Sig]),
+    (257, "openat", &[Fd, Str, OpenFlags, Mode]),
+    (262, "newfstatat", &[Fd, Str, Hex, Hex]),
+    (273, "set_robust_list", &[Hex, UInt]),
+    (293, "pipe2", &[Hex, OpenFlags]),
+    (302, "prlimit64", &[Int, Int, Hex, Hex]),
+    (318, "getrandom", &[Hex, UInt, Hex]),
+    (332, "statx", &[Fd, Str, Hex, Hex, Hex]),
+    (334, "rseq", &[Hex, UInt, Int, Hex]),
+    (435, "clone3", &[Hex, UInt]),
+];
+
+/// Longest string or buffer shown in a trace line
+const MAX_STR: usize = 32;
+
+/// AT_FDCWD as a directory fd
+const AT_FDCWD: i64 = -100;
+
+pub fn syscall_name(nr: u64) -> Option<&'static str> {
+    SYSCALLS.iter().find(|(n, _, _)| *n == nr).map(|(_, name, _)| *name)
+}
+
+pub fn syscall_number(name: &str) -> Option<u64> {
+    SYSCALLS.iter().find(|(_, n, _)| *n == name).map(|(nr, _, _)| *nr)
+}
+
+/// Syscall from a name or number, as given to `catch syscall`
+pub fn parse_syscall(s: &str) -> Option<u64> {
+    match s.parse::<u64>() {
+        Ok(nr) => Some(nr),
+        Err(_) => syscall_number(s),
+    }
+}
+
+fn display_name(nr: u64) -> String {
+    match syscall_name(nr) {
+        Some(name) => name.to_string(),
+        None => format!("syscall_{}", nr),
+    }
+}
+
+/// A `catch syscall` catchpoint, numbered along with breakpoints
+#[derive(Clone, Debug)]
+pub struct Catchpoint {
+    pub id: usize,
+    /// Syscall numbers to stop on, empty for all of them
+    pub syscalls: Vec<u64>,
+    pub enabled: bool,
+    pub freq: u64,
+}
+
+impl Catchpoint {
+    pub fn matches(&self, nr: u64) -> bool {
+        self.enabled && (self.syscalls.is_empty() || self.syscalls.contains(&nr))
+    }
+
+    /// "syscall 'write' [1] 'read' [0]" or "any syscall"
+    pub fn describe(&self) -> String {
+        if self.syscalls.is_empty() {
+            return String::from("any syscall");
+        }
+        let names: Vec<String> = self.syscalls.iter()
+            .map(|&nr| format!("'{}' [{}]", display_name(nr), nr))
+            .collect();
+        let plural = if self.syscalls.len() > 1 { "s" } else { "" };
+        format!("syscall{} {}", plural, names.join(" "))
+    }
+}
+
+impl Inferior {
+    /// `catch syscall [name|number...]`
+    pub fn catch_syscalls(&mut self, syscalls: Vec<u64>) -> usize {
+        let id = self.alloc_bp_id();
+        let catch = Catchpoint { id, syscalls, enabled: true, freq: 0 };
+        println!("Catchpoint {} ({})", id, catch.describe());
+        self.catchpoints.push(catch);
+        id
+    }
+
+    /// All `catch syscall` catchpoints
+    pub fn catchpoints(&self) -> &[Catchpoint] {
+        &self.catchpoints
+    }
+
+    pub(crate) fn catchpoint_index(&self, id: usize) -> Option<usize> {
+        self.catchpoints.iter().position(|c| c.id == id)
+    }
+
+    /// `trace syscalls on|off`
+    pub fn set_trace_syscalls(&mut self, val: bool) {
+        self.trace_syscalls = val;
+        if !self.syscall_stops() {
+            self.syscall_entry.clear();
+        }
+    }
+
+    /* Resume with PTRACE_SYSCALL while anything wants to see syscalls */
+    fn syscall_stops(&self) -> bool {
+        self.trace_syscalls || self.catchpoints.iter().any(|c| c.enabled)
+    }
+
+    /* Restart a stopped thread, the way every resume should go */
+    pub(crate) fn resume_thread(&self, tid: Pid, sig: Option<Signal>) -> nix::Result<()> {
+        if self.syscall_stops() {
+            ffi::syscall(tid, sig)
+        } else {
+            ptrace::cont(tid, sig)
+        }
+    }
+
+    /* Syscall entry or exit stop of `tid`. Returns true if a catchpoint
+     * fired and `report` asked for it to stop. */
+    pub(crate) fn handle_syscall(&mut self, tid: Pid, report: bool) -> bool {
+        let regs = match ptrace::getregs(tid) {
+            Ok(regs) => regs,
+            Err(_) => return false,
+        };
+
+        let entering = match ffi::syscall_info_op(tid) {
+            Ok(ffi::PTRACE_SYSCALL_INFO_ENTRY) => true,
+            Ok(ffi::PTRACE_SYSCALL_INFO_EXIT) => false,
+            _ => regs.rax as i64 == -(Errno::ENOSYS as i64),
+        };
+
+        let nr = if entering {
+            let nr = regs.orig_rax;
+            let args = [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9];
+            let args = self.format_args(tid, nr, &args);
+            self.syscall_entry.insert(tid, (nr, args));
+            nr
+        } else {
+            // Tracing may have started while the thread was already inside
+            let (nr, args) = self.syscall_entry.remove(&tid)
+                .unwrap_or_else(|| (regs.orig_rax, String::from("?")));
+            if self.trace_syscalls {
+                println!("[{}] {}({}) = {}", tid, display_name(nr), args, format_return(nr, regs.rax as i64));
+            }
+            nr
+        };
+
+        if !report { return false }
+        let catch = match self.catchpoints.iter_mut().find(|c| c.matches(nr)) {
+            Some(catch) => catch,
+            None => return false,
+        };
+        catch.freq += 1;
+
+        let what = if entering { "call to" } else { "returned from" };
+        println!("Catchpoint {} ({} syscall {}), {:#x}", catch.id, what, display_name(nr), regs.rip);
+        if !entering {
+            println!("Return value: {}", format_return(nr, regs.rax as i64));
+        }
+        true
+    }
+
+    fn format_args(&self, tid: Pid, nr: u64, args: &[u64; 6]) -> String {
+        let kinds: &[Arg] = match SYSCALLS.iter().find(|(n, _, _)| *n == nr) {
+            Some((_, _, kinds)) => kinds,
+            None => &[Hex, Hex, Hex, Hex, Hex, Hex],
+        };
+
+        kinds.iter().enumerate()
+            .map(|(i, kind)| match kind {
+                Int => format!("{}", args[i] as i64),
+                UInt => format!("{}", args[i]),
+                Hex => format!("{:#x}", args[i]),
+                Fd if args[i] as i32 as i64 == AT_FDCWD => String::from("AT_FDCWD"),
+                Fd => format!("{}", args[i] as i32),
+                Str => self.format_string(tid, args[i], None),
+                Buf => self.format_string(tid, args[i], args.get(i + 1).map(|&len| len as usize)),
+                OpenFlags => open_flags(args[i]),
+                Mode => format!("{:#o}", args[i]),
+                Prot => prot_flags(args[i]),
+                MapFlags => map_flags(args[i]),
+                Sig => match Signal::from_c_int(args[i] as i32) {
+                    Ok(sig) => sig.as_ref().to_string(),
+                    Err(_) => format!("{}", args[i]),
+                },
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /* Quote a string or buffer from the inferior like strace does */
+    fn format_string(&self, tid: Pid, addr: u64, len: Option<usize>) -> String {
+        if addr == 0 {
+            return String::from("NULL");
+        }
+
+        let want = std::cmp::min(len.unwrap_or(MAX_STR), MAX_STR);
+        let mut bytes = Vec::new();
+        let mut terminated = false;
+        'read: while bytes.len() < want {
+            let word = match ptrace::read(tid, (addr as usize + bytes.len()) as ptrace::AddressType) {
+                Ok(word) => word,
+                Err(_) => break,
+            };
+            for b in word.to_le_bytes().iter() {
+                if len.is_none() && *b == 0 {
+                    terminated = true;
+                    break 'read;
+                }
+                bytes.push(*b);
+                if bytes.len() == want { break }
+            }
+        }
+
+        if bytes.is_empty() && !terminated && len != Some(0) {
+            return format!("{:#x}", addr);
+        }
+
+        let mut s = String::from("\"");
+        for &b in &bytes {
+            match b {
+                b'"' => s.push_str("\\\""),
+                b'\\' => s.push_str("\\\\"),
+                b'\n' => s.push_str("\\n"),
+                b'\t' => s.push_str("\\t"),
+                b'\r' => s.push_str("\\r"),
+                0x20..=0x7e => s.push(b as char),
+                _ => s.push_str(&format!("\\x{:02x}", b)),
+            }
+        }
+        s.push('"');
+
+        let truncated = match len {
+            Some(len) => len > bytes.len(),
+            None => !terminated,
+        };
+        if truncated { s.push_str("...") }
+        s
+    }
+}
+
+/* Return value, or -1 and the errno name for failures */
+fn format_return(nr: u64, ret: i64) -> String {
+    if (-4095..0).contains(&ret) {
+        let errno = Errno::from_i32(-ret as i32);
+        return format!("-1 {:?} ({})", errno, errno.desc());
+    }
+    match syscall_name(nr) {
+        Some("mmap") | Some("brk") | Some("mremap") => format!("{:#x}", ret),
+        _ => format!("{}", ret),
+    }
+}
+
+/* `A|B|0x..` for the bits in `names`, with anything left over in hex */
+fn flags(mut val: u64, names: &[(u64, &str)]) -> String {
+    let mut parts = Vec::new();
+    for &(bit, name) in names {
+        if bit != 0 && val & bit == bit {
+            parts.push(name.to_string());
+            val &= !bit;
+        }
+    }
+    if val != 0 || parts.is_empty() {
+        parts.push(format!("{:#x}", val));
+    }
+    parts.join("|")
+}
+
+fn open_flags(val: u64) -> String {
+    let access = match val & libc::O_ACCMODE as u64 {
+        0 => "O_RDONLY",
+        1 => "O_WRONLY",
+        _ => "O_RDWR",
+    };
+    let rest = val & !(libc::O_ACCMODE as u64);
+    if rest == 0 {
+        return access.to_string();
+    }
+    format!("{}|{}", access, flags(rest, &[
+        (libc::O_CREAT as u64, "O_CREAT"),
+        (libc::O_EXCL as u64, "O_EXCL"),
+        (libc::O_NOCTTY as u64, "O_NOCTTY"),
+        (libc::O_TRUNC as u64, "O_TRUNC"),
+        (libc::O_APPEND as u64, "O_APPEND"),
+        (libc::O_NONBLOCK as u64, "O_NONBLOCK"),
+        (libc::O_DIRECTORY as u64, "O_DIRECTORY"),
+        (libc::O_NOFOLLOW as u64, "O_NOFOLLOW"),
+        (libc::O_CLOEXEC as u64, "O_CLOEXEC"),
+    ]))
+}
+
+fn prot_flags(val: u64) -> String {
+    if val == 0 {
+        return String::from("PROT_NONE");
+    }
+    flags(val, &[
+        (libc::PROT_READ as u64, "PROT_READ"),
+        (libc::PROT_WRITE as u64, "PROT_WRITE"),
+        (libc::PROT_EXEC as u64, "PROT_EXEC"),
+    ])
+}
+
+fn map_flags(val: u64) -> String {
+    flags(val, &[
+        (libc::MAP_SHARED as u64, "MAP_SHARED"),
+        (libc::MAP_PRIVATE as u64, "MAP_PRIVATE"),
+        (libc::MAP_FIXED as u64, "MAP_FIXED"),
+        (libc::MAP_ANONYMOUS as u64, "MAP_ANONYMOUS"),
+        (libc::MAP_NORESERVE as u64, "MAP_NORESERVE"),
+        (libc::MAP_DENYWRITE as u64, "MAP_DENYWRITE"),
+        (libc::MAP_STACK as u64, "MAP_STACK"),
+    ])
+}
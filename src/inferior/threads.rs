@@ -0,0 +1,254 @@
+/* Thread tracking
+ * New threads are reported through PTRACE_O_TRACECLONE. The debugger is
+ * all-stop: when one thread reports an event every other thread is stopped
+ * with SIGSTOP before we return to the prompt, and `cont` restarts them all.
+ */
+
+use nix::sys::{ptrace, signal};
+use nix::sys::signal::Signal;
+use nix::sys::wait::*;
+use nix::unistd::Pid;
+
+use super::{ ffi, Inferior, InferiorState };
+
+impl Inferior {
+    /* Events we want reported by ptrace */
+    pub(crate) fn ptrace_options(&self) -> ptrace::Options {
+        ptrace::Options::PTRACE_O_TRACECLONE
+            | ptrace::Options::PTRACE_O_TRACEFORK
+            | ptrace::Options::PTRACE_O_TRACEVFORK
+            | ptrace::Options::PTRACE_O_TRACEVFORKDONE
+            | ptrace::Options::PTRACE_O_TRACEEXEC
+            | ptrace::Options::PTRACE_O_TRACESYSGOOD
+    }
+
+    /* Track a thread of the inferior, returns its thread number */
+    pub(crate) fn add_thread(&mut self, tid: Pid) -> u32 {
+        let num = self.tids.keys().max().unwrap_or(&0) + 1;
+        self.tids.insert(num, tid);
+        self.thread_states.insert(tid, InferiorState::Startup);
+        num
+    }
+
+    pub(crate) fn remove_thread(&mut self, tid: Pid) {
+        self.tids.retain(|_, t| *t != tid);
+        self.thread_states.remove(&tid);
+        self.pending_signals.remove(&tid);
+        self.syscall_entry.remove(&tid);
+        if self.tid == tid {
+            self.tid = self.pid;
+        }
+    }
+
+    /// Thread number of `tid`
+    pub fn thread_num(&self, tid: Pid) -> Option<u32> {
+        self.tids.iter().find(|(_, t)| **t == tid).map(|(n, _)| *n)
+    }
+
+    /* Every thread of the inferior */
+    pub(crate) fn thread_pids(&self) -> Vec<Pid> {
+        if self.tids.is_empty() {
+            vec![self.pid]
+        } else {
+            let mut tids: Vec<(&u32, &Pid)> = self.tids.iter().collect();
+            tids.sort_by_key(|(num, _)| **num);
+            tids.into_iter().map(|(_, tid)| *tid).collect()
+        }
+    }
+
+    /* New threads report a SIGSTOP (or an event stop when seized) before
+     * running. It can arrive before or after the clone event. */
+    pub(crate) fn thread_starting(&self, tid: Pid) -> bool {
+        match self.thread_states.get(&tid) {
+            Some(state) => *state == InferiorState::Startup,
+            None => true,
+        }
+    }
+
+    /* Let a new thread run once its initial stop came in */
+    pub(crate) fn start_thread(&mut self, tid: Pid) {
+        if self.thread_num(tid).is_none() {
+            self.new_thread(tid);
+        }
+
+        // Debug registers aren't inherited by new threads
+        if let Err(e) = self.program_thread_debugregs(tid) {
+            println!("Unable to set debug registers for thread {}: {}", tid, e);
+        }
+
+        self.thread_states.insert(tid, InferiorState::Running);
+        let _ = self.resume_thread(tid, None);
+    }
+
+    /* PTRACE_EVENT_CLONE from `parent` */
+    pub(crate) fn handle_clone(&mut self, parent: Pid) {
+        self.register_clone(parent);
+        let _ = self.resume_thread(parent, None);
+    }
+
+    fn register_clone(&mut self, parent: Pid) {
+        if let Ok(new) = ptrace::getevent(parent) {
+            let new = Pid::from_raw(new as i32);
+            if self.thread_num(new).is_none() {
+                self.new_thread(new);
+            }
+        }
+    }
+
+    /* A ptrace event from `tid` while it has to stay where it is, being
+     * stepped or stopped. Nothing is resumed and there's no switching
+     * processes. False if an exec replaced the thread's stop. */
+    pub(crate) fn handle_event_in_place(&mut self, tid: Pid, event: i32) -> bool {
+        match event {
+            libc::PTRACE_EVENT_CLONE => self.register_clone(tid),
+            libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK => self.release_fork_child_of(tid, event),
+            libc::PTRACE_EVENT_VFORK_DONE => self.handle_vfork_done(),
+            // The other threads are gone and the leader sits in the exec
+            // stop. It's left stopped, and marked so, for `cont` to resume
+            // with the new image in place.
+            libc::PTRACE_EVENT_EXEC => {
+                self.handle_exec();
+                return false;
+            },
+            _ => {},
+        }
+        true
+    }
+
+    fn new_thread(&mut self, tid: Pid) {
+        let num = self.add_thread(tid);
+        println!("[New Thread {} ({})]", tid, num);
+    }
+
+    /* A thread or the whole process went away */
+    pub(crate) fn handle_exit(&mut self, tid: Pid, code: i32) {
+        if tid == self.pid {
+            println!("Process exited. Pid: {} Code: {}", tid, code);
+            self.process_exited();
+            self.tids.clear();
+            self.thread_states.clear();
+            self.state = InferiorState::Dead;
+        } else {
+            println!("[Thread {} exited]", tid);
+            self.remove_thread(tid);
+        }
+    }
+
+    /* `tid` stopped for something the user needs to see. Stop everything
+     * else before handing back the prompt. */
+    pub(crate) fn stop_all_threads(&mut self, tid: Pid) {
+        if tid != self.tid && self.tids.len() > 1 {
+            println!("[Switching to thread {} ({})]", self.thread_num(tid).unwrap_or(0), tid);
+        }
+        self.tid = tid;
+        self.state = InferiorState::Stopped;
+        self.stop_other_threads(tid);
+        self.refresh_modules();
+    }
+
+    /* SIGSTOP every running thread but `tid` and wait for them */
+    pub(crate) fn stop_other_threads(&mut self, tid: Pid) {
+        self.thread_states.insert(tid, InferiorState::Stopped);
+
+        for thread in self.thread_pids() {
+            if thread == tid { continue }
+            if self.thread_states.get(&thread) != Some(&InferiorState::Running) { continue }
+            if ffi::tgkill(self.pid, thread, Signal::SIGSTOP).is_err() {
+                self.remove_thread(thread);
+                continue;
+            }
+            self.wait_thread_stop(thread);
+        }
+    }
+
+    /* Wait for the SIGSTOP sent by `stop_all_threads`. Anything else the
+     * thread reports first is kept for later. */
+    fn wait_thread_stop(&mut self, tid: Pid) {
+        loop {
+            match waitpid(tid, Some(WaitPidFlag::__WALL)) {
+                Ok(WaitStatus::Stopped(_, signal::SIGSTOP)) => {
+                    self.thread_states.insert(tid, InferiorState::Stopped);
+                    return;
+                },
+                Ok(WaitStatus::Stopped(_, signal::SIGTRAP)) => {
+                    // Raced into a breakpoint, back up so it gets hit again
+                    // once resumed
+                    self.unwind_breakpoint_trap(tid);
+                    let _ = self.resume_thread(tid, None);
+                },
+                Ok(WaitStatus::Stopped(_, sig)) => {
+                    // Reported now, delivered when everything resumes
+                    let policy = self.signal_policy(sig);
+                    if policy.print {
+                        println!("Thread {} ({}) received signal {}.",
+                                 self.thread_num(tid).unwrap_or(0), tid, sig.as_ref());
+                    }
+                    if policy.pass {
+                        self.pending_signals.insert(tid, sig);
+                    }
+                    let _ = self.resume_thread(tid, None);
+                },
+                Ok(WaitStatus::PtraceSyscall(_)) => {
+                    // Logged if tracing, catchpoints wait for the next time
+                    self.handle_syscall(tid, false);
+                    let _ = self.resume_thread(tid, None);
+                },
+                Ok(WaitStatus::PtraceEvent(_, _, event)) => {
+                    if !self.handle_event_in_place(tid, event) { return }
+                    let _ = self.resume_thread(tid, None);
+                },
+                Ok(WaitStatus::Exited(..)) | Ok(WaitStatus::Signaled(..)) | Err(_) => {
+                    self.remove_thread(tid);
+                    return;
+                },
+                Ok(_) => continue,
+            }
+        }
+    }
+
+    /* Move RIP back onto an int3 the thread just executed */
+    fn unwind_breakpoint_trap(&mut self, tid: Pid) {
+        if let Ok(mut regs) = ptrace::getregs(tid) {
+            let addr = (regs.rip - 1) as usize;
+            if self.breakpoints.get(&addr).map(|bp| bp.enabled) == Some(true) {
+                regs.rip = addr as u64;
+                let _ = ptrace::setregs(tid, regs);
+            }
+        }
+    }
+
+    /// `info threads`
+    pub fn show_threads(&self) {
+        if self.tids.is_empty() {
+            println!("No threads.");
+            return;
+        }
+
+        println!("  {:<5}{:<20}{:<12}Location", "Id", "Target Id", "State");
+        let mut tids: Vec<(&u32, &Pid)> = self.tids.iter().collect();
+        tids.sort_by_key(|(num, _)| **num);
+        for (num, tid) in tids {
+            let state = self.thread_states.get(tid).cloned().unwrap_or(InferiorState::None);
+            let location = match ptrace::getregs(*tid) {
+                Ok(regs) => {
+                    let pc = regs.rip as usize;
+                    format!("{:#x}{}", pc, self.addr_label(pc))
+                },
+                Err(_) => String::new(),
+            };
+            println!("{} {:<5}{:<20}{:<12}{}",
+                     if *tid == self.tid { "*" } else { " " },
+                     num, format!("Thread {}", tid), format!("{:?}", state), location);
+        }
+    }
+
+    /// `thread <n>`: Switch the thread registers and stepping act on
+    pub fn select_thread(&mut self, num: u32) -> Result<(), failure::Error> {
+        let tid = *self.tids.get(&num)
+            .ok_or_else(|| failure::format_err!("Invalid thread ID: {}", num))?;
+        self.tid = tid;
+        println!("[Switching to thread {} ({})]", num, tid);
+        self.print_pc();
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+/* Stack unwinding
+ * Callers are found with the .eh_frame or .debug_frame CFI of the module
+ * holding the PC. Code without CFI falls back to the RBP chain, and the
+ * innermost frame also recognises a prologue that hasn't set RBP up yet.
+ * Signal handlers return into the `__restore_rt` trampoline, whose frame
+ * holds the ucontext with the registers the signal interrupted.
+ *
+ * `frame`, `up` and `down` select a frame. Register reads and expressions
+ * then see that frame's registers until the thread runs again.
+ */
+
+use elfkit::{ Elf, SectionContent };
+use libc::user_regs_struct;
+use nix::sys::ptrace;
+use nix::unistd::Pid;
+
+use std::sync::Arc;
+
+use super::cfi::{ unwind_regs, CallFrameTable, DW_RA, DW_RBP, DW_RSP, REG_COUNT, REG_NAMES };
+use super::registers::{ read_register, write_register };
+use super::symbols::load_address;
+use super::Inferior;
+
+/// Frames `bt` shows without a count
+pub const MAX_FRAMES: usize = 256;
+
+/* mov rax, 15 (rt_sigreturn); syscall */
+const SIGRETURN: [u8; 9] = [0x48, 0xc7, 0xc0, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+
+/* uc_mcontext in struct ucontext, and the gregs index of each register by
+ * DWARF number */
+const UC_MCONTEXT: u64 = 40;
+const MCONTEXT_REGS: [u64; REG_COUNT] = [
+    13, 12, 14, 11, 9, 8, 10, 15,  // rax rdx rcx rbx rsi rdi rbp rsp
+    0, 1, 2, 3, 4, 5, 6, 7,        // r8 - r15
+    16,                            // rip
+];
+
+/// One frame of the stack, 0 being the innermost
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub num: usize,
+    pub pc: usize,
+    /// Stack pointer before the call into this frame, None if its caller
+    /// couldn't be found
+    pub cfa: Option<usize>,
+    /// Registers by DWARF number, None where they weren't recovered
+    pub regs: [Option<u64>; REG_COUNT],
+    /// The PC is where the frame stopped rather than a return address
+    pub interrupted: bool,
+    /// The frame is the signal trampoline
+    pub signal: bool,
+}
+
+/* A frame picked with frame, up or down, and where the thread was then */
+#[derive(Clone, Debug)]
+pub(crate) struct SelectedFrame {
+    tid: Pid,
+    rip: u64,
+    rsp: u64,
+    frame: Frame,
+}
+
+/// Call frame information of a loaded ELF, looked up by module offset
+pub fn frame_table(elf: &Elf) -> CallFrameTable {
+    let mut table = CallFrameTable::new(load_address(elf));
+    // .eh_frame first, it's what the code was built to unwind with
+    for name in &[".eh_frame", ".debug_frame"] {
+        let section = elf.sections.iter().find(|section| section.name == name.as_bytes());
+        if let Some(section) = section {
+            if let SectionContent::Raw(data) = &section.content {
+                table.add_section(data, section.header.addr, *name == ".eh_frame");
+            }
+        }
+    }
+    table
+}
+
+fn user_regs(regs: &user_regs_struct) -> [Option<u64>; REG_COUNT] {
+    let mut out = [None; REG_COUNT];
+    for (num, name) in REG_NAMES.iter().enumerate() {
+        out[num] = read_register(regs, name);
+    }
+    out
+}
+
+impl Inferior {
+    /* CFI of the module loaded at `base` */
+    fn module_frame_table(&self, base: usize) -> Option<Arc<CallFrameTable>> {
+        self.frame_tables.get(&self.module_path(base)?).cloned()
+    }
+
+    fn read_word(&self, addr: u64) -> Option<u64> {
+        self.read_u64(addr as usize).ok()
+    }
+
+    /* Is `pc` the start of the rt_sigreturn trampoline */
+    fn is_sigreturn(&self, pc: usize) -> bool {
+        self.read_exact(pc, SIGRETURN.len()).is_ok_and(|code| code[..] == SIGRETURN[..])
+    }
+
+    /* Registers the signal interrupted, from the ucontext at the stack
+     * pointer of the trampoline frame */
+    fn signal_caller(&self, sp: u64) -> Option<[Option<u64>; REG_COUNT]> {
+        let mcontext = sp + UC_MCONTEXT;
+        let mut regs = [None; REG_COUNT];
+        for (num, idx) in MCONTEXT_REGS.iter().enumerate() {
+            regs[num] = Some(self.read_word(mcontext + idx * 8)?);
+        }
+        Some(regs)
+    }
+
+    /* RBP chain, or in the innermost frame a prologue or epilogue where
+     * RBP isn't the frame's */
+    fn frame_pointer_caller(&self, regs: &[Option<u64>; REG_COUNT], innermost: bool)
+                            -> Option<(u64, [Option<u64>; REG_COUNT])> {
+        let (pc, sp) = (regs[DW_RA]?, regs[DW_RSP]?);
+        let mut found = None;
+        if innermost {
+            let insn = self.disassemble(pc as usize, 1);
+            found = match insn.first().map(|i| (i.mnemonic.as_str(), i.op_str.as_str())) {
+                Some(("endbr64", _)) | Some(("push", "rbp")) => Some((sp + 8, regs[DW_RBP])),
+                Some((m, _)) if m.starts_with("ret") => Some((sp + 8, regs[DW_RBP])),
+                Some(("mov", "rbp, rsp")) => Some((sp + 16, self.read_word(sp))),
+                _ => None,
+            };
+        }
+        let (cfa, rbp) = match found {
+            Some(found) => found,
+            None => {
+                let rbp = regs[DW_RBP].filter(|&rbp| rbp != 0)?;
+                (rbp + 16, self.read_word(rbp))
+            },
+        };
+
+        let mut caller = [None; REG_COUNT];
+        caller[DW_RBP] = rbp;
+        caller[DW_RSP] = Some(cfa);
+        caller[DW_RA] = Some(self.read_word(cfa - 8)?);
+        Some((cfa, caller))
+    }
+
+    /* CFA of the frame with `regs` and its caller's registers, and whether
+     * this is the signal trampoline. `interrupted` as in `Frame`. */
+    fn unwind_frame(&self, regs: &[Option<u64>; REG_COUNT], interrupted: bool, innermost: bool)
+                    -> Option<(u64, [Option<u64>; REG_COUNT], bool)> {
+        let pc = regs[DW_RA]? as usize;
+        let sp = regs[DW_RSP]?;
+        if self.is_sigreturn(pc) {
+            return self.signal_caller(sp).map(|caller| (sp, caller, true));
+        }
+
+        // A return address may be just past a call that never returns, look
+        // at the call itself
+        let lookup = if interrupted { pc } else { pc.wrapping_sub(1) };
+        let row = self.module_from_addr(lookup)
+            .and_then(|(_, base)| self.module_frame_table(base)?.row((lookup - base) as u64));
+        if let Some(row) = row {
+            let read = |addr| self.read_word(addr);
+            if let Some((cfa, caller)) = unwind_regs(&row, regs, &read) {
+                if caller[DW_RA].is_some() {
+                    return Some((cfa, caller, row.signal));
+                }
+            }
+        }
+        self.frame_pointer_caller(regs, innermost).map(|(cfa, caller)| (cfa, caller, false))
+    }
+
+    /// Frames of the current thread from the innermost out, at most `limit`
+    pub fn backtrace(&self, limit: usize) -> Result<Vec<Frame>, failure::Error> {
+        self.check_stopped()?;
+        let regs = ptrace::getregs(self.tid)
+            .map_err(|e| failure::format_err!("Unable to read registers: {}", e))?;
+
+        let mut regs = user_regs(&regs);
+        let mut interrupted = true;
+        let mut frames: Vec<Frame> = Vec::new();
+        while frames.len() < limit {
+            let num = frames.len();
+            let step = self.unwind_frame(&regs, interrupted, num == 0);
+            frames.push(Frame {
+                num,
+                pc: regs[DW_RA].unwrap_or(0) as usize,
+                cfa: step.as_ref().map(|(cfa, _, _)| *cfa as usize),
+                regs,
+                interrupted,
+                signal: step.as_ref().is_some_and(|(_, _, signal)| *signal),
+            });
+
+            let (_, caller, signal) = match step {
+                Some(step) => step,
+                None => break,
+            };
+            // The outermost frame returns to 0. Stacks grow down, a caller
+            // below us means the unwind went wrong.
+            match caller[DW_RA] {
+                Some(ra) if ra != 0 && self.mem.has_perms(ra as usize, "x") => {},
+                _ => break,
+            }
+            match (caller[DW_RSP], regs[DW_RSP]) {
+                (Some(outer), Some(inner)) if signal || outer > inner => {},
+                _ => break,
+            }
+            regs = caller;
+            interrupted = signal;
+        }
+        Ok(frames)
+    }
+
+    /// `module!symbol+off` for the PC of a frame
+    pub fn frame_label(&self, frame: &Frame) -> String {
+        let pc = frame.pc;
+        let lookup = if frame.interrupted || frame.signal { pc } else { pc.wrapping_sub(1) };
+        let (module, base) = match self.module_from_addr(lookup) {
+            Some(module) => module,
+            None => return "??".to_string(),
+        };
+        match self.symbol_at(lookup) {
+            Some((name, off)) => format!("{}!{}+{:#x}", module, self.display_symbol(&name), off + (pc - lookup)),
+            None => format!("{}+{:#x}", module, pc - base),
+        }
+    }
+
+    fn print_frame(&self, frame: &Frame) {
+        let cfa = frame.cfa.map_or_else(|| "?".to_string(), |cfa| format!("{:#x}", cfa));
+        println!("#{:<3}{:#018x} in {} (cfa {}){}", frame.num, frame.pc, self.frame_label(frame), cfa,
+                 if frame.signal { " <signal handler called>" } else { "" });
+    }
+
+    /// `bt [N]`
+    pub fn show_backtrace(&self, limit: Option<usize>) -> Result<(), failure::Error> {
+        let limit = limit.unwrap_or(MAX_FRAMES);
+        let mut frames = self.backtrace(limit + 1)?;
+        let more = frames.len() > limit;
+        frames.truncate(limit);
+        for frame in &frames {
+            self.print_frame(frame);
+        }
+        if more {
+            println!("(More stack frames follow...)");
+        }
+        Ok(())
+    }
+
+    /// Frame picked with frame, up or down, as long as the thread hasn't
+    /// moved since
+    pub fn selected_frame(&self) -> Option<&Frame> {
+        let sel = self.selected_frame.as_ref()?;
+        let regs = ptrace::getregs(self.tid).ok()?;
+        if sel.tid == self.tid && sel.rip == regs.rip && sel.rsp == regs.rsp {
+            Some(&sel.frame)
+        } else {
+            None
+        }
+    }
+
+    fn selected_frame_num(&self) -> usize {
+        self.selected_frame().map_or(0, |frame| frame.num)
+    }
+
+    /* Forget the selected frame, the thread is about to run */
+    pub(crate) fn clear_selected_frame(&mut self) {
+        self.selected_frame = None;
+    }
+
+    /// `frame [N]`: Select frame N, or show the selected one
+    pub fn select_frame(&mut self, num: Option<usize>) -> Result<(), failure::Error> {
+        let num = num.unwrap_or_else(|| self.selected_frame_num());
+        let frame = self.backtrace(num + 1)?.into_iter().nth(num)
+            .ok_or_else(|| failure::format_err!("No frame at level {}.", num))?;
+        let regs = ptrace::getregs(self.tid)?;
+        self.print_frame(&frame);
+        self.selected_frame = Some(SelectedFrame { tid: self.tid, rip: regs.rip, rsp: regs.rsp, frame });
+        Ok(())
+    }
+
+    /// `up [N]`: Select the frame N callers out
+    pub fn frame_up(&mut self, count: usize) -> Result<(), failure::Error> {
+        let cur = self.selected_frame_num();
+        let frames = self.backtrace(cur + count + 1)?;
+        if frames.len() <= cur + 1 {
+            return Err(failure::format_err!("Initial frame selected; you cannot go up."));
+        }
+        self.select_frame(Some(std::cmp::min(cur + count, frames.len() - 1)))
+    }
+
+    /// `down [N]`: Select the frame N callees in
+    pub fn frame_down(&mut self, count: usize) -> Result<(), failure::Error> {
+        let cur = self.selected_frame_num();
+        if cur == 0 {
+            return Err(failure::format_err!("Bottom (innermost) frame selected; you cannot go down."));
+        }
+        self.select_frame(Some(cur.saturating_sub(count)))
+    }
+
+    /// Registers of the selected frame. Those the unwinder couldn't recover
+    /// keep the innermost frame's values.
+    pub fn frame_registers(&self) -> Result<user_regs_struct, failure::Error> {
+        let mut regs = ptrace::getregs(self.tid)
+            .map_err(|e| failure::format_err!("Unable to read registers: {}", e))?;
+        if let Some(frame) = self.selected_frame() {
+            for (num, value) in frame.regs.iter().enumerate() {
+                if let Some(value) = value {
+                    write_register(&mut regs, REG_NAMES[num], *value)?;
+                }
+            }
+        }
+        Ok(regs)
+    }
+
+    /// PC of the selected frame
+    pub fn frame_pc(&self) -> Result<usize, failure::Error> {
+        Ok(self.frame_registers()?.rip as usize)
+    }
+
+    /// Is a frame other than the innermost selected
+    pub fn outer_frame_selected(&self) -> bool {
+        self.selected_frame_num() != 0
+    }
+}
@@ -4,15 +4,47 @@ pub static RDBG_COMMANDS: &[(&str, &str)] = &[
     ("help", "Display help"),
     ("quit", "Exit rdbg"),
     ("load", "Load a file"),
-    ("run", "Execute a file"),
+    ("run", "Execute a file (run [path] [args...] [< in] [> out])"),
     ("execute", "Execute a file"),
-    ("break", "Set a breakpoint"),
+    ("attach", "Attach to a running process"),
+    ("detach", "Detach from the inferior and let it run"),
+    ("break", "Set a breakpoint (0x.., *expr, symbol+off, module!symbol+off, module+off, file.c:line)"),
+    ("hbreak", "Set a hardware breakpoint"),
+    ("watch", "Break when memory is written"),
+    ("rwatch", "Break when memory is read"),
+    ("awatch", "Break when memory is read or written"),
+    ("delete", "Delete breakpoints by number, or all of them"),
+    ("disable", "Disable breakpoints by number"),
+    ("enable", "Enable breakpoints by number"),
+    ("clear", "Delete the breakpoint at a location"),
+    ("condition", "Set or remove a breakpoint condition"),
+    ("ignore", "Skip the next N hits of a breakpoint"),
+    ("info", "Show information (breakpoints, registers, float, vector, threads, signals, sharedlibrary, launch)"),
+    ("catch", "Stop on system calls (catch syscall [name|number...])"),
+    ("trace", "Log system calls without stopping (trace syscalls on|off)"),
+    ("handle", "Set whether a signal stops, prints or is passed to the inferior"),
+    ("thread", "Select a thread by number"),
     ("continue", "Continue execution"),
-    ("hexdump", "Dump Hex"),
-    ("registers", "Show register information for inferior"),
-    ("symbols", "Show symbols for inferior"),
-    ("disas", "Disassemble a function"),
+    ("stepi", "Step one instruction"),
+    ("nexti", "Step one instruction, stepping over calls"),
+    ("finish", "Run until the current function returns"),
+    ("bt", "Show the call stack, at most N frames (bt [N])"),
+    ("frame", "Select a frame for registers and memory, or show the selected one (frame [N])"),
+    ("up", "Select the caller of the selected frame (up [N])"),
+    ("down", "Select the callee of the selected frame (down [N])"),
+    ("until", "Run until a location is reached"),
+    ("jump", "Resume execution at a location"),
+    ("x", "Examine memory (x/<count><x|d|u|c|s|i><b|h|w|g> <addr>)"),
+    ("hexdump", "Dump memory with offset, hex and ASCII columns (hexdump <addr> <len>)"),
+    ("write", "Write bytes to memory (write <addr> <hex bytes>)"),
+    ("fill", "Fill memory with a byte (fill <addr> <len> <byte>)"),
+    ("registers", "Show registers, changed ones highlighted (registers [name...])"),
+    ("symbols", "Show symbols, optionally those matching a regex (symbols [regex])"),
+    ("disas", "Disassemble at a location or the PC (disas [location] [count])"),
     ("pcode", "Show pcode for a function"),
+    ("vmmap", "Show the memory map, or the mappings matching an address or filter (vmmap [addr|filter])"),
     ("memory", "Show memory map of inferior"),
-    ("context", "Show context for current inferior")
+    ("context", "Show context for current inferior"),
+    ("set", "Change a register, memory or a setting ($reg = .., *(type*)addr = .., args, env, cwd, disable-randomization, personality, rlimit, follow-fork-mode, detach-on-fork, demangle)"),
+    ("unset", "Remove an environment variable (unset env [NAME])")
 ];
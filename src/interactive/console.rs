@@ -1,4 +1,5 @@
 use ansi_term::Color;
+use nix::sys::signal::Signal;
 use linefeed::{Interface, ReadResult};
 use linefeed::command::COMMANDS;
 use linefeed::inputrc::parse_text;
@@ -23,11 +24,22 @@ use tui::Terminal;
 //use std::path::Path;
 //use std::u64;
 
-use crate::inferior::{ Inferior, InferiorState };
+use crate::inferior::{ Inferior, InferiorState, parse_addr };
+use crate::inferior::expr;
+use crate::inferior::location::parse_location;
+use crate::inferior::demangle::parse_demangle_mode;
+use crate::inferior::examine::parse_x_format;
+use crate::inferior::patch::parse_hex_bytes;
+use crate::inferior::fpregs::is_fp_register;
+use crate::inferior::debugreg::HwKind;
+use crate::inferior::fork::FollowForkMode;
+use crate::inferior::signals::parse_signal;
+use crate::inferior::syscalls::parse_syscall;
+use crate::inferior::launch::parse_rlimit_value;
 use crate::interactive::context::Context;
 use crate::interactive::commands::*;
 use crate::interactive::completer::DbgCompleter;
-use crate::interactive::util::{split_first_word};
+use crate::interactive::util::{split_args, split_first_word};
 use crate::interactive::util::event::{Config, Event, Events};
 use crate::interactive::ui;
 //use self::app::{ui, App};
@@ -36,12 +48,50 @@ use crate::interactive::ui;
 
 const HISTORY_FILE: &str = ".rdbg_history";
 
+// Repeat count for stepping commands, defaults to 1
+fn parse_count(args: &str) -> usize {
+    match args.trim() {
+        "" => 1,
+        n => n.parse::<usize>().unwrap_or_else(|_| { println!("Invalid count: {}", n); 0 }),
+    }
+}
+
+// Optional frame number or count for `bt`, `frame`, `up` and `down`
+fn parse_level(args: &str) -> Result<Option<usize>, failure::Error> {
+    match args.trim() {
+        "" => Ok(None),
+        n => n.parse::<usize>().map(Some).map_err(|_| failure::format_err!("Invalid number: {}", n)),
+    }
+}
+
+// Breakpoint numbers given to `delete`, `enable` and `disable`
+fn parse_ids(args: &str) -> Vec<usize> {
+    args.split_whitespace()
+        .filter_map(|a| match a.parse::<usize>() {
+            Ok(id) => Some(id),
+            Err(_) => { println!("Invalid breakpoint number: {}", a); None }
+        })
+        .collect()
+}
+
+// on/off values for `set`
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value.trim() {
+        "on" | "1" | "yes" | "enable" => Some(true),
+        "off" | "0" | "no" | "disable" => Some(false),
+        _ => None,
+    }
+}
+
 #[derive(Debug, StructOpt)]
 struct Cli {
     #[structopt(long = "tick-rate", default_value = "250")]
     tick_rate: u64,
     #[structopt(long = "log")]
     log: bool,
+    /// Attach to a running process on startup
+    #[structopt(short = "p", long = "pid")]
+    pid: Option<u32>,
 }
 
 pub struct Menu<'a> {
@@ -101,6 +151,17 @@ impl<'a> Menu<'a> {
         }
     }
 
+    // Handle command line options that act before the first prompt
+    pub fn startup(&mut self) {
+        let cli = Cli::from_args();
+
+        if let Some(pid) = cli.pid {
+            if let Err(e) = self.inferior.attach(pid) {
+                println!("Attach Error: {}", e);
+            }
+        }
+    }
+
     // https://github.com/murarth/linefeed/blob/master/examples/demo.rs
     pub fn cmdloop(&mut self) -> Result<(), failure::Error> {
         while let ReadResult::Input(line) = self.linefeed.read_line()? {
@@ -109,19 +170,22 @@ impl<'a> Menu<'a> {
             }
 
             let (cmd, _args) = split_first_word(&line);
-            let debug_target = String::from("/home/nmavis/dev/rustdbg/tests/elf/hello_world");
-            let debug_args = &[];
 
             match cmd {
-                // InferiorState::None||Startup
-                "test" => {
-                    if self.inferior.state == InferiorState::None { self.inferior.start(debug_target, debug_args); }
-                    //Inferior::breakpoint::set(inf.pid, 0x55555555513d);
-                },
                 "run" => {
-                    if _args.is_empty() { println!("Please provide a process path to debug"); }
-                    else if Path::new(_args).is_file() { self.inferior.start(_args.into(), debug_args); }
-                    else { println!("Invalid path to inferior."); }
+                    if let Err(e) = self.run(_args) { println!("Run Error: {}", e); }
+                },
+                "attach" => {
+                    if self.inferior.attached { println!("Already attached to process {}", self.inferior.pid); }
+                    else {
+                        match _args.parse::<u32>() {
+                            Ok(pid) => if let Err(e) = self.inferior.attach(pid) { println!("Attach Error: {}", e); },
+                            Err(_) => println!("Please provide a pid to attach to"),
+                        }
+                    }
+                },
+                "detach" => {
+                    if let Err(e) = self.inferior.detach() { println!("Detach Error: {}", e); }
                 },
                 "context" => {
                     if self.inferior.state == InferiorState::Stopped {
@@ -132,13 +196,127 @@ impl<'a> Menu<'a> {
                 },
                 "continue" => if self.inferior.state == InferiorState::Stopped { self.inferior.resume() },
                 "break" => {
-                    let bpaddr = _args.split_whitespace().collect();
-                    self.inferior.set_breakpoint(bpaddr)?;
+                    if let Err(e) = self.set_breakpoint(_args) {
+                        println!("Breakpoint Error: {}", e);
+                    }
+                },
+                "stepi" | "si" => {
+                    if let Err(e) = self.inferior.stepi(parse_count(_args)) { println!("Step Error: {}", e); }
+                },
+                "nexti" | "ni" => {
+                    if let Err(e) = self.inferior.nexti(parse_count(_args)) { println!("Step Error: {}", e); }
+                },
+                "finish" => {
+                    if let Err(e) = self.inferior.finish() { println!("Step Error: {}", e); }
+                },
+                "until" | "u" => {
+                    let res = self.location_addr(_args).and_then(|addr| self.inferior.until(addr));
+                    if let Err(e) = res { println!("Step Error: {}", e); }
+                },
+                "jump" => {
+                    let res = self.location_addr(_args).and_then(|addr| self.inferior.jump(addr));
+                    if let Err(e) = res { println!("Jump Error: {}", e); }
+                },
+                "bt" | "backtrace" | "where" => {
+                    let res = parse_level(_args).and_then(|limit| self.inferior.show_backtrace(limit));
+                    if let Err(e) = res { println!("Backtrace Error: {}", e); }
+                },
+                "frame" | "f" | "up" | "down" => {
+                    if let Err(e) = self.frame(cmd, _args) { println!("Frame Error: {}", e); }
+                },
+                "disas" | "disassemble" => {
+                    if let Err(e) = self.disassemble(_args) { println!("Disassemble Error: {}", e); }
+                },
+                "hbreak" => self.set_hw_breakpoint(_args, HwKind::Execute),
+                "watch" => self.set_hw_breakpoint(_args, HwKind::Write),
+                "rwatch" => self.set_hw_breakpoint(_args, HwKind::Read),
+                "awatch" => self.set_hw_breakpoint(_args, HwKind::Access),
+                "info" => self.info(_args),
+                "catch" => {
+                    if let Err(e) = self.catch(_args) { println!("Catch Error: {}", e); }
+                },
+                "trace" => {
+                    let (what, value) = split_first_word(_args);
+                    match (what, parse_on_off(if value.is_empty() { "on" } else { value })) {
+                        ("syscalls", Some(val)) => self.inferior.set_trace_syscalls(val),
+                        _ => println!("Usage: trace syscalls [on|off]"),
+                    }
+                },
+                "handle" => {
+                    if let Err(e) = self.handle_signals(_args) { println!("Signal Error: {}", e); }
+                },
+                "thread" => {
+                    match _args.parse::<u32>() {
+                        Ok(num) => if let Err(e) = self.inferior.select_thread(num) { println!("Thread Error: {}", e); },
+                        Err(_) => self.inferior.show_threads(),
+                    }
+                },
+                "delete" => {
+                    if _args.is_empty() {
+                        self.inferior.delete_all_breakpoints();
+                    }
+                    for id in parse_ids(_args) {
+                        if let Err(e) = self.inferior.delete_breakpoint(id) { println!("Breakpoint Error: {}", e); }
+                    }
+                },
+                "disable" => {
+                    for id in parse_ids(_args) {
+                        if let Err(e) = self.inferior.disable_breakpoint(id) { println!("Breakpoint Error: {}", e); }
+                    }
+                },
+                "enable" => {
+                    for id in parse_ids(_args) {
+                        if let Err(e) = self.inferior.enable_breakpoint(id) { println!("Breakpoint Error: {}", e); }
+                    }
+                },
+                "condition" => {
+                    let (id, cond) = split_first_word(_args);
+                    let res = match id.parse::<usize>() {
+                        Ok(id) if cond.is_empty() => self.inferior.set_condition(id, None),
+                        Ok(id) => expr::parse(cond).and_then(|c| self.inferior.set_condition(id, Some(c))),
+                        Err(_) => Err(failure::format_err!("Usage: condition N [expr]")),
+                    };
+                    if let Err(e) = res { println!("Breakpoint Error: {}", e); }
+                },
+                "ignore" => {
+                    let args: Vec<&str> = _args.split_whitespace().collect();
+                    let res = match (args.first().map(|a| a.parse::<usize>()), args.get(1).map(|a| a.parse::<u64>())) {
+                        (Some(Ok(id)), Some(Ok(count))) => self.inferior.ignore_breakpoint(id, count),
+                        _ => Err(failure::format_err!("Usage: ignore N <count>")),
+                    };
+                    if let Err(e) = res { println!("Breakpoint Error: {}", e); }
+                },
+                "clear" => {
+                    let res = self.location_addr(_args).and_then(|addr| self.inferior.clear_breakpoint(addr));
+                    if let Err(e) = res { println!("Breakpoint Error: {}", e); }
+                },
+                "x" => {
+                    if let Err(e) = self.examine("", _args) { println!("Memory Error: {}", e); }
+                },
+                cmd if cmd.starts_with("x/") => {
+                    if let Err(e) = self.examine(&cmd[2..], _args) { println!("Memory Error: {}", e); }
+                },
+                "hexdump" => {
+                    if let Err(e) = self.hexdump(_args) { println!("Memory Error: {}", e); }
+                },
+                "write" => {
+                    if let Err(e) = self.write_memory(_args) { println!("Memory Error: {}", e); }
+                },
+                "fill" => {
+                    if let Err(e) = self.fill_memory(_args) { println!("Memory Error: {}", e); }
+                },
+                "registers" => self.show_registers(_args),
+                "vmmap" | "memory" => self.vmmap(_args),
+                "symbols" => {
+                    if let Err(e) = self.inferior.show_symbols(_args.trim()) { println!("Symbol Error: {}", e); }
                 },
-                "registers" => println!("{:#x?}", self.inferior.registers()),
-                "memory" => self.inferior.show_memory_map(),
                 // "files" => inf.files(),
-                "env" => println!("{:#?}", self.inferior.env),
+                // The attached process's own, otherwise what `run` passes on
+                "env" => if self.inferior.process_env.is_empty() {
+                    println!("{:#?}", self.inferior.env)
+                } else {
+                    println!("{:#?}", self.inferior.process_env)
+                },
                 "pcode" => unimplemented!(),
                 "help" => {
                     println!("rustdbg commands:\n");
@@ -172,9 +350,13 @@ impl<'a> Menu<'a> {
                     }
                 },
                 "quit" => break,
-                "set" => {
-                    let d = parse_text("<input>", &line);
-                    self.linefeed.evaluate_directives(d);
+                "set" => self.set(_args, &line),
+                "unset" => {
+                    let (what, name) = split_first_word(_args);
+                    match what {
+                        "env" | "environment" => self.inferior.unset_env(if name.is_empty() { None } else { Some(name) }),
+                        _ => println!("Usage: unset env [NAME]"),
+                    }
                 },
                 _ => continue,
             }
@@ -186,6 +368,350 @@ impl<'a> Menu<'a> {
         Ok(())
     }
 
+    // Address of a location: 0x.., *expr, symbol+off, module!symbol+off,
+    // module+off or file.c:line
+    fn location_addr(&self, args: &str) -> Result<usize, failure::Error> {
+        let loc = parse_location(args)?;
+        self.inferior.resolve_location(&loc)
+    }
+
+    // frame [N], up [N], down [N]
+    fn frame(&mut self, cmd: &str, args: &str) -> Result<(), failure::Error> {
+        let level = parse_level(args)?;
+        match cmd {
+            "up" => self.inferior.frame_up(level.unwrap_or(1)),
+            "down" => self.inferior.frame_down(level.unwrap_or(1)),
+            _ => self.inferior.select_frame(level),
+        }
+    }
+
+    // break <location...> [if <expr>]
+    fn set_breakpoint(&mut self, args: &str) -> Result<(), failure::Error> {
+        let (locs, cond) = match args.find(" if ") {
+            Some(pos) => (&args[..pos], Some(expr::parse(&args[pos + 4..])?)),
+            None => (args, None),
+        };
+
+        let ids = self.inferior.set_breakpoint(locs.split_whitespace().collect())?;
+        if let Some(cond) = cond {
+            for id in ids {
+                self.inferior.set_condition(id, Some(cond.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // hbreak <addr> | watch/rwatch/awatch <addr> [len]
+    fn set_hw_breakpoint(&mut self, args: &str, kind: HwKind) {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        let addr = match args.first().map(|a| parse_addr(a)) {
+            Some(Ok(addr)) => addr,
+            _ => { println!("Please provide an address"); return; }
+        };
+        let len = match args.get(1).map(|a| a.parse::<usize>()) {
+            Some(Ok(len)) => len,
+            Some(Err(_)) => { println!("Invalid length: {}", args[1]); return; }
+            None => 8,
+        };
+
+        match self.inferior.set_hw_breakpoint(addr, len, kind) {
+            Ok(id) if kind == HwKind::Execute => println!("{} {} at {:#x}", kind.describe(), id, addr),
+            Ok(id) => println!("{} {}: {:#x} ({} bytes)", kind.describe(), id, addr, len),
+            Err(e) => println!("Breakpoint Error: {}", e),
+        }
+    }
+
+    // x/<count><fmt><size> <location>
+    fn examine(&mut self, format: &str, args: &str) -> Result<(), failure::Error> {
+        let format = parse_x_format(format)?;
+        if args.trim().is_empty() {
+            return Err(failure::format_err!("Please provide an address to examine"));
+        }
+        let addr = self.location_addr(args)?;
+        self.inferior.examine(addr, &format)
+    }
+
+    // disas [location] [count], at the PC without a location
+    fn disassemble(&mut self, args: &str) -> Result<(), failure::Error> {
+        if self.inferior.state != InferiorState::Stopped {
+            return Err(failure::format_err!("The program is not being run."));
+        }
+        let args = args.trim();
+        let (loc, count) = match args.rfind(char::is_whitespace) {
+            Some(pos) => match args[pos..].trim().parse::<usize>() {
+                Ok(count) => (args[..pos].trim(), count),
+                Err(_) => (args, 16),
+            },
+            None => (args, 16),
+        };
+        let addr = if loc.is_empty() {
+            self.inferior.frame_pc()?
+        } else {
+            self.location_addr(loc)?
+        };
+        self.inferior.show_disassembly(addr, count)
+    }
+
+    // vmmap [addr-expr|filter]
+    fn vmmap(&mut self, args: &str) {
+        if self.inferior.state != InferiorState::Stopped {
+            println!("No inferior found...");
+            return;
+        }
+        let args = args.trim();
+        let filter = if args.starts_with('$') || args.starts_with(|c: char| c.is_ascii_digit()) {
+            match expr::parse(args).and_then(|e| e.eval(&self.inferior)) {
+                Ok(addr) => format!("{:#x}", addr),
+                Err(e) => { println!("Memory Error: {}", e); return; }
+            }
+        } else {
+            args.to_string()
+        };
+        self.inferior.show_memory_map(&filter);
+    }
+
+    // hexdump <addr-expr> <len>
+    fn hexdump(&mut self, args: &str) -> Result<(), failure::Error> {
+        let args = args.trim();
+        let (addr, len) = match args.rfind(char::is_whitespace) {
+            Some(pos) => (&args[..pos], args[pos..].trim()),
+            None => return Err(failure::format_err!("Usage: hexdump <addr> <len>")),
+        };
+        let addr = self.location_addr(addr)?;
+        let len = expr::parse_number(len)? as usize;
+        self.inferior.hexdump(addr, len)
+    }
+
+    // set $reg = <expr> | set *(type*)<addr> = <expr>
+    // set $xmm0.v4_float = {1, 2, 3, 4} | set $st0 = 1.5
+    fn assign(&mut self, args: &str) -> Result<(), failure::Error> {
+        if let Some(pos) = args.find('=') {
+            let target = args[..pos].trim();
+            let (name, view) = match target.find('.') {
+                Some(dot) => (&target[..dot], Some(&target[dot + 1..])),
+                None => (target, None),
+            };
+            if name.starts_with('$') && is_fp_register(name) {
+                return self.inferior.set_fp_register(name, view, &args[pos + 1..]);
+            }
+        }
+
+        let (target, value) = expr::parse_assignment(args)?;
+        self.inferior.assign(&target, &value)
+    }
+
+    // write <addr> <hex bytes>
+    fn write_memory(&mut self, args: &str) -> Result<(), failure::Error> {
+        let (addr, bytes) = split_first_word(args);
+        let addr = expr::parse(addr)?.eval(&self.inferior)? as usize;
+        let bytes = parse_hex_bytes(bytes)?;
+        self.inferior.patch_memory(addr, &bytes)?;
+        println!("Wrote {} bytes at {:#x}", bytes.len(), addr);
+        Ok(())
+    }
+
+    // fill <addr> <len> <byte>
+    fn fill_memory(&mut self, args: &str) -> Result<(), failure::Error> {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        if args.len() != 3 {
+            return Err(failure::format_err!("Usage: fill <addr> <len> <byte>"));
+        }
+        let addr = expr::parse(args[0])?.eval(&self.inferior)? as usize;
+        let len = expr::parse_number(args[1])? as usize;
+        let byte = expr::parse_number(args[2])?;
+        if byte > 0xff {
+            return Err(failure::format_err!("Fill value {:#x} doesn't fit in a byte", byte));
+        }
+        self.inferior.fill_memory(addr, len, byte as u8)?;
+        println!("Filled {} bytes at {:#x} with {:#04x}", len, addr, byte);
+        Ok(())
+    }
+
+    // Debugger settings, anything else is handed to linefeed as a directive
+    fn set(&mut self, args: &str, line: &str) {
+        let (what, value) = split_first_word(args);
+
+        if what.starts_with('$') || what.starts_with('*') {
+            if let Err(e) = self.assign(args) { println!("Error: {}", e); }
+            return;
+        }
+
+        match what {
+            "var" | "variable" => {
+                if let Err(e) = self.assign(value) { println!("Error: {}", e); }
+            },
+            "follow-fork-mode" => match value.trim() {
+                "parent" => self.inferior.set_follow_fork_mode(FollowForkMode::Parent),
+                "child" => self.inferior.set_follow_fork_mode(FollowForkMode::Child),
+                _ => println!("Usage: set follow-fork-mode parent|child"),
+            },
+            "demangle" => match parse_demangle_mode(value) {
+                Some(mode) => self.inferior.set_demangle_mode(mode),
+                None => println!("Usage: set demangle on|off|nohash"),
+            },
+            "detach-on-fork" => match parse_on_off(value) {
+                Some(val) => self.inferior.set_detach_on_fork(val),
+                None => println!("Usage: set detach-on-fork on|off"),
+            },
+            "args" => self.inferior.set_args(split_args(value)),
+            "env" | "environment" => {
+                // NAME=VALUE or NAME VALUE
+                let (name, val) = match value.find('=') {
+                    Some(pos) => (value[..pos].trim(), value[pos + 1..].trim()),
+                    None => split_first_word(value),
+                };
+                if name.is_empty() { println!("Usage: set env NAME=VALUE"); }
+                else { self.inferior.set_env(name, val); }
+            },
+            "cwd" => {
+                if let Err(e) = self.inferior.set_cwd(value.trim()) { println!("Error: {}", e); }
+            },
+            "disable-randomization" => match parse_on_off(value) {
+                Some(val) => self.inferior.set_disable_randomization(val),
+                None => println!("Usage: set disable-randomization on|off"),
+            },
+            "personality" => {
+                let (flag, val) = split_first_word(value);
+                match parse_on_off(val) {
+                    Some(val) => if let Err(e) = self.inferior.set_personality_flag(flag, val) { println!("Error: {}", e); },
+                    None => println!("Usage: set personality <flag> on|off"),
+                }
+            },
+            "rlimit" => {
+                let args: Vec<&str> = value.split_whitespace().collect();
+                let soft = args.get(1).and_then(|v| parse_rlimit_value(v));
+                let hard = args.get(2).map(|v| parse_rlimit_value(v));
+                match (args.first(), soft, hard) {
+                    (Some(name), Some(soft), None) => if let Err(e) = self.inferior.set_rlimit(name, soft, None) { println!("Error: {}", e); },
+                    (Some(name), Some(soft), Some(Some(hard))) => if let Err(e) = self.inferior.set_rlimit(name, soft, Some(hard)) { println!("Error: {}", e); },
+                    _ => println!("Usage: set rlimit <core|stack|nofile|as|data|fsize|cpu> <soft|unlimited> [hard]"),
+                }
+            },
+            _ => {
+                let d = parse_text("<input>", line);
+                self.linefeed.evaluate_directives(d);
+            },
+        }
+    }
+
+    // run [path] [args...] [< in] [> out]
+    // Arguments and redirects replace the previous ones, `run` on its own
+    // restarts the last program with the same settings
+    fn run(&mut self, args: &str) -> Result<(), failure::Error> {
+        if self.inferior.attached && self.inferior.state != InferiorState::Dead {
+            return Err(failure::format_err!("The program is already being debugged, detach first"));
+        }
+
+        let mut words = split_args(args).into_iter();
+        let path = match words.next() {
+            Some(path) => path,
+            None if !self.inferior.location.is_empty() => self.inferior.location.clone(),
+            None => return Err(failure::format_err!("Please provide a process path to debug")),
+        };
+        if !Path::new(&path).is_file() {
+            return Err(failure::format_err!("Invalid path to inferior: {}", path));
+        }
+
+        let words: Vec<String> = words.collect();
+        if !words.is_empty() {
+            let mut argv = Vec::new();
+            let (mut stdin, mut stdout) = (None, None);
+            let mut words = words.into_iter();
+            while let Some(word) = words.next() {
+                if word == "<" || word == ">" {
+                    let file = words.next()
+                        .ok_or_else(|| failure::format_err!("Missing file name after '{}'", word))?;
+                    if word == "<" { stdin = Some(file.into()); } else { stdout = Some(file.into()); }
+                } else if let Some(file) = word.strip_prefix('<') {
+                    stdin = Some(file.into());
+                } else if let Some(file) = word.strip_prefix('>') {
+                    stdout = Some(file.into());
+                } else {
+                    argv.push(word);
+                }
+            }
+            self.inferior.set_args(argv);
+            self.inferior.set_redirects(stdin, stdout);
+        }
+
+        self.inferior.start(path)
+    }
+
+    // catch syscall [name|number...]
+    fn catch(&mut self, args: &str) -> Result<(), failure::Error> {
+        let (what, rest) = split_first_word(args);
+        if what != "syscall" {
+            return Err(failure::format_err!("Usage: catch syscall [name|number...]"));
+        }
+
+        let mut syscalls = Vec::new();
+        for name in rest.split_whitespace() {
+            match parse_syscall(name) {
+                Some(nr) => syscalls.push(nr),
+                None => return Err(failure::format_err!("Unknown syscall name '{}'", name)),
+            }
+        }
+        self.inferior.catch_syscalls(syscalls);
+        Ok(())
+    }
+
+    // handle <signal...|all> <stop|nostop|print|noprint|pass|nopass...>
+    fn handle_signals(&mut self, args: &str) -> Result<(), failure::Error> {
+        let mut sigs = Vec::new();
+        let mut actions = Vec::new();
+        for arg in args.split_whitespace() {
+            if arg == "all" {
+                sigs.extend(Signal::iterator()
+                    .filter(|s| *s != Signal::SIGTRAP && *s != Signal::SIGINT));
+            } else if let Some(sig) = parse_signal(arg) {
+                sigs.push(sig);
+            } else {
+                actions.push(arg);
+            }
+        }
+        self.inferior.handle_signals(&sigs, &actions)
+    }
+
+    // info registers [name...]
+    fn show_registers(&self, args: &str) {
+        if self.inferior.state != InferiorState::Stopped {
+            println!("The program has no registers now.");
+            return;
+        }
+        let names: Vec<&str> = args.split_whitespace().collect();
+        if let Err(e) = self.inferior.show_registers(&names) { println!("Register Error: {}", e); }
+    }
+
+    fn info(&mut self, args: &str) {
+        let (what, rest) = split_first_word(args);
+
+        match what {
+            "breakpoints" | "break" | "b" => self.inferior.show_breakpoints(),
+            "threads" => self.inferior.show_threads(),
+            "registers" | "reg" | "r" => self.show_registers(rest),
+            "float" | "vector" => {
+                if self.inferior.state != InferiorState::Stopped {
+                    println!("The program has no registers now.");
+                    return;
+                }
+                let res = if what == "float" { self.inferior.show_float() } else { self.inferior.show_vector() };
+                if let Err(e) = res { println!("Register Error: {}", e); }
+            },
+            "launch" => self.inferior.show_launch(),
+            "sharedlibrary" | "shared" | "dll" => self.inferior.show_shared_libraries(),
+            "signals" | "handle" => {
+                match rest.trim() {
+                    "" => self.inferior.show_signals(&[]),
+                    name => match parse_signal(name) {
+                        Some(sig) => self.inferior.show_signals(&[sig]),
+                        None => println!("Unknown signal: {}", name),
+                    },
+                }
+            },
+            _ => println!("Unknown info command: \"{}\"", what),
+        }
+    }
+
     fn show_context(&mut self) -> Result<(), failure::Error> {
         let cli = Cli::from_args();
 
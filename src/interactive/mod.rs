@@ -14,6 +14,8 @@ pub fn main() -> Result<(), failure::Error> {
     // Intialize fresh rustdbg interface
     // let rdbg = Menu::new().unwrap().expect("Unable to initialize rustdbg interface.");
     let mut rdbg = Box::new(Menu::new().expect("Unable to create menu"));
+    // Act on command line options (ex: `--pid`)
+    rdbg.startup();
     // Start command loop to get user input.
     rdbg.cmdloop()?;
     Ok(())
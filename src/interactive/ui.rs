@@ -116,7 +116,16 @@ where B: Backend,
     //     .split(chunks[1]);
 
 
-    Paragraph::new([Text::raw( "Testing" )].iter())
+    // Changed registers in red, same as `info registers`
+    let changed_style = Style::default().fg(Color::Red).modifier(Modifier::BOLD);
+    let registers: Vec<Text> = match rdbg.inferior.register_rows(&[]) {
+        Ok(rows) => rows.iter().map(|row| {
+            let line = format!("{:<8}{}\n", row.name, row.hex());
+            if row.changed { Text::styled(line, changed_style) } else { Text::styled(line, normal_style) }
+        }).collect(),
+        Err(e) => vec![Text::raw(e.to_string())],
+    };
+    Paragraph::new(registers.iter())
         .block(
             Block::default()
                 .borders(Borders::ALL)
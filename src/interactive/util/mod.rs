@@ -8,3 +8,39 @@ pub fn split_first_word(s: &str) -> (&str, &str) {
         None => (s, "")
     }
 }
+
+// Split a command line into words, honouring '...', "..." and backslash
+// escapes the way a shell would
+pub fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = s.chars();
+
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                if let Some(next) = chars.next() { cur.push(next); }
+            },
+            (Some(_), c) => cur.push(c),
+            (None, '\'') | (None, '"') => { quote = Some(ch); in_word = true; },
+            (None, '\\') => {
+                if let Some(next) = chars.next() { cur.push(next); }
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut cur));
+                    in_word = false;
+                }
+            },
+            (None, c) => { cur.push(c); in_word = true; },
+        }
+    }
+    if in_word {
+        args.push(cur);
+    }
+    args
+}
@@ -0,0 +1,76 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::demangle::{demangle, parse_demangle_mode, symbol_matches, DemangleMode};
+use rustdbg::inferior::symbols::{SymKind, Symbol, SymbolTable};
+
+#[test]
+fn rust_legacy() {
+    let name = "_ZN6twelve4main17h0123456789abcdefE";
+    assert_eq!(demangle(name, DemangleMode::On), "twelve::main::h0123456789abcdef");
+    assert_eq!(demangle(name, DemangleMode::NoHash), "twelve::main");
+    assert_eq!(demangle(name, DemangleMode::Off), name);
+    assert_eq!(demangle("_ZN4test1a2bcE", DemangleMode::NoHash), "test::a::bc");
+}
+
+#[test]
+fn rust_legacy_generics() {
+    let name = "_ZN4core3ptr40drop_in_place$LT$std..rt..lang_start$GT$17h0123456789abcdefE";
+    assert_eq!(demangle(name, DemangleMode::On),
+               "core::ptr::drop_in_place<std::rt::lang_start>::h0123456789abcdef");
+    assert_eq!(demangle(name, DemangleMode::NoHash), "core::ptr::drop_in_place<std::rt::lang_start>");
+
+    // An LLVM suffix doesn't make it C++
+    let name = "_ZN6twelve4main17h0123456789abcdefE.llvm.1A2B3C";
+    assert!(demangle(name, DemangleMode::NoHash).starts_with("twelve::main"));
+}
+
+#[test]
+fn rust_v0() {
+    assert_eq!(demangle("_RNvC6_123foo3bar", DemangleMode::On), "123foo::bar");
+    assert_eq!(demangle("_RNvCs1234_7mycrate3foo", DemangleMode::NoHash), "mycrate::foo");
+    assert!(demangle("_RNvCs1234_7mycrate3foo", DemangleMode::On).starts_with("mycrate["));
+}
+
+#[test]
+fn cpp() {
+    assert_eq!(demangle("_Z3addii", DemangleMode::On), "add(int, int)");
+    assert_eq!(demangle("_ZN3foo3barEv", DemangleMode::NoHash), "foo::bar()");
+    assert_eq!(demangle("_ZN3foo3barEv", DemangleMode::Off), "_ZN3foo3barEv");
+}
+
+#[test]
+fn plain_names() {
+    assert_eq!(demangle("main", DemangleMode::On), "main");
+    assert_eq!(demangle("_start", DemangleMode::NoHash), "_start");
+}
+
+#[test]
+fn modes() {
+    assert_eq!(parse_demangle_mode("on"), Some(DemangleMode::On));
+    assert_eq!(parse_demangle_mode(" off "), Some(DemangleMode::Off));
+    assert_eq!(parse_demangle_mode("nohash"), Some(DemangleMode::NoHash));
+    assert_eq!(parse_demangle_mode("maybe"), None);
+}
+
+#[test]
+fn matching() {
+    let name = "_ZN6twelve4main17h0123456789abcdefE";
+    assert!(symbol_matches(name, name));
+    assert!(symbol_matches(name, "twelve::main"));
+    assert!(symbol_matches(name, "twelve::main::h0123456789abcdef"));
+    assert!(!symbol_matches(name, "twelve"));
+    assert!(symbol_matches("_ZN3foo3barEi", "foo::bar"));
+    assert!(!symbol_matches("_ZN3foo3barEi", "foo::ba"));
+    assert!(!symbol_matches("main", "twelve::main"));
+}
+
+#[test]
+fn find_by_path() {
+    let table = SymbolTable::new(vec![
+        Symbol { name: "main".to_string(), offset: 0x1000, size: 0x10, kind: SymKind::Func },
+        Symbol { name: "_ZN6twelve4main17h0123456789abcdefE".to_string(), offset: 0x1100, size: 0x20, kind: SymKind::Func },
+    ]);
+    assert_eq!(table.find("main").map(|s| s.offset), Some(0x1000));
+    assert_eq!(table.find("twelve::main").map(|s| s.offset), Some(0x1100));
+    assert!(table.find("twelve::foo").is_none());
+}
@@ -0,0 +1,21 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::examine::{hexdump_line, parse_x_format, XFmt, XFormat};
+
+#[test]
+fn x_formats() {
+    assert_eq!(parse_x_format("").unwrap(), XFormat { count: 1, fmt: XFmt::Hex, size: 4 });
+    assert_eq!(parse_x_format("4xg").unwrap(), XFormat { count: 4, fmt: XFmt::Hex, size: 8 });
+    assert_eq!(parse_x_format("8bd").unwrap(), XFormat { count: 8, fmt: XFmt::Decimal, size: 1 });
+    assert_eq!(parse_x_format("s").unwrap(), XFormat { count: 1, fmt: XFmt::Str, size: 1 });
+    assert_eq!(parse_x_format("10ig").unwrap(), XFormat { count: 10, fmt: XFmt::Insn, size: 1 });
+    assert!(parse_x_format("4q").is_err());
+}
+
+#[test]
+fn hexdump_rows() {
+    assert_eq!(hexdump_line(0x10, b"ABCDEFGH\x00\x01ijklmn"),
+               "00000010  41 42 43 44 45 46 47 48  00 01 69 6a 6b 6c 6d 6e  |ABCDEFGH..ijklmn|");
+    assert_eq!(hexdump_line(0, b"hi\n"),
+               format!("00000000  68 69 0a {}|hi.|", " ".repeat(3 * 13 + 2)));
+}
@@ -0,0 +1,60 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::expr::{self, Expr, ExprContext};
+
+struct Regs;
+
+impl ExprContext for Regs {
+    fn register(&self, name: &str) -> Result<u64, failure::Error> {
+        match name {
+            "rax" => Ok(5),
+            "rsp" => Ok(0x1000),
+            _ => Err(failure::format_err!("Invalid register")),
+        }
+    }
+
+    fn memory(&self, addr: u64, size: usize) -> Result<u64, failure::Error> {
+        // Every byte reads back as the low byte of its address
+        Ok((0..size as u64).fold(0, |v, i| v | (((addr + i) & 0xff) << (i * 8))))
+    }
+}
+
+fn eval(s: &str) -> u64 {
+    expr::parse(s).unwrap().eval(&Regs).unwrap()
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(eval("1 + 2 * 3"), 7);
+    assert_eq!(eval("(1 + 2) * 3"), 9);
+    assert_eq!(eval("0x10 >> 2 == 4 && 1"), 1);
+}
+
+#[test]
+fn registers_and_memory() {
+    assert_eq!(eval("$rax == 5"), 1);
+    assert_eq!(eval("$RAX - 6"), !0);
+    assert_eq!(eval("*(u8*)($rsp + 0x41)"), 0x41);
+    assert_eq!(eval("*(u16*)0x1234"), 0x3534);
+}
+
+#[test]
+fn errors() {
+    assert!(expr::parse("1 +").is_err());
+    assert!(expr::parse("*(float*)0").is_err());
+    assert!(expr::parse("$rbx").unwrap().eval(&Regs).is_err());
+}
+
+#[test]
+fn assignments() {
+    let (target, value) = expr::parse_assignment("$rax = 0x41").unwrap();
+    assert_eq!(target, Expr::Reg("rax".into()));
+    assert_eq!(value.eval(&Regs).unwrap(), 0x41);
+
+    let (target, value) = expr::parse_assignment("*(u32*)0x601040 = $rax == 5").unwrap();
+    assert_eq!(target, Expr::Deref(Box::new(Expr::Num(0x601040)), 4));
+    assert_eq!(value.eval(&Regs).unwrap(), 1);
+
+    assert!(expr::parse_assignment("$rax == 5").is_err());
+    assert!(expr::parse_assignment("1 + 2 = 3").is_err());
+}
@@ -0,0 +1,57 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::fpregs::*;
+
+#[test]
+fn names() {
+    assert_eq!(parse_vector_name("$xmm15"), Some((VecKind::Xmm, 15)));
+    assert_eq!(parse_vector_name("zmm31"), Some((VecKind::Zmm, 31)));
+    assert_eq!(parse_vector_name("k7"), Some((VecKind::K, 7)));
+    assert_eq!(parse_vector_name("xmm32"), None);
+    assert_eq!(parse_vector_name("k"), None);
+    assert_eq!(parse_st_name("st(3)"), Some(3));
+    assert!(is_fp_register("$mxcsr"));
+    assert!(!is_fp_register("rax"));
+}
+
+#[test]
+fn x87_conversion() {
+    for &v in &[0.0, 1.0, -2.5, std::f64::consts::PI, 1e300, 5e-324, f64::INFINITY] {
+        assert_eq!(x87_to_f64(&f64_to_x87(v)), v);
+    }
+    assert_eq!(f64_to_x87(1.0), [0, 0, 0, 0, 0, 0, 0, 0x80, 0xff, 0x3f]);
+}
+
+#[test]
+fn lanes() {
+    assert_eq!(parse_lane_view("v4_float", 16), Some(Lane::Float));
+    assert_eq!(parse_lane_view("v8_float", 16), None);
+    assert_eq!(parse_lane_view("v2_int128", 32), Some(Lane::Int128));
+
+    let bytes = encode_lanes("{1, 2, -1, 0x10}", Lane::Int32, 16).unwrap();
+    assert_eq!(format_lanes(&bytes, Lane::Int32), "{0x1, 0x2, 0xffffffff, 0x10}");
+    assert_eq!(format_lanes(&bytes, Lane::Int64), "{0x200000001, 0x10ffffffff}");
+
+    let bytes = encode_lanes("{1.5, -2}", Lane::Double, 16).unwrap();
+    assert_eq!(format_lanes(&bytes, Lane::Double), "{1.5, -2}");
+    assert!(encode_lanes("{1, 2}", Lane::Int32, 16).is_err());
+    assert!(encode_lanes("{256, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0}", Lane::Int8, 16).is_err());
+}
+
+#[test]
+fn fxsave_area() {
+    let mut regs = FpRegs::from_bytes(vec![0; 512], false);
+    regs.set_control("mxcsr", 0x1f80).unwrap();
+    assert_eq!(regs.mxcsr(), 0x1f80);
+    assert_eq!(decode_mxcsr(0x1f80), "[ IM DM ZM OM UM PM RC=nearest ]");
+
+    regs.set_st(0, 2.5);
+    assert!(regs.st_valid(0) && !regs.st_valid(1));
+    assert_eq!(regs.st(0), 2.5);
+
+    regs.set_vector(VecKind::Xmm, 3, &[0x41; 16]).unwrap();
+    assert_eq!(regs.vector(VecKind::Xmm, 3).unwrap(), vec![0x41; 16]);
+    assert_eq!(regs.bytes()[160 + 3 * 16], 0x41);
+    assert!(regs.vector(VecKind::Ymm, 0).is_err());
+    assert!(regs.set_control("fcw", 0x10000).is_err());
+}
@@ -0,0 +1,13 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::launch::parse_rlimit_value;
+
+#[test]
+fn rlimit_values() {
+    assert_eq!(parse_rlimit_value("0"), Some(0));
+    assert_eq!(parse_rlimit_value("8m"), Some(8 << 20));
+    assert_eq!(parse_rlimit_value("16K"), Some(16 << 10));
+    assert_eq!(parse_rlimit_value("unlimited"), Some(libc::RLIM_INFINITY));
+    assert_eq!(parse_rlimit_value("lots"), None);
+    assert_eq!(parse_rlimit_value(""), None);
+}
@@ -0,0 +1,89 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::expr::Expr;
+use rustdbg::inferior::lines::{line_address, parse_line_programs};
+use rustdbg::inferior::location::{parse_location, Location};
+use rustdbg::inferior::pending::{ModuleLocation, ModuleTarget};
+
+fn module(module: &str, target: ModuleTarget) -> Location {
+    Location::Module(ModuleLocation { module: module.to_string(), target })
+}
+
+#[test]
+fn locations() {
+    assert_eq!(parse_location("0x401000").unwrap(), Location::Address(0x401000));
+    assert_eq!(parse_location("401000").unwrap(), Location::Address(0x401000));
+    assert_eq!(parse_location("*$rsp").unwrap(), Location::Expr(Expr::Reg("rsp".to_string())));
+    assert_eq!(parse_location("main").unwrap(), Location::Symbol("main".to_string(), 0));
+    assert_eq!(parse_location("add").unwrap(), Location::Symbol("add".to_string(), 0));
+    assert_eq!(parse_location("main+0x10").unwrap(), Location::Symbol("main".to_string(), 0x10));
+    assert_eq!(parse_location("ns::func").unwrap(), Location::Symbol("ns::func".to_string(), 0));
+    assert_eq!(parse_location("libc.so.6!malloc+4").unwrap(),
+               module("libc.so.6", ModuleTarget::Symbol("malloc".to_string(), 4)));
+    assert_eq!(parse_location("libc.so.6+0x1234").unwrap(), module("libc.so.6", ModuleTarget::Offset(0x1234)));
+    assert_eq!(parse_location("main.c:42").unwrap(), Location::Line("main.c".to_string(), 42));
+    assert_eq!(parse_location("src/main.c:7").unwrap(), Location::Line("src/main.c".to_string(), 7));
+
+    // Anything else is an expression
+    assert!(matches!(parse_location("$rsp + 8").unwrap(), Location::Expr(_)));
+    assert!(matches!(parse_location("0x1000+8").unwrap(), Location::Expr(_)));
+    assert!(parse_location("").is_err());
+}
+
+#[test]
+fn location_display() {
+    for loc in &["0x401000", "main", "main+0x10", "libc.so.6!malloc+0x4", "libc.so.6+0x1234", "main.c:42"] {
+        assert_eq!(parse_location(loc).unwrap().to_string(), *loc);
+    }
+}
+
+/* A DWARF 4 line program for src/main.c: line 10 at 0x1000, line 11 at
+ * 0x1004, ending at 0x1006 */
+fn line_program() -> Vec<u8> {
+    let mut header = vec![
+        1,              // minimum_instruction_length
+        1,              // maximum_operations_per_instruction
+        1,              // default_is_stmt
+        0xfb,           // line_base -5
+        14,             // line_range
+        13,             // opcode_base
+        0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1,
+    ];
+    header.extend_from_slice(b"src\0\0");
+    header.extend_from_slice(b"main.c\0\x01\0\0\0");
+
+    let mut program = vec![0x00, 9, 0x02];          // DW_LNE_set_address
+    program.extend_from_slice(&0x1000u64.to_le_bytes());
+    program.extend_from_slice(&[0x03, 9]);          // DW_LNS_advance_line 9
+    program.push(0x01);                             // DW_LNS_copy
+    program.push(13 + (1 + 5) + 14 * 4);            // Special: +4 bytes, +1 line
+    program.extend_from_slice(&[0x02, 2]);          // DW_LNS_advance_pc 2
+    program.extend_from_slice(&[0x00, 1, 0x01]);    // DW_LNE_end_sequence
+
+    let mut unit = 4u16.to_le_bytes().to_vec();
+    unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    unit.extend(header);
+    unit.extend(program);
+
+    let mut data = (unit.len() as u32).to_le_bytes().to_vec();
+    data.extend(unit);
+    data
+}
+
+#[test]
+fn line_table() {
+    let rows = parse_line_programs(&line_program(), &[], &[]);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].file, "src/main.c");
+    assert_eq!((rows[0].address, rows[0].line), (0x1000, 10));
+    assert_eq!((rows[1].address, rows[1].line), (0x1004, 11));
+    assert!(rows[2].end_sequence);
+    assert_eq!(rows[2].address, 0x1006);
+
+    assert_eq!(line_address(&rows, "main.c", 10), Some(0x1000));
+    assert_eq!(line_address(&rows, "src/main.c", 11), Some(0x1004));
+    // No code on line 9, the next line with code is used
+    assert_eq!(line_address(&rows, "main.c", 9), Some(0x1000));
+    assert_eq!(line_address(&rows, "main.c", 12), None);
+    assert_eq!(line_address(&rows, "other.c", 10), None);
+}
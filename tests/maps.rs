@@ -0,0 +1,53 @@
+extern crate rustdbg;
+
+use procfs::{ MemoryMap, MMapPath };
+use rustdbg::inferior::maps::{classify, MemoryMapList, Region};
+
+fn map(start: u64, end: u64, perms: &str, pathname: MMapPath) -> MemoryMap {
+    MemoryMap {
+        address: (start, end),
+        perms: perms.to_string(),
+        offset: 0,
+        dev: (0, 0),
+        inode: 0,
+        pathname,
+    }
+}
+
+fn maps() -> MemoryMapList {
+    MemoryMapList::from_maps(vec![
+        map(0x7ffffffde000, 0x7ffffffff000, "rw-p", MMapPath::Stack),
+        map(0x400000, 0x401000, "r-xp", MMapPath::Path("/bin/true".into())),
+        map(0x401000, 0x402000, "r--p", MMapPath::Path("/bin/true".into())),
+        map(0x600000, 0x621000, "rw-p", MMapPath::Heap),
+        map(0x7f0000000000, 0x7f0000001000, "rw-p", MMapPath::Anonymous),
+    ])
+}
+
+#[test]
+fn lookup() {
+    let maps = maps();
+    assert_eq!(maps.find(0x400000).unwrap().address, (0x400000, 0x401000));
+    assert_eq!(maps.find(0x401fff).unwrap().address, (0x401000, 0x402000));
+    assert!(maps.find(0x402000).is_none());
+    assert!(maps.find(0x1000).is_none());
+    assert!(maps.has_perms(0x400010, "rx"));
+    assert!(!maps.has_perms(0x401010, "w"));
+}
+
+#[test]
+fn ranges() {
+    let maps = maps();
+    assert!(maps.check_range(0x400ff0, 0x20).is_ok());
+    let err = maps.check_range(0x401ff0, 0x20).unwrap_err();
+    assert_eq!(err.to_string(), "Address 0x402000 is not mapped");
+    assert!(maps.check_range(0x10, 1).is_err());
+}
+
+#[test]
+fn regions() {
+    let maps = maps();
+    let regions: Vec<Region> = maps.iter().map(classify).collect();
+    assert_eq!(regions, vec![Region::Code, Region::Module, Region::Heap, Region::Anon, Region::Stack]);
+    assert_eq!(maps.region(0x7ffffffdf000), Some(Region::Stack));
+}
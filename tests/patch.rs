@@ -0,0 +1,13 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::patch::parse_hex_bytes;
+
+#[test]
+fn hex_bytes() {
+    assert_eq!(parse_hex_bytes("41 42 43").unwrap(), vec![0x41, 0x42, 0x43]);
+    assert_eq!(parse_hex_bytes("90909090").unwrap(), vec![0x90; 4]);
+    assert_eq!(parse_hex_bytes("0xcc \\xc3").unwrap(), vec![0xcc, 0xc3]);
+    assert!(parse_hex_bytes("414").is_err());
+    assert!(parse_hex_bytes("zz").is_err());
+    assert!(parse_hex_bytes("").is_err());
+}
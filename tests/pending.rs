@@ -0,0 +1,41 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::pending::{module_matches, parse_module_location, ModuleLocation, ModuleTarget};
+
+fn loc(module: &str, target: ModuleTarget) -> Option<ModuleLocation> {
+    Some(ModuleLocation { module: module.to_string(), target })
+}
+
+#[test]
+fn module_locations() {
+    assert_eq!(parse_module_location("libfoo.so+0x1234"), loc("libfoo.so", ModuleTarget::Offset(0x1234)));
+    assert_eq!(parse_module_location("libfoo.so+16"), loc("libfoo.so", ModuleTarget::Offset(16)));
+    assert_eq!(parse_module_location("libfoo.so!func"),
+               loc("libfoo.so", ModuleTarget::Symbol("func".to_string(), 0)));
+    assert_eq!(parse_module_location("libc.so.6!malloc+0x10"),
+               loc("libc.so.6", ModuleTarget::Symbol("malloc".to_string(), 0x10)));
+
+    // Plain addresses and expressions aren't module relative
+    assert_eq!(parse_module_location("0x401000"), None);
+    assert_eq!(parse_module_location("401000+0x10"), None);
+    assert_eq!(parse_module_location("$rip+4"), None);
+    assert_eq!(parse_module_location("libfoo.so+zz"), None);
+    assert_eq!(parse_module_location("!func"), None);
+    assert_eq!(parse_module_location("libfoo.so!"), None);
+}
+
+#[test]
+fn module_location_display() {
+    assert_eq!(parse_module_location("libfoo.so+4660").unwrap().to_string(), "libfoo.so+0x1234");
+    assert_eq!(parse_module_location("libfoo.so!func").unwrap().to_string(), "libfoo.so!func");
+    assert_eq!(parse_module_location("libfoo.so!func+16").unwrap().to_string(), "libfoo.so!func+0x10");
+}
+
+#[test]
+fn module_names() {
+    assert!(module_matches("libc.so.6", "libc.so.6"));
+    assert!(module_matches("libc.so.6", "libc.so"));
+    assert!(module_matches("libc.so.6", "libc"));
+    assert!(!module_matches("libcrypto.so.1.1", "libc"));
+    assert!(!module_matches("libc.so.6", "libc.so.6.1"));
+}
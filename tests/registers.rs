@@ -0,0 +1,58 @@
+extern crate rustdbg;
+
+use libc::user_regs_struct;
+use rustdbg::inferior::registers::{decode_rflags, lookup, read_register, register_rows, write_register};
+
+fn regs() -> user_regs_struct {
+    let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+    regs.rax = 0x1122334455667788;
+    regs.r8 = 0xffff_ffff_ffff_ffff;
+    regs.eflags = 0x246;
+    regs
+}
+
+#[test]
+fn sub_registers() {
+    let regs = regs();
+    assert_eq!(read_register(&regs, "rax"), Some(0x1122334455667788));
+    assert_eq!(read_register(&regs, "eax"), Some(0x55667788));
+    assert_eq!(read_register(&regs, "ax"), Some(0x7788));
+    assert_eq!(read_register(&regs, "al"), Some(0x88));
+    assert_eq!(read_register(&regs, "ah"), Some(0x77));
+    assert_eq!(read_register(&regs, "r8d"), Some(0xffff_ffff));
+    assert_eq!(read_register(&regs, "$RAX"), Some(0x1122334455667788));
+    assert_eq!(lookup("pc").unwrap().full, "rip");
+    assert!(lookup("xax").is_none());
+}
+
+#[test]
+fn writes_keep_other_bits() {
+    let mut regs = regs();
+    write_register(&mut regs, "ah", 0x41).unwrap();
+    assert_eq!(regs.rax, 0x1122334455664188);
+    write_register(&mut regs, "r8w", 0).unwrap();
+    assert_eq!(regs.r8, 0xffff_ffff_ffff_0000);
+    write_register(&mut regs, "eax", -1i64 as u64).unwrap();
+    assert_eq!(regs.rax, 0x11223344ffffffff);
+    assert!(write_register(&mut regs, "al", 0x100).is_err());
+}
+
+#[test]
+fn rflags() {
+    assert_eq!(decode_rflags(0x246), "[ PF ZF IF ]");
+    assert_eq!(decode_rflags(0), "[  ]");
+}
+
+#[test]
+fn changed_rows() {
+    let prev = regs();
+    let mut now = regs();
+    now.rax += 1;
+
+    let rows = register_rows(&now, Some(&prev), &["rax", "al", "r8", "eflags"]).unwrap();
+    let changed: Vec<bool> = rows.iter().map(|r| r.changed).collect();
+    assert_eq!(changed, vec![true, true, false, false]);
+    assert_eq!(rows[3].natural(), "[ PF ZF IF ]");
+    assert_eq!(rows[1].hex(), "0x89");
+    assert!(register_rows(&now, None, &["nope"]).is_err());
+}
@@ -1,5 +1,10 @@
 use std::process::exit;
 
+#[inline(never)]
+pub fn foo() -> i32 {
+    12
+}
+
 pub fn main() {
-    exit(12);
+    exit(foo());
 }
@@ -0,0 +1,36 @@
+extern crate rustdbg;
+
+use nix::sys::signal::Signal;
+use rustdbg::inferior::signals::{default_signal_policies, parse_signal, SignalPolicy};
+
+#[test]
+fn parse_names_and_numbers() {
+    assert_eq!(parse_signal("SIGSEGV"), Some(Signal::SIGSEGV));
+    assert_eq!(parse_signal("segv"), Some(Signal::SIGSEGV));
+    assert_eq!(parse_signal("11"), Some(Signal::SIGSEGV));
+    assert_eq!(parse_signal("SIGNOPE"), None);
+    assert_eq!(parse_signal("stop"), None);
+}
+
+#[test]
+fn actions_imply_each_other() {
+    let mut policy = SignalPolicy { stop: false, print: false, pass: true };
+    policy.apply("stop").unwrap();
+    assert!(policy.stop && policy.print);
+
+    policy.apply("noprint").unwrap();
+    assert!(!policy.stop && !policy.print);
+
+    policy.apply("nopass").unwrap();
+    assert!(!policy.pass);
+
+    assert!(policy.apply("sometimes").is_err());
+}
+
+#[test]
+fn defaults() {
+    let policies = default_signal_policies();
+    assert_eq!(policies[&Signal::SIGSEGV], SignalPolicy { stop: true, print: true, pass: true });
+    assert_eq!(policies[&Signal::SIGCHLD], SignalPolicy { stop: false, print: false, pass: true });
+    assert!(!policies[&Signal::SIGINT].pass);
+}
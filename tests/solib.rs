@@ -0,0 +1,56 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::solib::parse_auxv;
+use rustdbg::inferior::Inferior;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn entry(key: u64, val: u64) -> Vec<u8> {
+    let mut bytes = key.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&val.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn auxv() {
+    let mut data = Vec::new();
+    data.extend(entry(3, 0x555555554040));  // AT_PHDR
+    data.extend(entry(5, 13));              // AT_PHNUM
+    data.extend(entry(9, 0x555555555060));  // AT_ENTRY
+    data.extend(entry(0, 0));               // AT_NULL
+    data.extend(entry(7, 0xdead));          // Past the end
+
+    let auxv = parse_auxv(&data);
+    assert_eq!(auxv.len(), 3);
+    assert_eq!(auxv[&3], 0x555555554040);
+    assert_eq!(auxv[&5], 13);
+    assert_eq!(auxv[&9], 0x555555555060);
+    assert!(!auxv.contains_key(&7));
+
+    // A truncated entry is ignored
+    assert_eq!(parse_auxv(&data[..20]).len(), 1);
+}
+
+#[test]
+fn modload_callbacks() {
+    let loaded = Rc::new(RefCell::new(Vec::new()));
+    let nested = Rc::new(RefCell::new(None));
+
+    let mut inferior = Inferior::new();
+    let seen = loaded.clone();
+    inferior.register_modload_callback(Box::new(move |_, name, base| {
+        seen.borrow_mut().push((name.to_string(), base));
+    })).unwrap();
+    let result = nested.clone();
+    inferior.register_modload_callback(Box::new(move |inferior, _, _| {
+        let res = inferior.register_modload_callback(Box::new(|_, _, _| ()));
+        *result.borrow_mut() = Some(res.is_err());
+    })).unwrap();
+
+    // Nothing is mapped, so the module is named after its base
+    inferior.register_module(0x7000);
+    assert_eq!(*loaded.borrow(), vec![(String::from("0x7000"), 0x7000)]);
+    // Callbacks can't be added while they run
+    assert_eq!(*nested.borrow(), Some(true));
+}
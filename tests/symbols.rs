@@ -0,0 +1,47 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::symbols::{SymKind, Symbol, SymbolTable};
+
+fn sym(name: &str, offset: usize, size: usize, kind: SymKind) -> Symbol {
+    Symbol { name: name.to_string(), offset, size, kind }
+}
+
+fn table() -> SymbolTable {
+    SymbolTable::new(vec![
+        sym("main", 0x1140, 0x20, SymKind::Func),
+        sym("_start", 0x1040, 0x2f, SymKind::Func),
+        sym("data_start", 0x4000, 0, SymKind::Other),
+        sym("counter", 0x4010, 4, SymKind::Object),
+        // An alias at the same address as main
+        sym("main_alias", 0x1140, 0, SymKind::Other),
+        sym("main", 0x1140, 0x20, SymKind::Func),
+    ])
+}
+
+#[test]
+fn sorted_and_deduplicated() {
+    let table = table();
+    let names: Vec<&str> = table.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["_start", "main", "main_alias", "data_start", "counter"]);
+    assert_eq!(table.len(), 5);
+    assert_eq!(table.find("counter").map(|s| s.offset), Some(0x4010));
+    assert!(table.find("missing").is_none());
+}
+
+#[test]
+fn lookup() {
+    let table = table();
+    let name = |off| table.lookup(off).map(|(s, off)| (s.name.clone(), off));
+
+    assert_eq!(name(0x1000), None);
+    assert_eq!(name(0x1040), Some(("_start".to_string(), 0)));
+    assert_eq!(name(0x1050), Some(("_start".to_string(), 0x10)));
+    // Functions win over other symbols at the same address
+    assert_eq!(name(0x1144), Some(("main".to_string(), 4)));
+    // Past the end of main only the unsized alias is left
+    assert_eq!(name(0x1170), Some(("main_alias".to_string(), 0x30)));
+    // Unsized symbols run up to the next one
+    assert_eq!(name(0x4008), Some(("data_start".to_string(), 8)));
+    assert_eq!(name(0x4012), Some(("counter".to_string(), 2)));
+    assert_eq!(name(0x4014), None);
+}
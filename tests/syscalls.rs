@@ -0,0 +1,27 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::syscalls::{parse_syscall, syscall_name, Catchpoint};
+
+#[test]
+fn names_and_numbers() {
+    assert_eq!(parse_syscall("write"), Some(1));
+    assert_eq!(parse_syscall("openat"), Some(257));
+    assert_eq!(parse_syscall("59"), Some(59));
+    assert_eq!(parse_syscall("notacall"), None);
+    assert_eq!(syscall_name(60), Some("exit"));
+    assert_eq!(syscall_name(9999), None);
+}
+
+#[test]
+fn catchpoint_matching() {
+    let any = Catchpoint { id: 1, syscalls: vec![], enabled: true, freq: 0 };
+    assert!(any.matches(0) && any.matches(231));
+    assert_eq!(any.describe(), "any syscall");
+
+    let mut some = Catchpoint { id: 2, syscalls: vec![1, 0], enabled: true, freq: 0 };
+    assert!(some.matches(1) && !some.matches(2));
+    assert_eq!(some.describe(), "syscalls 'write' [1] 'read' [0]");
+
+    some.enabled = false;
+    assert!(!some.matches(1));
+}
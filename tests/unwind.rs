@@ -0,0 +1,127 @@
+extern crate rustdbg;
+
+use rustdbg::inferior::cfi::{
+    eval_expression, unwind_regs, CallFrameTable, CfaRule, RegRule, DW_RA, DW_RBP, DW_RSP, REG_COUNT,
+};
+
+/// Link time address of the section data
+const EH_FRAME: u64 = 0x2000;
+
+fn entry(body: &[u8]) -> Vec<u8> {
+    let mut data = (body.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(body);
+    data
+}
+
+/// One CIE and an FDE for `push rbp; mov rbp, rsp; ...; ret` at 0x1000
+fn eh_frame() -> Vec<u8> {
+    let cie = entry(&[
+        0, 0, 0, 0,             // CIE id
+        1, b'z', b'R', 0,       // version, augmentation
+        0x01, 0x78, 0x10,       // code align 1, data align -8, return address r16
+        0x01, 0x1b,             // augmentation data: pcrel sdata4 pointers
+        0x0c, 0x07, 0x08,       // def_cfa rsp+8
+        0x90, 0x01,             // offset r16 at cfa-8
+        0x00, 0x00,
+    ]);
+
+    let fde_pos = cie.len() as u64;
+    let id_pos = fde_pos + 4;
+    let start = 0x1000 - (EH_FRAME + id_pos + 4) as i32;
+    let mut body = (id_pos as u32).to_le_bytes().to_vec();
+    body.extend_from_slice(&start.to_le_bytes());
+    body.extend_from_slice(&0x20u32.to_le_bytes());
+    body.extend_from_slice(&[
+        0x00,                   // no augmentation data
+        0x41, 0x0e, 0x10,       // +1: def_cfa_offset 16
+        0x86, 0x02,             // rbp at cfa-16
+        0x43, 0x0d, 0x06,       // +3: def_cfa_register rbp
+        0x58, 0x0c, 0x07, 0x08, // +0x18: def_cfa rsp+8
+        0x00, 0x00, 0x00,
+    ]);
+
+    let mut data = cie;
+    data.extend(entry(&body));
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data
+}
+
+fn table() -> CallFrameTable {
+    let mut table = CallFrameTable::new(0);
+    table.add_section(&eh_frame(), EH_FRAME, true);
+    table
+}
+
+#[test]
+fn rows() {
+    let table = table();
+    assert_eq!(table.len(), 1);
+    assert!(table.row(0xfff).is_none());
+    assert!(table.row(0x1020).is_none());
+
+    let row = table.row(0x1000).unwrap();
+    assert_eq!(row.cfa, CfaRule::RegOffset(DW_RSP, 8));
+    assert_eq!(row.regs[DW_RA], RegRule::Offset(-8));
+    assert_eq!(row.regs[DW_RBP], RegRule::SameValue);
+
+    let row = table.row(0x1001).unwrap();
+    assert_eq!(row.cfa, CfaRule::RegOffset(DW_RSP, 16));
+    assert_eq!(row.regs[DW_RBP], RegRule::Offset(-16));
+
+    assert_eq!(table.row(0x1010).unwrap().cfa, CfaRule::RegOffset(DW_RBP, 16));
+    assert_eq!(table.row(0x101c).unwrap().cfa, CfaRule::RegOffset(DW_RSP, 8));
+}
+
+#[test]
+fn load_address() {
+    // Same file linked at 0x400000, rows are still found by module offset
+    let mut table = CallFrameTable::new(0x40_0000);
+    table.add_section(&eh_frame(), EH_FRAME, true);
+    assert!(table.row(0x1000).is_none());
+
+    let mut table = CallFrameTable::new(0x1000);
+    table.add_section(&eh_frame(), EH_FRAME, true);
+    assert_eq!(table.row(0x4).unwrap().cfa, CfaRule::RegOffset(DW_RBP, 16));
+}
+
+#[test]
+fn unwind_through_frame() {
+    let row = table().row(0x1010).unwrap();
+    let mut regs = [None; REG_COUNT];
+    regs[DW_RA] = Some(0x1010);
+    regs[DW_RSP] = Some(0x6ff0);
+    regs[DW_RBP] = Some(0x7000);
+    regs[0] = Some(42);
+
+    let read = |addr: u64| match addr {
+        0x7008 => Some(0x4000),
+        0x7000 => Some(0x7100),
+        _ => None,
+    };
+    let (cfa, caller) = unwind_regs(&row, &regs, &read).unwrap();
+    assert_eq!(cfa, 0x7010);
+    assert_eq!(caller[DW_RA], Some(0x4000));
+    assert_eq!(caller[DW_RBP], Some(0x7100));
+    assert_eq!(caller[DW_RSP], Some(0x7010));
+    // Scratch registers don't survive a call
+    assert_eq!(caller[0], None);
+}
+
+#[test]
+fn plt_expression() {
+    // rsp + 8 + ((rip & 15) >= 11) << 3, the CFA of a PLT entry
+    let expr = [0x77, 0x08, 0x80, 0x00, 0x3f, 0x1a, 0x3b, 0x2a, 0x33, 0x24, 0x22];
+    let read = |_| None;
+    let mut regs = [None; REG_COUNT];
+    regs[DW_RSP] = Some(0x100);
+
+    regs[DW_RA] = Some(0x1010);
+    assert_eq!(eval_expression(&expr, &regs, None, &read), Some(0x108));
+    regs[DW_RA] = Some(0x101b);
+    assert_eq!(eval_expression(&expr, &regs, None, &read), Some(0x110));
+
+    // Unknown registers and memory fail the expression
+    regs[DW_RSP] = None;
+    assert_eq!(eval_expression(&expr, &regs, None, &read), None);
+    assert_eq!(eval_expression(&[0x30, 0x06], &regs, None, &read), None);
+}
@@ -0,0 +1,11 @@
+extern crate rustdbg;
+
+use rustdbg::interactive::util::split_args;
+
+#[test]
+fn split_words_and_quotes() {
+    assert_eq!(split_args("a  b\tc"), vec!["a", "b", "c"]);
+    assert_eq!(split_args("'hello world' \"x y\" z"), vec!["hello world", "x y", "z"]);
+    assert_eq!(split_args(r#"a\ b "q\"q" ''"#), vec!["a b", "q\"q", ""]);
+    assert!(split_args("   ").is_empty());
+}